@@ -1,6 +1,6 @@
 use criterion::{criterion_group, criterion_main, Criterion};
 use halo2_proofs::arithmetic::Field;
-use mimc_halo2::{mimc::primitives::{mimc5_hash_pallas, mimc5_encrypt_pallas, mimc5_hash_vesta, mimc5_encrypt_vesta}, mimc_feistel::primitives::{mimc5_feistel_hash_pallas, mimc5_feistel_encrypt_pallas, mimc5_feistel_hash_vesta, mimc5_feistel_encrypt_vesta}};
+use mimc_halo2::{mimc::primitives::{mimc5_hash_pallas, mimc5_encrypt_pallas, mimc5_hash_vesta, mimc5_encrypt_vesta, mimc5_hash_batch_pallas, mimc5_encrypt_batch_pallas}, mimc_feistel::primitives::{mimc5_feistel_hash_pallas, mimc5_feistel_encrypt_pallas, mimc5_feistel_hash_vesta, mimc5_feistel_encrypt_vesta, mimc5_feistel_hash_batch_pallas, mimc5_feistel_encrypt_batch_pallas}};
 use rand::rngs::OsRng;
 use pasta_curves::{pallas, vesta};
 
@@ -85,6 +85,69 @@ fn bench_primitives(c: &mut Criterion) {
                 mimc5_feistel_encrypt_vesta(&mut vesta_message_l, &mut vesta_message_r, vesta_key)
             })
         });
+    }
+
+    {
+        let mut group = c.benchmark_group("MiMCBatch");
+        group.sample_size(10);
+
+        let messages: Vec<pallas::Base> = (0..1 << 14).map(|_| pallas::Base::random(rng)).collect();
+        let keys: Vec<pallas::Base> = (0..1 << 14).map(|_| pallas::Base::random(rng)).collect();
+
+        group.bench_function("pallas_hash_one_by_one_16384", |b| {
+            b.iter(|| {
+                let mut states = messages.clone();
+                for state in states.iter_mut() {
+                    mimc5_hash_pallas(state);
+                }
+                states
+            })
+        });
+
+        group.bench_function("pallas_hash_batch_16384", |b| {
+            b.iter(|| {
+                let mut states = messages.clone();
+                mimc5_hash_batch_pallas(&mut states);
+                states
+            })
+        });
+
+        group.bench_function("pallas_encrypt_batch_16384", |b| {
+            b.iter(|| {
+                let mut states = messages.clone();
+                mimc5_encrypt_batch_pallas(&mut states, &keys);
+                states
+            })
+        });
+
+        let feistel_messages: Vec<(pallas::Base, pallas::Base)> =
+            messages.iter().map(|message| (*message, pallas::Base::random(rng))).collect();
+
+        group.bench_function("pallas_feistel_hash_one_by_one_16384", |b| {
+            b.iter(|| {
+                let mut states = feistel_messages.clone();
+                for (state_l, state_r) in states.iter_mut() {
+                    mimc5_feistel_hash_pallas(state_l, state_r);
+                }
+                states
+            })
+        });
+
+        group.bench_function("pallas_feistel_hash_batch_16384", |b| {
+            b.iter(|| {
+                let mut states = feistel_messages.clone();
+                mimc5_feistel_hash_batch_pallas(&mut states);
+                states
+            })
+        });
+
+        group.bench_function("pallas_feistel_encrypt_batch_16384", |b| {
+            b.iter(|| {
+                let mut states = feistel_messages.clone();
+                mimc5_feistel_encrypt_batch_pallas(&mut states, &keys);
+                states
+            })
+        });
     }}
 
 criterion_group!(benches, bench_primitives);
@@ -0,0 +1,181 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use halo2_proofs::{
+    arithmetic::Field,
+    circuit::{AssignedCell, Chip, Layouter, SimpleFloorPlanner, Value},
+    dev::MockProver,
+    plonk::{Advice, Circuit, Column, ConstraintSystem, Error},
+};
+use mimc_halo2::{
+    field::MiMCField,
+    mimc::{
+        mimc_hash::{MiMC5HashChip, MiMC5HashConfig},
+        primitives::mimc5_hash_trace,
+    },
+    mimc_feistel::mimc_feistel_hash::{MiMC5FeistelHashChip, MiMC5FeistelHashConfig},
+};
+use mimc_halo2::cost::chip_costs;
+use pasta_curves::Fp;
+use rand::rngs::OsRng;
+
+// Synthesis time of circuits that invoke a chip many times, measured with
+// MockProver::run, which synthesizes the circuit without checking it
+
+#[derive(Debug, Clone)]
+struct ManyHashesConfig<C> {
+    input: Column<Advice>,
+    chip_config: C,
+}
+
+struct ManyMiMC5Hashes<F: MiMCField> {
+    messages: Vec<F>,
+    // Baseline: recompute the rounds inside the region closure, as the chips
+    // did before computing the trace once per invocation
+    trace_in_closure: bool,
+}
+
+// `MiMC5HashChip::hash_message` as it was before the trace moved out of the
+// region closure: SimpleFloorPlanner runs the closure twice per region, and
+// the message value is known in both runs, so the trace is computed twice
+fn hash_message_trace_in_closure<F: MiMCField>(
+    chip: &MiMC5HashChip<F>,
+    mut layouter: impl Layouter<F>,
+    message: &AssignedCell<F, F>,
+) -> Result<AssignedCell<F, F>, Error> {
+    let config = chip.config();
+    layouter.assign_region(
+        || "MiMC5 table",
+        |mut region| {
+            let mut state_cell = message.copy_advice(|| "message", &mut region, config.state(), 0)?;
+            let trace = message.value().map(|message| mimc5_hash_trace(*message, F::MIMC5_ROUND_CONSTANTS));
+            for (i, c) in F::MIMC5_ROUND_CONSTANTS.iter().enumerate() {
+                config.s_in_rounds().enable(&mut region, i + 1)?;
+                region.assign_fixed(|| "round constant", config.round_constants(), i, || Value::known(*c))?;
+                state_cell = region.assign_advice(
+                    || "round output",
+                    config.state(),
+                    i + 1,
+                    || trace.as_ref().map(|trace| trace[i + 1]),
+                )?;
+            }
+            Ok(state_cell)
+        },
+    )
+}
+
+impl<F: MiMCField> Circuit<F> for ManyMiMC5Hashes<F> {
+    type Config = ManyHashesConfig<MiMC5HashConfig>;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            messages: vec![F::zero(); self.messages.len()],
+            trace_in_closure: self.trace_in_closure,
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        let input = meta.advice_column();
+        meta.enable_equality(input);
+        let state = meta.advice_column();
+        let round_constants = meta.fixed_column();
+        ManyHashesConfig {
+            input,
+            chip_config: MiMC5HashChip::configure(meta, state, round_constants),
+        }
+    }
+
+    fn synthesize(&self, config: Self::Config, mut layouter: impl Layouter<F>) -> Result<(), Error> {
+        let chip = MiMC5HashChip::construct(config.chip_config);
+        for (i, message) in self.messages.iter().enumerate() {
+            let cell = layouter.assign_region(
+                || "load message",
+                |mut region| region.assign_advice(|| "message", config.input, 0, || Value::known(*message)),
+            )?;
+            let layouter = layouter.namespace(|| format!("hash {}", i));
+            if self.trace_in_closure {
+                hash_message_trace_in_closure(&chip, layouter, &cell)?;
+            } else {
+                chip.hash_message(layouter, &cell)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+struct ManyMiMC5FeistelHashes<F: MiMCField> {
+    messages: Vec<(F, F)>,
+}
+
+impl<F: MiMCField> Circuit<F> for ManyMiMC5FeistelHashes<F> {
+    type Config = ManyHashesConfig<MiMC5FeistelHashConfig>;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            messages: vec![(F::zero(), F::zero()); self.messages.len()],
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        let input = meta.advice_column();
+        meta.enable_equality(input);
+        let state_left = meta.advice_column();
+        let state_right = meta.advice_column();
+        let round_constants = meta.fixed_column();
+        ManyHashesConfig {
+            input,
+            chip_config: MiMC5FeistelHashChip::configure(meta, state_left, state_right, round_constants),
+        }
+    }
+
+    fn synthesize(&self, config: Self::Config, mut layouter: impl Layouter<F>) -> Result<(), Error> {
+        let chip = MiMC5FeistelHashChip::construct(config.chip_config);
+        for (i, (left, right)) in self.messages.iter().enumerate() {
+            let (left, right) = layouter.assign_region(
+                || "load message",
+                |mut region| {
+                    let left = region.assign_advice(|| "left message", config.input, 0, || Value::known(*left))?;
+                    let right = region.assign_advice(|| "right message", config.input, 1, || Value::known(*right))?;
+                    Ok((left, right))
+                },
+            )?;
+            chip.hash_message(layouter.namespace(|| format!("hash {}", i)), &left, &right)?;
+        }
+        Ok(())
+    }
+}
+
+fn bench_synthesis(c: &mut Criterion) {
+    let rng = OsRng;
+    let mut group = c.benchmark_group("synthesis");
+    group.sample_size(10);
+
+    let costs = chip_costs();
+    let k = costs.mimc5_hash.min_k(64);
+    let messages: Vec<Fp> = (0..64).map(|_| Fp::random(rng)).collect();
+    let circuit = ManyMiMC5Hashes::<Fp> {
+        messages: messages.clone(),
+        trace_in_closure: false,
+    };
+    group.bench_function("pallas_64_mimc5_hashes", |b| {
+        b.iter(|| MockProver::run(k, &circuit, vec![]).expect("synthesis should not fail"))
+    });
+    let baseline = ManyMiMC5Hashes::<Fp> {
+        messages,
+        trace_in_closure: true,
+    };
+    group.bench_function("pallas_64_mimc5_hashes_trace_in_closure", |b| {
+        b.iter(|| MockProver::run(k, &baseline, vec![]).expect("synthesis should not fail"))
+    });
+
+    let k = costs.mimc5_feistel_hash.min_k(32);
+    let circuit = ManyMiMC5FeistelHashes::<Fp> {
+        messages: (0..32).map(|_| (Fp::random(rng), Fp::random(rng))).collect(),
+    };
+    group.bench_function("pallas_32_mimc5_feistel_hashes", |b| {
+        b.iter(|| MockProver::run(k, &circuit, vec![]).expect("synthesis should not fail"))
+    });
+}
+
+criterion_group!(benches, bench_synthesis);
+criterion_main!(benches);
@@ -0,0 +1,425 @@
+// Versioned on-disk format for proving artifacts: the IPA parameters and the
+// key pins of a MiMC circuit.
+//
+// Every artifact file is laid out as (integers little-endian)
+//
+//   magic    8 bytes   "MIMCHALO"
+//   version  u32       ARTIFACT_VERSION
+//   kind     u8        1 = params, 2 = key pin
+//   curve    u32 length, then the base field modulus of the curve as a string
+//   payload  u64 length, then the payload
+//   digest   32 bytes  BLAKE2b-256 of all preceding bytes
+//
+// Keys are NOT persisted. halo2_proofs 0.2 keeps the fields of
+// `VerifyingKey` and `ProvingKey` private and has no way to serialize them or
+// build them from parts, so the fixed commitments, fixed polynomials and
+// permutation data cannot be written or restored. What is written instead is
+// a key pin: k, a hash of the pinned constraint system and a hash of the
+// pinned verifying key. `keygen_vk_pinned` and `keygen_pk_pinned` run keygen
+// from the params and the circuit, so a pin saves no keygen time, and reject
+// the result if either hash differs, e.g. because the pin was written for an
+// older chip layout.
+//
+// Lengths read from a file are untrusted: they are checked against upper
+// bounds before anything is read, and the payload buffer only grows with the
+// bytes actually present.
+
+use std::{fmt, io::{self, Read}};
+
+use blake2b_simd::{Params as Blake2bParams, State as Blake2bState};
+use halo2_proofs::{
+    arithmetic::{CurveAffine, FieldExt},
+    plonk::{keygen_pk, keygen_vk, Circuit, ConstraintSystem, ProvingKey, VerifyingKey},
+    poly::commitment::Params,
+};
+
+pub const ARTIFACT_VERSION: u32 = 1;
+
+const MAGIC: &[u8; 8] = b"MIMCHALO";
+const KIND_PARAMS: u8 = 1;
+const KIND_KEY_PIN: u8 = 2;
+
+// k, the constraint system hash and the verifying key hash
+const KEY_PIN_PAYLOAD_LEN: usize = 4 + 32 + 32;
+// The longest curve string accepted; field moduli are far shorter
+const MAX_CURVE_LEN: usize = 256;
+// The largest params accepted, 2^24 rows
+const MAX_PARAMS_K: u32 = 24;
+
+#[derive(Debug)]
+pub enum ArtifactError {
+    Io(io::Error),
+    BadMagic,
+    UnsupportedVersion(u32),
+    WrongKind { expected: u8, found: u8 },
+    WrongCurve { expected: String, found: String },
+    DigestMismatch,
+    // The file ended before the artifact did
+    Truncated,
+    // A length or field is out of range
+    Malformed(&'static str),
+    StaleConstraintSystem,
+    StaleVerifyingKey,
+    WrongK { params: u32, key: u32 },
+    Keygen(halo2_proofs::plonk::Error),
+}
+
+impl fmt::Display for ArtifactError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArtifactError::Io(e) => write!(f, "I/O error: {}", e),
+            ArtifactError::BadMagic => write!(f, "not a MiMC artifact file"),
+            ArtifactError::UnsupportedVersion(v) => {
+                write!(f, "unsupported artifact version {} (expected {})", v, ARTIFACT_VERSION)
+            }
+            ArtifactError::WrongKind { expected, found } => {
+                write!(f, "wrong artifact kind {} (expected {})", found, expected)
+            }
+            ArtifactError::WrongCurve { expected, found } => {
+                write!(f, "artifact is for curve with base field {} (expected {})", found, expected)
+            }
+            ArtifactError::DigestMismatch => write!(f, "artifact digest mismatch"),
+            ArtifactError::Truncated => write!(f, "artifact file is truncated"),
+            ArtifactError::Malformed(what) => write!(f, "malformed artifact: {}", what),
+            ArtifactError::StaleConstraintSystem => {
+                write!(f, "key pin was written for a different constraint system")
+            }
+            ArtifactError::StaleVerifyingKey => {
+                write!(f, "key pin was written for a different circuit layout or params")
+            }
+            ArtifactError::WrongK { params, key } => {
+                write!(f, "key pin is for k = {} but params have k = {}", key, params)
+            }
+            ArtifactError::Keygen(e) => write!(f, "key generation failed: {:?}", e),
+        }
+    }
+}
+
+impl std::error::Error for ArtifactError {}
+
+impl From<io::Error> for ArtifactError {
+    fn from(e: io::Error) -> Self {
+        match e.kind() {
+            io::ErrorKind::UnexpectedEof => ArtifactError::Truncated,
+            _ => ArtifactError::Io(e),
+        }
+    }
+}
+
+impl From<halo2_proofs::plonk::Error> for ArtifactError {
+    fn from(e: halo2_proofs::plonk::Error) -> Self {
+        ArtifactError::Keygen(e)
+    }
+}
+
+fn hasher() -> Blake2bState {
+    Blake2bParams::new()
+        .hash_length(32)
+        .personal(b"MiMC-Halo2-Artif")
+        .to_state()
+}
+
+fn hash_debug(value: &impl fmt::Debug) -> [u8; 32] {
+    let s = format!("{:?}", value);
+    let mut state = hasher();
+    state.update(&(s.len() as u64).to_le_bytes());
+    state.update(s.as_bytes());
+    state.finalize().as_bytes().try_into().unwrap()
+}
+
+// Hash of the pinned constraint system of a circuit: its gates, queried
+// columns and rotations, selectors and equality-enabled columns
+pub fn constraint_system_hash<F: FieldExt, S: Circuit<F>>() -> [u8; 32] {
+    let mut cs = ConstraintSystem::default();
+    S::configure(&mut cs);
+    hash_debug(&cs.pinned())
+}
+
+// Hash of the pinned verifying key, which also covers the fixed column
+// commitments and the permutation
+pub fn verifying_key_hash<C: CurveAffine>(vk: &VerifyingKey<C>) -> [u8; 32] {
+    hash_debug(&vk.pinned())
+}
+
+// Params does not expose k, but it has 2^k generators
+pub fn params_k<C: CurveAffine>(params: &Params<C>) -> u32 {
+    params.get_g().len().trailing_zeros()
+}
+
+// Length of `Params::write` at k: k, then 2^k generators and their Lagrange
+// basis, then w and u
+fn params_payload_len<C: CurveAffine>(k: u32) -> usize {
+    let point_len = C::Repr::default().as_ref().len();
+    4 + (2 * (1usize << k) + 2) * point_len
+}
+
+fn max_payload_len<C: CurveAffine>(kind: u8) -> usize {
+    match kind {
+        KIND_PARAMS => params_payload_len::<C>(MAX_PARAMS_K),
+        _ => KEY_PIN_PAYLOAD_LEN,
+    }
+}
+
+fn write_artifact<C: CurveAffine, W: io::Write>(
+    kind: u8,
+    payload: &[u8],
+    writer: &mut W,
+) -> Result<(), ArtifactError> {
+    let curve = C::Base::MODULUS.as_bytes();
+
+    let mut bytes = Vec::with_capacity(payload.len() + curve.len() + 64);
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&ARTIFACT_VERSION.to_le_bytes());
+    bytes.push(kind);
+    bytes.extend_from_slice(&(curve.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(curve);
+    bytes.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+    bytes.extend_from_slice(payload);
+
+    let mut state = hasher();
+    state.update(&bytes);
+    bytes.extend_from_slice(state.finalize().as_bytes());
+
+    writer.write_all(&bytes)?;
+    Ok(())
+}
+
+fn read_artifact<C: CurveAffine, R: io::Read>(
+    kind: u8,
+    reader: &mut R,
+) -> Result<Vec<u8>, ArtifactError> {
+    let mut state = hasher();
+    // Callers bound `len`, and the buffer grows only with the bytes read
+    let mut read = |len: usize| -> Result<Vec<u8>, ArtifactError> {
+        let mut buf = vec![];
+        reader.by_ref().take(len as u64).read_to_end(&mut buf)?;
+        if buf.len() != len {
+            return Err(ArtifactError::Truncated);
+        }
+        state.update(&buf);
+        Ok(buf)
+    };
+
+    if read(MAGIC.len())? != MAGIC {
+        return Err(ArtifactError::BadMagic);
+    }
+    let version = u32::from_le_bytes(read(4)?.try_into().unwrap());
+    if version != ARTIFACT_VERSION {
+        return Err(ArtifactError::UnsupportedVersion(version));
+    }
+    let found_kind = read(1)?[0];
+    if found_kind != kind {
+        return Err(ArtifactError::WrongKind { expected: kind, found: found_kind });
+    }
+    let curve_len = u32::from_le_bytes(read(4)?.try_into().unwrap()) as usize;
+    if curve_len > MAX_CURVE_LEN {
+        return Err(ArtifactError::Malformed("curve length out of range"));
+    }
+    let curve = String::from_utf8_lossy(&read(curve_len)?).into_owned();
+    if curve != C::Base::MODULUS {
+        return Err(ArtifactError::WrongCurve { expected: C::Base::MODULUS.to_string(), found: curve });
+    }
+    let payload_len = u64::from_le_bytes(read(8)?.try_into().unwrap());
+    if payload_len > max_payload_len::<C>(kind) as u64 {
+        return Err(ArtifactError::Malformed("payload length out of range"));
+    }
+    let payload = read(payload_len as usize)?;
+
+    let mut digest = [0u8; 32];
+    reader.read_exact(&mut digest)?;
+    if state.finalize().as_bytes() != digest {
+        return Err(ArtifactError::DigestMismatch);
+    }
+    Ok(payload)
+}
+
+pub fn write_params<C: CurveAffine, W: io::Write>(
+    params: &Params<C>,
+    writer: &mut W,
+) -> Result<(), ArtifactError> {
+    let mut payload = vec![];
+    params.write(&mut payload)?;
+    write_artifact::<C, W>(KIND_PARAMS, &payload, writer)
+}
+
+pub fn read_params<C: CurveAffine, R: io::Read>(
+    reader: &mut R,
+) -> Result<Params<C>, ArtifactError> {
+    let payload = read_artifact::<C, R>(KIND_PARAMS, reader)?;
+    // `Params::read` allocates 2^k points up front, so k must agree with the
+    // payload length before it is called
+    if payload.len() < 4 {
+        return Err(ArtifactError::Malformed("params payload too short"));
+    }
+    let k = u32::from_le_bytes(payload[..4].try_into().unwrap());
+    if k > MAX_PARAMS_K || payload.len() != params_payload_len::<C>(k) {
+        return Err(ArtifactError::Malformed("params length does not match k"));
+    }
+    Ok(Params::read(&mut &payload[..])?)
+}
+
+// Writes the pin of `vk`, the verifying key of `S` under `params`. The
+// proving key has the same pin as its verifying key.
+pub fn write_key_pin<C: CurveAffine, S: Circuit<C::Scalar>, W: io::Write>(
+    params: &Params<C>,
+    vk: &VerifyingKey<C>,
+    writer: &mut W,
+) -> Result<(), ArtifactError> {
+    let mut payload = vec![];
+    payload.extend_from_slice(&params_k(params).to_le_bytes());
+    payload.extend_from_slice(&constraint_system_hash::<C::Scalar, S>());
+    payload.extend_from_slice(&verifying_key_hash(vk));
+    write_artifact::<C, W>(KIND_KEY_PIN, &payload, writer)
+}
+
+// Runs keygen for `shape` and checks the verifying key against the pin read
+// from `reader`; the pin holds no key material to load
+pub fn keygen_vk_pinned<C: CurveAffine, S: Circuit<C::Scalar>, R: io::Read>(
+    reader: &mut R,
+    params: &Params<C>,
+    shape: &S,
+) -> Result<VerifyingKey<C>, ArtifactError> {
+    let payload = read_artifact::<C, R>(KIND_KEY_PIN, reader)?;
+    if payload.len() != KEY_PIN_PAYLOAD_LEN {
+        return Err(ArtifactError::Malformed("key pin payload length"));
+    }
+    let k = u32::from_le_bytes(payload[..4].try_into().unwrap());
+    if k != params_k(params) {
+        return Err(ArtifactError::WrongK { params: params_k(params), key: k });
+    }
+    if payload[4..36] != constraint_system_hash::<C::Scalar, S>() {
+        return Err(ArtifactError::StaleConstraintSystem);
+    }
+
+    let vk = keygen_vk(params, &shape.without_witnesses())?;
+    if payload[36..] != verifying_key_hash(&vk) {
+        return Err(ArtifactError::StaleVerifyingKey);
+    }
+    Ok(vk)
+}
+
+// As `keygen_vk_pinned`, then runs keygen for the proving key too
+pub fn keygen_pk_pinned<C: CurveAffine, S: Circuit<C::Scalar>, R: io::Read>(
+    reader: &mut R,
+    params: &Params<C>,
+    shape: &S,
+) -> Result<ProvingKey<C>, ArtifactError> {
+    let vk = keygen_vk_pinned(reader, params, shape)?;
+    Ok(keygen_pk(params, vk, &shape.without_witnesses())?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuits::{
+        Visibility,
+        mimc_hash::MiMC5HashPallasCircuit,
+        mimc_cipher::MiMC5CipherPallasCircuit,
+    };
+    use pasta_curves::{pallas, vesta};
+
+    #[test]
+    fn test_params_round_trip() {
+        let params: Params<vesta::Affine> = Params::new(4);
+        let mut bytes = vec![];
+        write_params(&params, &mut bytes).unwrap();
+
+        let read: Params<vesta::Affine> = read_params(&mut &bytes[..]).unwrap();
+        assert_eq!(params_k(&read), 4);
+        assert_eq!(read.get_g(), params.get_g());
+
+        let mut tampered = bytes.clone();
+        let payload_byte = tampered.len() - 40;
+        tampered[payload_byte] ^= 1;
+        assert!(matches!(
+            read_params::<vesta::Affine, _>(&mut &tampered[..]),
+            Err(ArtifactError::DigestMismatch)
+        ));
+
+        assert!(matches!(
+            read_params::<pallas::Affine, _>(&mut &bytes[..]),
+            Err(ArtifactError::WrongCurve { .. })
+        ));
+    }
+
+    #[test]
+    fn test_untrusted_lengths_are_rejected() {
+        let params: Params<vesta::Affine> = Params::new(4);
+        let mut bytes = vec![];
+        write_params(&params, &mut bytes).unwrap();
+
+        assert!(matches!(
+            read_params::<vesta::Affine, _>(&mut &bytes[..bytes.len() - 1]),
+            Err(ArtifactError::Truncated)
+        ));
+        assert!(matches!(
+            read_params::<vesta::Affine, _>(&mut &bytes[..100]),
+            Err(ArtifactError::Truncated)
+        ));
+
+        // The curve length follows the magic, version and kind
+        let mut huge_curve = bytes.clone();
+        huge_curve[13..17].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(matches!(
+            read_params::<vesta::Affine, _>(&mut &huge_curve[..]),
+            Err(ArtifactError::Malformed(_))
+        ));
+
+        // The payload length follows the curve
+        let curve_len = vesta::Base::MODULUS.len();
+        let mut huge_payload = bytes.clone();
+        huge_payload[17 + curve_len..25 + curve_len].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(matches!(
+            read_params::<vesta::Affine, _>(&mut &huge_payload[..]),
+            Err(ArtifactError::Malformed(_))
+        ));
+
+        // A well-formed file whose params claim a larger k than they hold
+        let mut payload = vec![];
+        params.write(&mut payload).unwrap();
+        payload[..4].copy_from_slice(&20u32.to_le_bytes());
+        let mut lying_k = vec![];
+        write_artifact::<vesta::Affine, _>(KIND_PARAMS, &payload, &mut lying_k).unwrap();
+        assert!(matches!(
+            read_params::<vesta::Affine, _>(&mut &lying_k[..]),
+            Err(ArtifactError::Malformed(_))
+        ));
+
+        let mut short_key = vec![];
+        write_artifact::<vesta::Affine, _>(KIND_KEY_PIN, &[0; 4], &mut short_key).unwrap();
+        assert!(matches!(
+            keygen_vk_pinned(&mut &short_key[..], &params, &MiMC5HashPallasCircuit::default()),
+            Err(ArtifactError::Malformed(_))
+        ));
+    }
+
+    #[test]
+    fn test_key_pin_rejects_stale_keys() {
+        let params: Params<vesta::Affine> = Params::new(7);
+        let shape = MiMC5HashPallasCircuit::default();
+        let vk = keygen_vk(&params, &shape).unwrap();
+
+        let mut bytes = vec![];
+        write_key_pin::<_, MiMC5HashPallasCircuit, _>(&params, &vk, &mut bytes).unwrap();
+
+        let read = keygen_pk_pinned(&mut &bytes[..], &params, &shape).unwrap();
+        assert_eq!(verifying_key_hash(read.get_vk()), verifying_key_hash(&vk));
+
+        // Same constraint system but a different permutation
+        let public_message = MiMC5HashPallasCircuit::with_visibility(
+            Default::default(),
+            Visibility::Public,
+            Visibility::Public,
+        );
+        assert!(matches!(
+            keygen_vk_pinned(&mut &bytes[..], &params, &public_message),
+            Err(ArtifactError::StaleVerifyingKey)
+        ));
+
+        // A different chip layout
+        assert!(matches!(
+            keygen_vk_pinned(&mut &bytes[..], &params, &MiMC5CipherPallasCircuit::default()),
+            Err(ArtifactError::StaleConstraintSystem)
+        ));
+    }
+}
@@ -0,0 +1,698 @@
+// Export of the full assignment of a circuit, and replay of an exported
+// assignment in `MockProver`.
+//
+// `AssignmentTable::synthesize` runs a circuit's floor planner at a given k
+// and records every assigned advice and fixed cell, the instance values, the
+// rows each selector is enabled on and the copy constraints. The table can be
+// written as JSON and read back, or written as a CSV grid for inspection.
+// `ReplayCircuit` configures the original circuit's gates and assigns the
+// table cell by cell, so a witness can be edited outside Rust and checked
+// with `MockProver`.
+//
+// The JSON object holds "k", then each column by name ("A0", "F0", "I0", as
+// in `constraints.rs`) under "advice", "fixed" and "instance", the selectors
+// ("S0", ...) under "selectors" and the copy constraints under "copies".
+// Advice and fixed columns are lists of [row, value] pairs of their assigned
+// cells, where the value is null if it was unknown during synthesis.
+// Instance columns are lists of values from row 0, and copies are
+// [column, row, column, row] lists.
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::marker::PhantomData;
+use std::{fmt, str::FromStr};
+
+use halo2_proofs::{
+    arithmetic::{Field, FieldExt},
+    circuit::{Cell, Layouter, SimpleFloorPlanner, Value},
+    plonk::{
+        Advice, Any, Assigned, Assignment, Circuit, Column, ConstraintSystem, Error, Fixed, FloorPlanner, Instance,
+        Selector,
+    },
+};
+use pasta_curves::group::ff::PrimeField;
+use serde_json::{json, Map, Value as Json};
+
+use crate::debug_format::{debug_index, FormatChanged, PinnedSystem};
+use crate::field_io::{format_field, parse_field, FieldFormat, ParseFieldError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ColumnRef {
+    Advice(usize),
+    Fixed(usize),
+    Instance(usize),
+}
+
+impl ColumnRef {
+    pub(crate) fn of(column: &Column<Any>) -> Self {
+        match column.column_type() {
+            Any::Advice => ColumnRef::Advice(debug_index(column)),
+            Any::Fixed => ColumnRef::Fixed(debug_index(column)),
+            Any::Instance => ColumnRef::Instance(debug_index(column)),
+        }
+    }
+}
+
+impl fmt::Display for ColumnRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ColumnRef::Advice(index) => write!(f, "A{}", index),
+            ColumnRef::Fixed(index) => write!(f, "F{}", index),
+            ColumnRef::Instance(index) => write!(f, "I{}", index),
+        }
+    }
+}
+
+impl FromStr for ColumnRef {
+    type Err = AssignmentError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bad_column = || AssignmentError::Malformed(format!("bad column name {:?}", s));
+        let index = s.get(1..).and_then(|index| index.parse().ok()).ok_or_else(bad_column)?;
+        match s.chars().next() {
+            Some('A') => Ok(ColumnRef::Advice(index)),
+            Some('F') => Ok(ColumnRef::Fixed(index)),
+            Some('I') => Ok(ColumnRef::Instance(index)),
+            _ => Err(bad_column()),
+        }
+    }
+}
+
+// A cell as its column and row
+pub type CellRef = (ColumnRef, usize);
+
+#[derive(Debug)]
+pub enum AssignmentError {
+    Malformed(String),
+    BadValue { cell: String, error: ParseFieldError },
+    // The table has a different number of columns of a kind than the
+    // circuit it is replayed in
+    ColumnCount { kind: &'static str, table: usize, circuit: usize },
+    FormatChanged(FormatChanged),
+}
+
+impl fmt::Display for AssignmentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AssignmentError::Malformed(message) => write!(f, "malformed assignment: {}", message),
+            AssignmentError::BadValue { cell, error } => write!(f, "cell {}: {}", cell, error),
+            AssignmentError::ColumnCount { kind, table, circuit } => write!(
+                f,
+                "the assignment has {} {} columns but the circuit has {}",
+                table, kind, circuit
+            ),
+            AssignmentError::FormatChanged(error) => error.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for AssignmentError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SelectorAssignment {
+    // Simple selectors are multiplied into gates, complex ones may also
+    // appear in lookups
+    pub simple: bool,
+    pub rows: BTreeSet<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssignmentTable<F: Field> {
+    pub k: u32,
+    // Assigned cells by column and row, None if the value was unknown
+    pub advice: Vec<BTreeMap<usize, Option<F>>>,
+    pub fixed: Vec<BTreeMap<usize, Option<F>>>,
+    pub instance: Vec<Vec<F>>,
+    pub selectors: Vec<SelectorAssignment>,
+    pub copies: Vec<(CellRef, CellRef)>,
+}
+
+impl<F: FieldExt + PrimeField<Repr = [u8; 32]>> AssignmentTable<F> {
+    // Synthesizes a circuit as `MockProver::run` would, with the same checks
+    // on the rows and instance values
+    pub fn synthesize<C: Circuit<F>>(k: u32, circuit: &C, instance: Vec<Vec<F>>) -> Result<Self, Error> {
+        let mut cs = ConstraintSystem::default();
+        let config = C::configure(&mut cs);
+        let columns = ReplayConfig::of(&cs)?;
+        let usable_rows = (1usize << k) - (cs.blinding_factors() + 1);
+        if instance.len() != columns.instance.len() {
+            return Err(Error::InvalidInstances);
+        }
+        if instance.iter().any(|values| values.len() > usable_rows) {
+            return Err(Error::InstanceTooLarge);
+        }
+
+        let mut recorder = AssignmentRecorder {
+            usable_rows,
+            table: Self {
+                k,
+                advice: vec![BTreeMap::new(); columns.advice.len()],
+                fixed: vec![BTreeMap::new(); columns.fixed.len()],
+                instance,
+                selectors: columns
+                    .simple_selectors
+                    .iter()
+                    .map(|simple| SelectorAssignment {
+                        simple: *simple,
+                        rows: BTreeSet::new(),
+                    })
+                    .collect(),
+                copies: vec![],
+            },
+        };
+        C::FloorPlanner::synthesize(&mut recorder, circuit, config, columns.constants)?;
+        Ok(recorder.table)
+    }
+
+    pub fn to_json(&self) -> Json {
+        let value = |value: &Option<F>| value.as_ref().map_or(Json::Null, |value| json!(format_field(value, FieldFormat::Hex)));
+        let cells = |columns: &[BTreeMap<usize, Option<F>>], column: fn(usize) -> ColumnRef| {
+            columns
+                .iter()
+                .enumerate()
+                .map(|(i, cells)| {
+                    let cells: Vec<Json> = cells.iter().map(|(row, v)| json!([row, value(v)])).collect();
+                    (column(i).to_string(), json!(cells))
+                })
+                .collect::<Map<_, _>>()
+        };
+        let instance: Map<_, _> = self
+            .instance
+            .iter()
+            .enumerate()
+            .map(|(i, values)| {
+                let values: Vec<Json> = values.iter().map(|v| json!(format_field(v, FieldFormat::Hex))).collect();
+                (ColumnRef::Instance(i).to_string(), json!(values))
+            })
+            .collect();
+        let selectors: Map<_, _> = self
+            .selectors
+            .iter()
+            .enumerate()
+            .map(|(i, selector)| (format!("S{}", i), json!({ "simple": selector.simple, "rows": selector.rows })))
+            .collect();
+        let copies: Vec<Json> = self
+            .copies
+            .iter()
+            .map(|((left, left_row), (right, right_row))| {
+                json!([left.to_string(), left_row, right.to_string(), right_row])
+            })
+            .collect();
+        json!({
+            "k": self.k,
+            "advice": cells(&self.advice, ColumnRef::Advice),
+            "fixed": cells(&self.fixed, ColumnRef::Fixed),
+            "instance": instance,
+            "selectors": selectors,
+            "copies": copies,
+        })
+    }
+
+    pub fn from_json(json: &Json) -> Result<Self, AssignmentError> {
+        let malformed = |message: &str| AssignmentError::Malformed(message.to_string());
+        let k = json["k"].as_u64().ok_or_else(|| malformed("missing k"))? as u32;
+        let parse = |cell: String, value: &Json| match value.as_str() {
+            Some(value) => parse_field::<F>(value).map_err(|error| AssignmentError::BadValue { cell, error }),
+            None => Err(AssignmentError::Malformed(format!("cell {} is not a string", cell))),
+        };
+        // The columns of a kind, which must be named from index 0 without gaps
+        let columns = |kind: &str, prefix: &str| -> Result<Vec<&Json>, AssignmentError> {
+            let columns = json[kind].as_object().ok_or_else(|| malformed(&format!("missing {}", kind)))?;
+            (0..columns.len())
+                .map(|i| {
+                    let name = format!("{}{}", prefix, i);
+                    columns.get(&name).ok_or_else(|| AssignmentError::Malformed(format!("missing column {}", name)))
+                })
+                .collect()
+        };
+        let cells = |kind: &str, prefix: &str| -> Result<Vec<BTreeMap<usize, Option<F>>>, AssignmentError> {
+            columns(kind, prefix)?
+                .into_iter()
+                .enumerate()
+                .map(|(i, cells)| {
+                    let cells = cells.as_array().ok_or_else(|| malformed("cells are not a list"))?;
+                    cells
+                        .iter()
+                        .map(|cell| {
+                            let row = cell[0].as_u64().ok_or_else(|| malformed("cell without a row"))? as usize;
+                            let value = match &cell[1] {
+                                Json::Null => None,
+                                value => Some(parse(format!("{}{}@{}", prefix, i, row), value)?),
+                            };
+                            Ok((row, value))
+                        })
+                        .collect()
+                })
+                .collect()
+        };
+
+        let instance = columns("instance", "I")?
+            .into_iter()
+            .enumerate()
+            .map(|(i, values)| {
+                let values = values.as_array().ok_or_else(|| malformed("instance values are not a list"))?;
+                values.iter().enumerate().map(|(row, value)| parse(format!("I{}@{}", i, row), value)).collect()
+            })
+            .collect::<Result<_, _>>()?;
+        let selectors = columns("selectors", "S")?
+            .into_iter()
+            .map(|selector| {
+                let rows = selector["rows"].as_array().ok_or_else(|| malformed("selector without rows"))?;
+                Ok(SelectorAssignment {
+                    simple: selector["simple"].as_bool().unwrap_or(true),
+                    rows: rows
+                        .iter()
+                        .map(|row| row.as_u64().map(|row| row as usize).ok_or_else(|| malformed("bad selector row")))
+                        .collect::<Result<_, _>>()?,
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        let copies = json["copies"]
+            .as_array()
+            .ok_or_else(|| malformed("missing copies"))?
+            .iter()
+            .map(|copy| {
+                let cell = |i: usize| -> Result<CellRef, AssignmentError> {
+                    let column = copy[i].as_str().ok_or_else(|| malformed("bad copy column"))?.parse()?;
+                    let row = copy[i + 1].as_u64().ok_or_else(|| malformed("bad copy row"))?;
+                    Ok((column, row as usize))
+                };
+                Ok((cell(0)?, cell(2)?))
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            k,
+            advice: cells("advice", "A")?,
+            fixed: cells("fixed", "F")?,
+            instance,
+            selectors,
+            copies,
+        })
+    }
+
+    // A grid with a line per row up to the last used one and a column per
+    // table column and selector. Unassigned cells are empty, unknown values
+    // are "?" and enabled selectors are 1. Copy constraints are not included.
+    pub fn to_csv(&self) -> String {
+        let rows = self
+            .advice
+            .iter()
+            .chain(&self.fixed)
+            .filter_map(|cells| cells.keys().last())
+            .chain(self.selectors.iter().filter_map(|selector| selector.rows.iter().last()))
+            .map(|row| row + 1)
+            .chain(self.instance.iter().map(|values| values.len()))
+            .max()
+            .unwrap_or(0);
+
+        let mut header = vec!["row".to_string()];
+        header.extend((0..self.advice.len()).map(|i| ColumnRef::Advice(i).to_string()));
+        header.extend((0..self.fixed.len()).map(|i| ColumnRef::Fixed(i).to_string()));
+        header.extend((0..self.instance.len()).map(|i| ColumnRef::Instance(i).to_string()));
+        header.extend((0..self.selectors.len()).map(|i| format!("S{}", i)));
+        let mut csv = header.join(",") + "\n";
+
+        let cell = |cells: &BTreeMap<usize, Option<F>>, row| match cells.get(&row) {
+            None => String::new(),
+            Some(None) => "?".to_string(),
+            Some(Some(value)) => format_field(value, FieldFormat::Hex),
+        };
+        for row in 0..rows {
+            let mut line = vec![row.to_string()];
+            line.extend(self.advice.iter().chain(&self.fixed).map(|cells| cell(cells, row)));
+            line.extend(
+                self.instance
+                    .iter()
+                    .map(|values| values.get(row).map_or(String::new(), |value| format_field(value, FieldFormat::Hex))),
+            );
+            line.extend(
+                self.selectors
+                    .iter()
+                    .map(|selector| if selector.rows.contains(&row) { "1" } else { "" }.to_string()),
+            );
+            csv += &(line.join(",") + "\n");
+        }
+        csv
+    }
+}
+
+// Handles for every column and selector of a configured constraint system,
+// indexed as in the constraint system
+#[derive(Debug, Clone)]
+pub struct ReplayConfig {
+    advice: Vec<Column<Advice>>,
+    fixed: Vec<Column<Fixed>>,
+    instance: Vec<Column<Instance>>,
+    selectors: Vec<Selector>,
+    simple_selectors: Vec<bool>,
+    constants: Vec<Column<Fixed>>,
+}
+
+impl ReplayConfig {
+    // Columns are identified by their index, so allocating the same number
+    // in a fresh constraint system gives handles equal to the original ones
+    fn of<F: Field>(cs: &ConstraintSystem<F>) -> Result<Self, FormatChanged> {
+        let pinned = PinnedSystem::of(cs)?;
+        let mut shadow = ConstraintSystem::<F>::default();
+        let advice = (0..pinned.advice).map(|_| shadow.advice_column()).collect();
+        let fixed: Vec<_> = (0..pinned.fixed).map(|_| shadow.fixed_column()).collect();
+        let instance = (0..pinned.instance).map(|_| shadow.instance_column()).collect();
+        let selectors = pinned
+            .simple_selectors
+            .iter()
+            .map(|simple| if *simple { shadow.selector() } else { shadow.complex_selector() })
+            .collect();
+        let constants = pinned
+            .constants
+            .iter()
+            .filter_map(|column| match column {
+                ColumnRef::Fixed(index) => Some(fixed[*index]),
+                _ => None,
+            })
+            .collect();
+        let simple_selectors = pinned.simple_selectors;
+        Ok(Self {
+            advice,
+            fixed,
+            instance,
+            selectors,
+            simple_selectors,
+            constants,
+        })
+    }
+}
+
+impl ReplayConfig {
+    // Checks that a table has the columns and selectors of this
+    // configuration, and copies only between them
+    fn check<F: Field>(&self, table: &AssignmentTable<F>) -> Result<(), AssignmentError> {
+        let counts = [
+            ("advice", table.advice.len(), self.advice.len()),
+            ("fixed", table.fixed.len(), self.fixed.len()),
+            ("instance", table.instance.len(), self.instance.len()),
+            ("selector", table.selectors.len(), self.selectors.len()),
+        ];
+        for (kind, table, circuit) in counts {
+            if table != circuit {
+                return Err(AssignmentError::ColumnCount { kind, table, circuit });
+            }
+        }
+        for ((left, _), (right, _)) in &table.copies {
+            for column in [left, right] {
+                let count = match column {
+                    ColumnRef::Advice(_) => self.advice.len(),
+                    ColumnRef::Fixed(_) => self.fixed.len(),
+                    ColumnRef::Instance(_) => self.instance.len(),
+                };
+                let (ColumnRef::Advice(index) | ColumnRef::Fixed(index) | ColumnRef::Instance(index)) = column;
+                if *index >= count {
+                    return Err(AssignmentError::Malformed(format!("copy to missing column {}", column)));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// The fixed columns that hold the constants of a constraint system
+pub(crate) fn constant_columns<F: Field>(cs: &ConstraintSystem<F>) -> Result<Vec<Column<Fixed>>, FormatChanged> {
+    Ok(ReplayConfig::of(cs)?.constants)
+}
+
+// Assigns an exported table in the columns of circuit `C`, whose gates and
+// equality-enabled columns it keeps. Run it in `MockProver` with the table's
+// k and instance values. `new` fails if the table does not have the columns
+// and selectors of `C`.
+pub struct ReplayCircuit<F: Field, C> {
+    table: AssignmentTable<F>,
+    _marker: PhantomData<C>,
+}
+
+impl<F: FieldExt, C: Circuit<F>> ReplayCircuit<F, C> {
+    pub fn new(table: AssignmentTable<F>) -> Result<Self, AssignmentError> {
+        let mut cs = ConstraintSystem::default();
+        C::configure(&mut cs);
+        ReplayConfig::of(&cs).map_err(AssignmentError::FormatChanged)?.check(&table)?;
+        Ok(Self {
+            table,
+            _marker: PhantomData,
+        })
+    }
+}
+
+impl<F: FieldExt, C: Circuit<F>> Circuit<F> for ReplayCircuit<F, C> {
+    type Config = ReplayConfig;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        let mut table = self.table.clone();
+        for cells in &mut table.advice {
+            cells.values_mut().for_each(|value| *value = None);
+        }
+        Self {
+            table,
+            _marker: PhantomData,
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        C::configure(meta);
+        // `new` has read the same constraint system
+        ReplayConfig::of(meta).expect("checked in ReplayCircuit::new")
+    }
+
+    fn synthesize(&self, config: Self::Config, mut layouter: impl Layouter<F>) -> Result<(), Error> {
+        let table = &self.table;
+        config.check(table).map_err(|_| Error::Synthesis)?;
+        let instance_copies = layouter.assign_region(
+            || "replayed assignment",
+            |mut region| {
+                let mut cells: HashMap<CellRef, Cell> = HashMap::new();
+                let value = |value: &Option<F>| value.map_or(Value::unknown(), Value::known);
+                for (i, (column, assigned)) in config.advice.iter().zip(&table.advice).enumerate() {
+                    for (row, v) in assigned {
+                        let cell = region.assign_advice(|| "replayed advice", *column, *row, || value(v))?;
+                        cells.insert((ColumnRef::Advice(i), *row), cell.cell());
+                    }
+                }
+                for (i, (column, assigned)) in config.fixed.iter().zip(&table.fixed).enumerate() {
+                    for (row, v) in assigned {
+                        let cell = region.assign_fixed(|| "replayed fixed", *column, *row, || value(v))?;
+                        cells.insert((ColumnRef::Fixed(i), *row), cell.cell());
+                    }
+                }
+                for (selector, assigned) in config.selectors.iter().zip(&table.selectors) {
+                    for row in &assigned.rows {
+                        selector.enable(&mut region, *row)?;
+                    }
+                }
+
+                // Copies to instance cells are made once the region is laid out
+                let mut instance_copies = vec![];
+                for (left, right) in &table.copies {
+                    match (left, right) {
+                        ((ColumnRef::Instance(column), row), cell) | (cell, (ColumnRef::Instance(column), row)) => {
+                            let cell = cells.get(cell).ok_or(Error::Synthesis)?;
+                            instance_copies.push((*cell, config.instance[*column], *row));
+                        }
+                        _ => {
+                            let left = cells.get(left).ok_or(Error::Synthesis)?;
+                            let right = cells.get(right).ok_or(Error::Synthesis)?;
+                            region.constrain_equal(*left, *right)?;
+                        }
+                    }
+                }
+                Ok(instance_copies)
+            },
+        )?;
+        for (cell, column, row) in instance_copies {
+            layouter.constrain_instance(cell, column, row)?;
+        }
+        Ok(())
+    }
+}
+
+struct AssignmentRecorder<F: Field> {
+    usable_rows: usize,
+    table: AssignmentTable<F>,
+}
+
+impl<F: Field> AssignmentRecorder<F> {
+    fn check_row(&self, row: usize) -> Result<(), Error> {
+        if row < self.usable_rows {
+            Ok(())
+        } else {
+            Err(Error::NotEnoughRowsAvailable { current_k: self.table.k })
+        }
+    }
+}
+
+// The value of a cell, None if unknown
+fn cell_value<F: Field, V: Into<Assigned<F>>>(to: Value<V>) -> Option<F> {
+    let mut value = None;
+    to.map(|v| value = Some(v.into().evaluate()));
+    value
+}
+
+impl<F: Field> Assignment<F> for AssignmentRecorder<F> {
+    fn enter_region<NR, N>(&mut self, _: N)
+    where
+        NR: Into<String>,
+        N: FnOnce() -> NR,
+    {
+    }
+
+    fn exit_region(&mut self) {}
+
+    fn enable_selector<A, AR>(&mut self, _: A, selector: &Selector, row: usize) -> Result<(), Error>
+    where
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        self.check_row(row)?;
+        self.table.selectors[debug_index(selector)].rows.insert(row);
+        Ok(())
+    }
+
+    // Rows past the given instance values read as zero, as in MockProver
+    fn query_instance(&self, column: Column<Instance>, row: usize) -> Result<Value<F>, Error> {
+        self.check_row(row)?;
+        let values = self.table.instance.get(debug_index(&column)).ok_or(Error::BoundsFailure)?;
+        Ok(Value::known(values.get(row).copied().unwrap_or_else(F::zero)))
+    }
+
+    fn assign_advice<V, VR, A, AR>(&mut self, _: A, column: Column<Advice>, row: usize, to: V) -> Result<(), Error>
+    where
+        V: FnOnce() -> Value<VR>,
+        VR: Into<Assigned<F>>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        self.check_row(row)?;
+        self.table.advice[debug_index(&column)].insert(row, cell_value(to()));
+        Ok(())
+    }
+
+    fn assign_fixed<V, VR, A, AR>(&mut self, _: A, column: Column<Fixed>, row: usize, to: V) -> Result<(), Error>
+    where
+        V: FnOnce() -> Value<VR>,
+        VR: Into<Assigned<F>>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        self.check_row(row)?;
+        self.table.fixed[debug_index(&column)].insert(row, cell_value(to()));
+        Ok(())
+    }
+
+    fn copy(&mut self, left: Column<Any>, left_row: usize, right: Column<Any>, right_row: usize) -> Result<(), Error> {
+        self.check_row(left_row)?;
+        self.check_row(right_row)?;
+        self.table.copies.push(((ColumnRef::of(&left), left_row), (ColumnRef::of(&right), right_row)));
+        Ok(())
+    }
+
+    fn fill_from_row(&mut self, column: Column<Fixed>, from_row: usize, to: Value<Assigned<F>>) -> Result<(), Error> {
+        self.check_row(from_row)?;
+        let value = cell_value(to);
+        let cells = &mut self.table.fixed[debug_index(&column)];
+        for row in from_row..self.usable_rows {
+            cells.insert(row, value);
+        }
+        Ok(())
+    }
+
+    fn push_namespace<NR, N>(&mut self, _: N)
+    where
+        NR: Into<String>,
+        N: FnOnce() -> NR,
+    {
+    }
+
+    fn pop_namespace(&mut self, _: Option<String>) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use halo2_proofs::dev::MockProver;
+    use pasta_curves::Fp;
+
+    use crate::circuits::{mimc_cipher::MiMC5CipherPallasCircuit, Visibility};
+
+    type CipherReplay = ReplayCircuit<Fp, MiMC5CipherPallasCircuit>;
+
+    #[test]
+    fn test_assignment_round_trip_and_replay() {
+        let k = 7;
+        let circuit = MiMC5CipherPallasCircuit::with_visibility(
+            Fp::from(3),
+            Fp::from(5),
+            Visibility::Public,
+            Visibility::Private,
+            Visibility::Public,
+        );
+        let table = AssignmentTable::synthesize(k, &circuit, vec![circuit.public_inputs()]).unwrap();
+        assert_eq!(table.selectors.iter().map(|s| s.rows.len()).sum::<usize>(), 111);
+        assert!(table.copies.iter().any(|(_, (column, _))| *column == ColumnRef::Instance(0)));
+
+        let json = serde_json::to_string(&table.to_json()).unwrap();
+        let imported = AssignmentTable::<Fp>::from_json(&serde_json::from_str(&json).unwrap()).unwrap();
+        assert_eq!(imported, table);
+
+        let prover = MockProver::run(k, &CipherReplay::new(imported.clone()).unwrap(), imported.instance.clone()).unwrap();
+        prover.assert_satisfied();
+
+        // An edited round state, or a public output that does not match the
+        // copied cell, fails the replay
+        let mut edited = imported.clone();
+        let (_, value) = edited.advice[1].iter_mut().nth(50).unwrap();
+        *value = value.map(|v| v + Fp::one());
+        let prover = MockProver::run(k, &CipherReplay::new(edited.clone()).unwrap(), edited.instance.clone()).unwrap();
+        assert!(prover.verify().is_err());
+
+        let mut edited = imported;
+        edited.instance[0][1] += Fp::one();
+        let prover = MockProver::run(k, &CipherReplay::new(edited.clone()).unwrap(), edited.instance.clone()).unwrap();
+        assert!(prover.verify().is_err());
+    }
+
+    #[test]
+    fn test_assignment_csv_and_errors() {
+        let circuit = MiMC5CipherPallasCircuit::default();
+        let table = AssignmentTable::synthesize(7, &circuit, vec![circuit.public_inputs()]).unwrap();
+        let csv = table.to_csv();
+        let mut lines = csv.lines();
+        assert_eq!(lines.next(), Some("row,A0,A1,A2,F0,I0,S0,S1"));
+        assert_eq!(lines.count(), 112);
+
+        // Synthesis checks rows and instance columns as MockProver does
+        assert!(matches!(
+            AssignmentTable::synthesize(6, &circuit, vec![vec![]]),
+            Err(Error::NotEnoughRowsAvailable { current_k: 6 })
+        ));
+        assert!(matches!(AssignmentTable::synthesize(7, &circuit, vec![]), Err(Error::InvalidInstances)));
+
+        let mut json = table.to_json();
+        json["advice"]["A1"][3][1] = json!("0xzz");
+        assert!(matches!(AssignmentTable::<Fp>::from_json(&json), Err(AssignmentError::BadValue { .. })));
+        json["advice"].as_object_mut().unwrap().remove("A0");
+        assert!(matches!(AssignmentTable::<Fp>::from_json(&json), Err(AssignmentError::Malformed(_))));
+
+        // A table is only replayed in a circuit with its columns
+        let mut extra = table.clone();
+        extra.advice.push(BTreeMap::new());
+        assert!(matches!(
+            CipherReplay::new(extra),
+            Err(AssignmentError::ColumnCount { kind: "advice", table: 4, circuit: 3 })
+        ));
+        let mut missing = table.clone();
+        missing.instance.clear();
+        assert!(matches!(
+            CipherReplay::new(missing),
+            Err(AssignmentError::ColumnCount { kind: "instance", table: 0, circuit: 1 })
+        ));
+        let mut copy = table;
+        copy.copies.push(((ColumnRef::Advice(0), 0), (ColumnRef::Instance(1), 0)));
+        assert!(matches!(CipherReplay::new(copy), Err(AssignmentError::Malformed(_))));
+    }
+}
@@ -0,0 +1,307 @@
+// Command-line access to the native MiMC primitives, so a reference digest or
+// ciphertext can be computed without writing Rust or running the Sage scripts.
+//
+// Values are read from the command line or, when none are given, from stdin
+// with one item per line. Each result is printed on its own line, either as
+// plain text or as a JSON object.
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::process::ExitCode;
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use pasta_curves::group::ff::PrimeField;
+use pasta_curves::{Fp, Fq};
+use serde_json::json;
+
+use mimc_halo2::constraints::chip_constraint_systems;
+use mimc_halo2::field::MiMCField;
+use mimc_halo2::field_io::{format_field, parse_field, FieldFormat};
+use mimc_halo2::merkle;
+
+mod prove;
+mod serve;
+
+#[derive(Parser)]
+#[command(name = "mimc", about = "Native MiMC5 and MiMC5 Feistel over the Pallas and Vesta base fields")]
+struct Cli {
+    /// Base field to work in
+    #[arg(long, value_enum, default_value_t = Curve::Pallas, global = true)]
+    curve: Curve,
+    /// Encoding of output field elements. Inputs may be "0x"-prefixed hex or decimal.
+    #[arg(long, value_enum, default_value_t = Format::Hex, global = true)]
+    format: Format,
+    /// Print one JSON object per result instead of plain text
+    #[arg(long, global = true)]
+    json: bool,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Curve {
+    Pallas,
+    Vesta,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    Hex,
+    Dec,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// MiMC5 hash of each value
+    Hash(Values),
+    /// MiMC5 encryption of each value
+    Encrypt(KeyedValues),
+    /// MiMC5 decryption of each value
+    Decrypt(KeyedValues),
+    /// MiMC5 Feistel hash of each (left, right) pair
+    FeistelHash(Values),
+    /// MiMC5 Feistel encryption of each (left, right) pair
+    FeistelEncrypt(KeyedValues),
+    /// MiMC5 Feistel decryption of each (left, right) pair
+    FeistelDecrypt(KeyedValues),
+    /// Merkle root over the lines or fixed-size chunks of a file
+    MerkleRoot(MerkleArgs),
+    /// Prove the statement in a JSON statement file and write a proof file
+    #[command(visible_alias = "mimc-prove")]
+    Prove(prove::ProveArgs),
+    /// Check a proof file written by `prove`
+    #[command(visible_alias = "mimc-verify")]
+    Verify(prove::VerifyArgs),
+    /// Serve prove and verify requests over JSON-RPC, keeping keys in memory
+    Serve(serve::ServeArgs),
+    /// Print the constraint system of each chip as JSON
+    Constraints,
+}
+
+#[derive(Args)]
+struct Values {
+    /// Input values. Read from stdin, one item per line, if omitted.
+    values: Vec<String>,
+}
+
+#[derive(Args)]
+struct KeyedValues {
+    #[arg(long)]
+    key: String,
+    #[command(flatten)]
+    values: Values,
+}
+
+#[derive(Args)]
+struct MerkleArgs {
+    /// File to read, or "-" for stdin
+    #[arg(default_value = "-")]
+    file: String,
+    /// Split the file into chunks of this many bytes instead of lines
+    #[arg(long)]
+    chunk_size: Option<usize>,
+}
+
+#[derive(Clone, Copy)]
+enum Op {
+    Hash,
+    Encrypt,
+    Decrypt,
+    FeistelHash,
+    FeistelEncrypt,
+    FeistelDecrypt,
+}
+
+impl Op {
+    fn name(self) -> &'static str {
+        match self {
+            Op::Hash => "hash",
+            Op::Encrypt => "encrypt",
+            Op::Decrypt => "decrypt",
+            Op::FeistelHash => "feistel-hash",
+            Op::FeistelEncrypt => "feistel-encrypt",
+            Op::FeistelDecrypt => "feistel-decrypt",
+        }
+    }
+
+    // Number of field elements in one input item
+    fn arity(self) -> usize {
+        match self {
+            Op::Hash | Op::Encrypt | Op::Decrypt => 1,
+            _ => 2,
+        }
+    }
+}
+
+impl Curve {
+    fn name(self) -> &'static str {
+        match self {
+            Curve::Pallas => "pallas",
+            Curve::Vesta => "vesta",
+        }
+    }
+}
+
+fn apply<F: MiMCField>(op: Op, key: F, input: &[F]) -> Vec<F> {
+    let mut state = input.to_vec();
+    match op {
+        Op::Hash => F::encrypt(&mut state[0], F::zero()),
+        Op::Encrypt => F::encrypt(&mut state[0], key),
+        Op::Decrypt => F::decrypt(&mut state[0], key),
+        Op::FeistelHash => {
+            let (left, right) = state.split_at_mut(1);
+            F::permute(&mut left[0], &mut right[0]);
+        }
+        Op::FeistelEncrypt => {
+            let (left, right) = state.split_at_mut(1);
+            F::feistel_encrypt(&mut left[0], &mut right[0], key);
+        }
+        Op::FeistelDecrypt => {
+            let (left, right) = state.split_at_mut(1);
+            F::feistel_decrypt(&mut left[0], &mut right[0], key);
+        }
+    }
+    state
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let result = match (&cli.command, cli.curve) {
+        // The statement or proof file names its own curve
+        (Command::Prove(args), _) => prove::prove(args),
+        (Command::Verify(args), _) => prove::verify(args, cli.json),
+        (Command::Serve(args), _) => serve::serve(args),
+        (Command::Constraints, _) => constraints(),
+        (_, Curve::Pallas) => run::<Fp>(&cli),
+        (_, Curve::Vesta) => run::<Fq>(&cli),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(message) => {
+            eprintln!("mimc: {}", message);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+// The chips are laid out the same over both curves, so this ignores --curve
+fn constraints() -> Result<(), String> {
+    let systems: serde_json::Map<_, _> = chip_constraint_systems()
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .map(|(name, system)| (name.to_string(), system))
+        .collect();
+    let output = serde_json::to_string_pretty(&systems).map_err(|e| e.to_string())?;
+    writeln!(io::stdout().lock(), "{}", output).map_err(|e| e.to_string())
+}
+
+fn run<F: MiMCField + PrimeField<Repr = [u8; 32]>>(cli: &Cli) -> Result<(), String> {
+    let format = match cli.format {
+        Format::Hex => FieldFormat::Hex,
+        Format::Dec => FieldFormat::Decimal,
+    };
+    let (op, key, values) = match &cli.command {
+        Command::Hash(values) => (Op::Hash, None, values),
+        Command::Encrypt(args) => (Op::Encrypt, Some(&args.key), &args.values),
+        Command::Decrypt(args) => (Op::Decrypt, Some(&args.key), &args.values),
+        Command::FeistelHash(values) => (Op::FeistelHash, None, values),
+        Command::FeistelEncrypt(args) => (Op::FeistelEncrypt, Some(&args.key), &args.values),
+        Command::FeistelDecrypt(args) => (Op::FeistelDecrypt, Some(&args.key), &args.values),
+        Command::MerkleRoot(args) => return merkle_root::<F>(args, cli.curve, format, cli.json),
+        Command::Prove(_) | Command::Verify(_) | Command::Serve(_) | Command::Constraints => unreachable!("dispatched in main"),
+    };
+    let key = match key {
+        Some(key) => parse_field::<F>(key).map_err(|e| format!("key: {}", e))?,
+        None => F::zero(),
+    };
+
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    let mut emit = |input: &[F]| -> Result<(), String> {
+        let output = apply(op, key, input);
+        let line = if cli.json {
+            json!({
+                "curve": cli.curve.name(),
+                "op": op.name(),
+                "input": input.iter().map(|v| format_field(v, format)).collect::<Vec<_>>(),
+                "output": output.iter().map(|v| format_field(v, format)).collect::<Vec<_>>(),
+            })
+            .to_string()
+        } else {
+            output.iter().map(|v| format_field(v, format)).collect::<Vec<_>>().join(" ")
+        };
+        writeln!(out, "{}", line).map_err(|e| e.to_string())
+    };
+
+    if !values.values.is_empty() {
+        if values.values.len() % op.arity() != 0 {
+            return Err(format!("{} takes {} values per item", op.name(), op.arity()));
+        }
+        let parsed = parse_values::<F>(values.values.iter().map(String::as_str))?;
+        for item in parsed.chunks(op.arity()) {
+            emit(item)?;
+        }
+        return Ok(());
+    }
+
+    // Batch mode: one item per non-empty stdin line, elements separated by
+    // whitespace or commas
+    for (index, line) in io::stdin().lock().lines().enumerate() {
+        let line = line.map_err(|e| e.to_string())?;
+        let fields: Vec<&str> = line.split(|c: char| c.is_whitespace() || c == ',').filter(|f| !f.is_empty()).collect();
+        if fields.is_empty() {
+            continue;
+        }
+        if fields.len() != op.arity() {
+            return Err(format!("line {}: expected {} values, found {}", index + 1, op.arity(), fields.len()));
+        }
+        let item = parse_values::<F>(fields.into_iter()).map_err(|e| format!("line {}: {}", index + 1, e))?;
+        emit(&item)?;
+    }
+    Ok(())
+}
+
+fn parse_values<'a, F: PrimeField<Repr = [u8; 32]>>(values: impl Iterator<Item = &'a str>) -> Result<Vec<F>, String> {
+    values
+        .map(|value| parse_field::<F>(value).map_err(|e| format!("{:?}: {}", value, e)))
+        .collect()
+}
+
+fn merkle_root<F: MiMCField + PrimeField<Repr = [u8; 32]>>(
+    args: &MerkleArgs,
+    curve: Curve,
+    format: FieldFormat,
+    json: bool,
+) -> Result<(), String> {
+    let mut contents = vec![];
+    let read = if args.file == "-" {
+        io::stdin().lock().read_to_end(&mut contents)
+    } else {
+        File::open(&args.file).and_then(|file| BufReader::new(file).read_to_end(&mut contents))
+    };
+    read.map_err(|e| format!("{}: {}", args.file, e))?;
+
+    let leaves: Vec<Vec<u8>> = match args.chunk_size {
+        Some(0) => return Err("chunk size must be positive".to_string()),
+        Some(chunk_size) => contents.chunks(chunk_size).map(<[u8]>::to_vec).collect(),
+        None => {
+            // A trailing newline ends the last line rather than starting an empty one
+            let text = contents.strip_suffix(b"\n").unwrap_or(&contents);
+            if contents.is_empty() {
+                vec![]
+            } else {
+                text.split(|b| *b == b'\n')
+                    .map(|line| line.strip_suffix(b"\r").unwrap_or(line).to_vec())
+                    .collect()
+            }
+        }
+    };
+    let root = merkle::merkle_root::<F, _>(&leaves).ok_or_else(|| format!("{}: no leaves", args.file))?;
+
+    let root = format_field(&root, format);
+    if json {
+        println!("{}", json!({ "curve": curve.name(), "op": "merkle-root", "leaves": leaves.len(), "root": root }));
+    } else {
+        println!("{}", root);
+    }
+    Ok(())
+}
@@ -0,0 +1,548 @@
+// The `prove` and `verify` subcommands.
+//
+// A statement file is a JSON object naming the circuit, the curve, the input
+// values and which of the inputs and output are public:
+//
+//   {
+//     "circuit": "cipher",
+//     "curve": "pallas",
+//     "inputs": { "message": "1", "key": "0x2" },
+//     "public": ["ciphertext"]
+//   }
+//
+// The inputs are "message" and "key" for the MiMC5 circuits and
+// "message_left", "message_right" and "key" for the Feistel circuits; the hash
+// circuits take no key. "public" may name "message", "key" and the output
+// ("hash" or "ciphertext"); it defaults to just the output.
+//
+// A proof file is a JSON object with the circuit, curve, public names, the
+// public inputs in instance order and the hex-encoded proof. Params are cached
+// in a per-user cache directory, as are key pins, which are checked against a
+// fresh keygen and rewritten if missing or stale.
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::Arc;
+use std::thread;
+
+use clap::{Args, ValueEnum};
+use halo2_proofs::{arithmetic::CurveAffine, plonk};
+use pasta_curves::group::ff::PrimeField;
+use pasta_curves::{pallas, vesta, Fp, Fq};
+use serde_json::{json, Map, Value};
+
+use mimc_halo2::circuits::{
+    mimc_cipher::MiMC5CipherCircuit, mimc_feistel_cipher::MiMC5FeistelCipherCircuit,
+    mimc_feistel_hash::MiMC5FeistelHashCircuit, mimc_hash::MiMC5HashCircuit, Visibility,
+};
+use mimc_halo2::artifacts::ArtifactError;
+use mimc_halo2::field::MiMCField;
+use mimc_halo2::field_io::{format_field, parse_field, FieldFormat};
+use mimc_halo2::prover::{MiMCProver, MiMCStatement, MiMCVerifier};
+
+use crate::Curve;
+
+#[derive(Args)]
+pub struct ProveArgs {
+    /// Statement file with the circuit, inputs and public values
+    statement: PathBuf,
+    /// Where to write the proof file
+    #[arg(short, long)]
+    output: PathBuf,
+    #[command(flatten)]
+    cache: CacheArgs,
+}
+
+#[derive(Args)]
+pub struct VerifyArgs {
+    /// Proof file written by `prove`
+    proof: PathBuf,
+    #[command(flatten)]
+    cache: CacheArgs,
+}
+
+// The cache is trusted input. Params read from it are used as they are, so
+// whoever can write to the directory chooses the params of every proof and
+// verification; it must be no more writable than the binary itself.
+#[derive(Args)]
+pub struct CacheArgs {
+    /// Directory holding cached params and key pins [default: the user's cache directory]
+    #[arg(long, env = "MIMC_CACHE_DIR", default_value_os_t = default_cache_dir(), hide_default_value = true)]
+    pub cache_dir: PathBuf,
+}
+
+// A per-user directory, e.g. ~/.cache/mimc-halo2 on Linux, or .mimc-cache
+// in the working directory where the platform has none
+fn default_cache_dir() -> PathBuf {
+    dirs::cache_dir().map_or_else(|| PathBuf::from(".mimc-cache"), |dir| dir.join("mimc-halo2"))
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum CircuitKind {
+    Hash,
+    Cipher,
+    FeistelHash,
+    FeistelCipher,
+}
+
+impl CircuitKind {
+    fn name(self) -> &'static str {
+        match self {
+            CircuitKind::Hash => "hash",
+            CircuitKind::Cipher => "cipher",
+            CircuitKind::FeistelHash => "feistel-hash",
+            CircuitKind::FeistelCipher => "feistel-cipher",
+        }
+    }
+
+    fn inputs(self) -> &'static [&'static str] {
+        match self {
+            CircuitKind::Hash => &["message"],
+            CircuitKind::Cipher => &["message", "key"],
+            CircuitKind::FeistelHash => &["message_left", "message_right"],
+            CircuitKind::FeistelCipher => &["message_left", "message_right", "key"],
+        }
+    }
+
+    fn output(self) -> &'static str {
+        match self {
+            CircuitKind::Hash | CircuitKind::FeistelHash => "hash",
+            CircuitKind::Cipher | CircuitKind::FeistelCipher => "ciphertext",
+        }
+    }
+
+    fn has_key(self) -> bool {
+        matches!(self, CircuitKind::Cipher | CircuitKind::FeistelCipher)
+    }
+}
+
+// Visibility of the message, key and output of a circuit
+#[derive(Clone, Copy)]
+struct Shape {
+    message: Visibility,
+    key: Visibility,
+    output: Visibility,
+}
+
+impl Shape {
+    fn from_names(kind: CircuitKind, names: &[String]) -> Result<Self, String> {
+        let mut shape = Shape {
+            message: Visibility::Private,
+            key: Visibility::Private,
+            output: Visibility::Private,
+        };
+        for name in names {
+            match name.as_str() {
+                "message" => shape.message = Visibility::Public,
+                "key" if kind.has_key() => shape.key = Visibility::Public,
+                name if name == kind.output() => shape.output = Visibility::Public,
+                name => return Err(format!("{} circuit has no value {:?} to make public", kind.name(), name)),
+            }
+        }
+        Ok(shape)
+    }
+
+    fn names(self, kind: CircuitKind) -> Vec<&'static str> {
+        let mut names = vec![];
+        if self.message.is_public() {
+            names.push("message");
+        }
+        if kind.has_key() && self.key.is_public() {
+            names.push("key");
+        }
+        if self.output.is_public() {
+            names.push(kind.output());
+        }
+        names
+    }
+
+    // Distinguishes cached keys of the same circuit with different public values
+    fn tag(self, kind: CircuitKind) -> String {
+        match self.names(kind) {
+            names if names.is_empty() => "private".to_string(),
+            names => names.join("+"),
+        }
+    }
+}
+
+// A statement or proof file header: everything needed to pick the circuit
+pub struct Header {
+    kind: CircuitKind,
+    curve: Curve,
+    shape: Shape,
+}
+
+impl Header {
+    fn parse(object: &Map<String, Value>) -> Result<Self, String> {
+        let kind = string_field(object, "circuit")?;
+        let kind = CircuitKind::from_str(kind, true).map_err(|_| format!("unknown circuit {:?}", kind))?;
+        let curve = string_field(object, "curve")?;
+        let curve = Curve::from_str(curve, true).map_err(|_| format!("unknown curve {:?}", curve))?;
+        let shape = match object.get("public") {
+            None => Shape::from_names(kind, &[kind.output().to_string()])?,
+            Some(Value::Array(names)) => {
+                let names = names
+                    .iter()
+                    .map(|name| name.as_str().map(str::to_string).ok_or("\"public\" must be a list of names"))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Shape::from_names(kind, &names)?
+            }
+            Some(_) => return Err("\"public\" must be a list of names".to_string()),
+        };
+        Ok(Header { kind, curve, shape })
+    }
+
+    // Names the key of this circuit and shape, e.g. "hash-pallas-message+hash"
+    pub fn key_name(&self) -> String {
+        format!("{}-{}-{}", self.kind.name(), self.curve.name(), self.shape.tag(self.kind))
+    }
+}
+
+pub fn prove(args: &ProveArgs) -> Result<(), String> {
+    let statement = read_json(&args.statement)?;
+    let proof_file = prove_json(&statement, &DiskKeys::new(&args.cache.cache_dir))?;
+    let contents = serde_json::to_string_pretty(&proof_file).map_err(|e| e.to_string())?;
+    fs::write(&args.output, contents + "\n").map_err(|e| format!("{}: {}", args.output.display(), e))
+}
+
+pub fn verify(args: &VerifyArgs, json: bool) -> Result<(), String> {
+    let proof_file = read_json(&args.proof)?;
+    let result = verify_json(&proof_file, &DiskKeys::new(&args.cache.cache_dir))?;
+    if json {
+        println!("{}", json!({ "circuit": proof_file["circuit"], "valid": result.is_ok() }));
+    } else if result.is_ok() {
+        println!("proof is valid");
+    }
+    result.map_err(|e| format!("{}: proof is invalid: {}", args.proof.display(), e))
+}
+
+// Proves a parsed statement file, returning the proof file
+pub fn prove_json<K: KeySource>(statement: &Map<String, Value>, keys: &K) -> Result<Value, String> {
+    let header = Header::parse(statement)?;
+    let inputs = match statement.get("inputs") {
+        Some(Value::Object(inputs)) => inputs,
+        _ => return Err("statement has no \"inputs\" object".to_string()),
+    };
+    match header.curve {
+        Curve::Pallas => prove_in::<Fp, vesta::Affine, K>(&header, inputs, keys),
+        Curve::Vesta => prove_in::<Fq, pallas::Affine, K>(&header, inputs, keys),
+    }
+}
+
+// Checks a parsed proof file. The outer error is a malformed file, the inner
+// one a proof that does not verify.
+pub fn verify_json<K: KeySource>(
+    proof_file: &Map<String, Value>,
+    keys: &K,
+) -> Result<Result<(), plonk::Error>, String> {
+    let header = Header::parse(proof_file)?;
+    match header.curve {
+        Curve::Pallas => verify_in::<Fp, vesta::Affine, K>(&header, proof_file, keys),
+        Curve::Vesta => verify_in::<Fq, pallas::Affine, K>(&header, proof_file, keys),
+    }
+}
+
+// Proves over field F with commitments on curve C, whose scalars are F
+fn prove_in<F, C, K>(header: &Header, inputs: &Map<String, Value>, keys: &K) -> Result<Value, String>
+where
+    F: MiMCField + PrimeField<Repr = [u8; 32]>,
+    C: CurveAffine<ScalarExt = F>,
+    K: KeySource,
+{
+    for name in inputs.keys() {
+        if !header.kind.inputs().contains(&name.as_str()) {
+            return Err(format!("{} circuit has no input {:?}", header.kind.name(), name));
+        }
+    }
+    let input = |name: &str| -> Result<F, String> {
+        let value = string_field(inputs, name).map_err(|_| format!("missing input {:?}", name))?;
+        parse_field::<F>(value).map_err(|e| format!("input {:?}: {}", name, e))
+    };
+    let Shape { message, key, output } = header.shape;
+
+    let (public_inputs, proof) = match header.kind {
+        CircuitKind::Hash => prove_statement::<C, _, K>(
+            header,
+            MiMC5HashCircuit::<F>::with_visibility(input("message")?, message, output),
+            keys,
+        )?,
+        CircuitKind::Cipher => prove_statement::<C, _, K>(
+            header,
+            MiMC5CipherCircuit::<F>::with_visibility(input("message")?, input("key")?, message, key, output),
+            keys,
+        )?,
+        CircuitKind::FeistelHash => prove_statement::<C, _, K>(
+            header,
+            MiMC5FeistelHashCircuit::<F>::with_visibility(
+                input("message_left")?,
+                input("message_right")?,
+                message,
+                output,
+            ),
+            keys,
+        )?,
+        CircuitKind::FeistelCipher => prove_statement::<C, _, K>(
+            header,
+            MiMC5FeistelCipherCircuit::<F>::with_visibility(
+                input("message_left")?,
+                input("message_right")?,
+                input("key")?,
+                message,
+                key,
+                output,
+            ),
+            keys,
+        )?,
+    };
+
+    Ok(json!({
+        "circuit": header.kind.name(),
+        "curve": header.curve.name(),
+        "public": header.shape.names(header.kind),
+        "public_inputs": public_inputs.iter().map(|v| format_field(v, FieldFormat::Hex)).collect::<Vec<_>>(),
+        "proof": to_hex(&proof),
+    }))
+}
+
+fn verify_in<F, C, K>(
+    header: &Header,
+    proof_file: &Map<String, Value>,
+    keys: &K,
+) -> Result<Result<(), plonk::Error>, String>
+where
+    F: MiMCField + PrimeField<Repr = [u8; 32]>,
+    C: CurveAffine<ScalarExt = F>,
+    K: KeySource,
+{
+    let public_inputs = match proof_file.get("public_inputs") {
+        Some(Value::Array(values)) => values
+            .iter()
+            .map(|value| {
+                let value = value.as_str().ok_or("public inputs must be strings")?;
+                parse_field::<F>(value).map_err(|e| format!("public input {:?}: {}", value, e))
+            })
+            .collect::<Result<Vec<F>, String>>()?,
+        _ => return Err("proof file has no \"public_inputs\" list".to_string()),
+    };
+    let proof = from_hex(string_field(proof_file, "proof")?)?;
+
+    let Shape { message, key, output } = header.shape;
+    let zero = F::zero();
+    match header.kind {
+        CircuitKind::Hash => verify_statement::<C, _, K>(
+            header,
+            MiMC5HashCircuit::<F>::with_visibility(zero, message, output),
+            &public_inputs,
+            &proof,
+            keys,
+        ),
+        CircuitKind::Cipher => verify_statement::<C, _, K>(
+            header,
+            MiMC5CipherCircuit::<F>::with_visibility(zero, zero, message, key, output),
+            &public_inputs,
+            &proof,
+            keys,
+        ),
+        CircuitKind::FeistelHash => verify_statement::<C, _, K>(
+            header,
+            MiMC5FeistelHashCircuit::<F>::with_visibility(zero, zero, message, output),
+            &public_inputs,
+            &proof,
+            keys,
+        ),
+        CircuitKind::FeistelCipher => verify_statement::<C, _, K>(
+            header,
+            MiMC5FeistelCipherCircuit::<F>::with_visibility(zero, zero, zero, message, key, output),
+            &public_inputs,
+            &proof,
+            keys,
+        ),
+    }
+}
+
+fn prove_statement<C: CurveAffine, S: MiMCStatement<C::Scalar> + Send + Sync + 'static, K: KeySource>(
+    header: &Header,
+    statement: S,
+    keys: &K,
+) -> Result<(Vec<C::Scalar>, Vec<u8>), String> {
+    let prover = keys.prover::<C, S>(header, &statement)?;
+    let proof = prover.prove(&statement).map_err(|e| format!("proving failed: {:?}", e))?;
+    Ok((statement.public_inputs(), proof))
+}
+
+fn verify_statement<C: CurveAffine, S: MiMCStatement<C::Scalar> + Send + Sync + 'static, K: KeySource>(
+    header: &Header,
+    shape: S,
+    public_inputs: &[C::Scalar],
+    proof: &[u8],
+    keys: &K,
+) -> Result<Result<(), plonk::Error>, String> {
+    let expected_len = shape.public_inputs().len();
+    if public_inputs.len() != expected_len {
+        return Err(format!("expected {} public inputs, found {}", expected_len, public_inputs.len()));
+    }
+    let verifier = keys.verifier::<C, S>(header, &shape)?;
+    Ok(verifier.verify(public_inputs, proof))
+}
+
+// Where provers and verifiers come from: the on-disk cache for one-shot
+// commands, or an in-memory cache in front of it for the daemon
+pub trait KeySource {
+    fn prover<C: CurveAffine, S: MiMCStatement<C::Scalar> + Send + Sync + 'static>(
+        &self,
+        header: &Header,
+        shape: &S,
+    ) -> Result<Arc<MiMCProver<C, S>>, String>;
+
+    fn verifier<C: CurveAffine, S: MiMCStatement<C::Scalar> + Send + Sync + 'static>(
+        &self,
+        header: &Header,
+        shape: &S,
+    ) -> Result<Arc<MiMCVerifier<C, S>>, String>;
+}
+
+// Params and pinned keys cached as artifact files in a directory
+pub struct DiskKeys {
+    cache_dir: PathBuf,
+}
+
+impl DiskKeys {
+    pub fn new(cache_dir: &Path) -> Self {
+        Self { cache_dir: cache_dir.to_path_buf() }
+    }
+
+    // Params are shared by every circuit over the same field with the same k;
+    // key pins are per circuit and public shape
+    fn artifact_paths<C: CurveAffine, S: MiMCStatement<C::Scalar>>(&self, header: &Header) -> (PathBuf, PathBuf) {
+        let params_path = self.cache_dir.join(format!("params-{}-k{}.bin", header.curve.name(), S::K));
+        let key_pin_path = self.cache_dir.join(header.key_name() + ".keypin");
+        (params_path, key_pin_path)
+    }
+
+    // Writes to a temporary file first so concurrent readers never see a
+    // partial artifact
+    fn write_artifacts(
+        &self,
+        paths: (PathBuf, PathBuf),
+        write: impl FnOnce(&mut Vec<u8>, &mut Vec<u8>) -> Result<(), ArtifactError>,
+    ) -> Result<(), String> {
+        let (mut params, mut key) = (vec![], vec![]);
+        write(&mut params, &mut key).map_err(|e| format!("writing cache: {}", e))?;
+        fs::create_dir_all(&self.cache_dir).map_err(|e| format!("{}: {}", self.cache_dir.display(), e))?;
+        for (path, contents) in [(paths.0, params), (paths.1, key)] {
+            let temp_path = path.with_extension(format!("tmp-{}-{:?}", process::id(), thread::current().id()));
+            fs::write(&temp_path, contents)
+                .and_then(|()| fs::rename(&temp_path, &path))
+                .map_err(|e| format!("{}: {}", path.display(), e))?;
+        }
+        Ok(())
+    }
+}
+
+fn open_artifacts(paths: &(PathBuf, PathBuf)) -> Option<(BufReader<File>, BufReader<File>)> {
+    let params = File::open(&paths.0).ok()?;
+    let key = File::open(&paths.1).ok()?;
+    Some((BufReader::new(params), BufReader::new(key)))
+}
+
+impl KeySource for DiskKeys {
+    fn prover<C: CurveAffine, S: MiMCStatement<C::Scalar> + Send + Sync + 'static>(
+        &self,
+        header: &Header,
+        shape: &S,
+    ) -> Result<Arc<MiMCProver<C, S>>, String> {
+        let paths = self.artifact_paths::<C, S>(header);
+        let cached = open_artifacts(&paths)
+            .and_then(|(mut params, mut key)| MiMCProver::<C, S>::read_artifacts(&mut params, &mut key, shape).ok());
+        if let Some(prover) = cached {
+            return Ok(Arc::new(prover));
+        }
+        let prover = MiMCProver::<C, S>::with_shape(shape).map_err(|e| format!("keygen failed: {:?}", e))?;
+        self.write_artifacts(paths, |params, key| prover.write_artifacts(params, key))?;
+        Ok(Arc::new(prover))
+    }
+
+    fn verifier<C: CurveAffine, S: MiMCStatement<C::Scalar> + Send + Sync + 'static>(
+        &self,
+        header: &Header,
+        shape: &S,
+    ) -> Result<Arc<MiMCVerifier<C, S>>, String> {
+        let paths = self.artifact_paths::<C, S>(header);
+        let cached = open_artifacts(&paths)
+            .and_then(|(mut params, mut key)| MiMCVerifier::<C, S>::read_artifacts(&mut params, &mut key, shape).ok());
+        if let Some(verifier) = cached {
+            return Ok(Arc::new(verifier));
+        }
+        let verifier = MiMCVerifier::<C, S>::with_shape(shape).map_err(|e| format!("keygen failed: {:?}", e))?;
+        self.write_artifacts(paths, |params, key| verifier.write_artifacts(params, key))?;
+        Ok(Arc::new(verifier))
+    }
+}
+
+fn read_json(path: &Path) -> Result<Map<String, Value>, String> {
+    let contents = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    match serde_json::from_str(&contents) {
+        Ok(Value::Object(object)) => Ok(object),
+        Ok(_) => Err(format!("{}: expected a JSON object", path.display())),
+        Err(e) => Err(format!("{}: {}", path.display(), e)),
+    }
+}
+
+fn string_field<'a>(object: &'a Map<String, Value>, name: &str) -> Result<&'a str, String> {
+    object
+        .get(name)
+        .and_then(Value::as_str)
+        .ok_or_else(|| format!("missing string field {:?}", name))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(s: &str) -> Result<Vec<u8>, String> {
+    if !s.len().is_multiple_of(2) || !s.is_ascii() {
+        return Err("proof is not a hex string".to_string());
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).map_err(|_| "proof is not a hex string".to_string()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prove_and_verify_round_trip() {
+        let cache_dir = std::env::temp_dir().join(format!("mimc-cli-test-{}", std::process::id()));
+        let statement: Map<String, Value> = serde_json::from_str(
+            r#"{"circuit":"hash","curve":"pallas","inputs":{"message":"1"},"public":["message","hash"]}"#,
+        )
+        .unwrap();
+        let header = Header::parse(&statement).unwrap();
+        let inputs = statement["inputs"].as_object().unwrap();
+        let keys = DiskKeys::new(&cache_dir);
+        let proof_file = prove_in::<Fp, vesta::Affine, _>(&header, inputs, &keys).unwrap();
+        let mut proof_file = proof_file.as_object().unwrap().clone();
+
+        // The second run checks its keygen against the key pin written by the first
+        assert!(verify_in::<Fp, vesta::Affine, _>(&header, &proof_file, &keys).unwrap().is_ok());
+        assert!(cache_dir.join("hash-pallas-message+hash.keypin").exists());
+
+        proof_file["public_inputs"][0] = Value::from("2");
+        assert!(verify_in::<Fp, vesta::Affine, _>(&header, &proof_file, &keys).unwrap().is_err());
+        fs::remove_dir_all(&cache_dir).unwrap();
+    }
+
+    #[test]
+    fn test_statement_header_errors() {
+        let parse = |s: &str| Header::parse(&serde_json::from_str(s).unwrap()).map(|_| ());
+        assert!(parse(r#"{"circuit":"feistel-cipher","curve":"vesta"}"#).is_ok());
+        assert!(parse(r#"{"circuit":"sponge","curve":"vesta"}"#).is_err());
+        assert!(parse(r#"{"circuit":"hash","curve":"vesta","public":["key"]}"#).is_err());
+        assert_eq!(from_hex(&to_hex(&[0, 1, 254, 255])), Ok(vec![0, 1, 254, 255]));
+        assert!(from_hex("abc").is_err());
+    }
+}
@@ -0,0 +1,452 @@
+// The `serve` subcommand: a long-running prover that keeps params and keys in
+// memory and answers JSON-RPC 2.0 requests over TCP, one request per line.
+//
+// Methods:
+//   prove   params: a statement object as accepted by `prove`; result: a proof file object
+//   verify  params: a proof file object; result: { "valid": bool, "error"?: string }
+//   status  result: worker, queue and key cache counters
+//
+// Proving and verifying run on a fixed pool of worker threads fed by a bounded
+// queue. When the queue is full a request fails immediately with QUEUE_FULL
+// instead of waiting. Connections are served by a fixed pool of acceptor
+// threads, so further connections wait to be accepted. A request line longer
+// than MAX_REQUEST_LEN is skipped without being buffered and gets an
+// INVALID_REQUEST error.
+use std::any::Any;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread;
+
+use clap::Args;
+use halo2_proofs::arithmetic::CurveAffine;
+use serde_json::{json, Map, Value};
+
+use mimc_halo2::prover::{MiMCProver, MiMCStatement, MiMCVerifier};
+
+use crate::prove::{prove_json, verify_json, CacheArgs, DiskKeys, Header, KeySource};
+
+// JSON-RPC error codes
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const INTERNAL_ERROR: i64 = -32603;
+const QUEUE_FULL: i64 = -32000;
+
+// The longest request line read, newline excluded. Proof files are a few KiB.
+const MAX_REQUEST_LEN: usize = 1 << 20;
+
+#[derive(Args)]
+pub struct ServeArgs {
+    /// Address to listen on
+    #[arg(long, default_value = "127.0.0.1:7878")]
+    listen: String,
+    /// Number of proofs or verifications run at the same time
+    #[arg(long, default_value_t = 2)]
+    workers: usize,
+    /// Number of requests that may wait for a worker before new ones are rejected
+    #[arg(long, default_value_t = 64)]
+    queue: usize,
+    /// Number of connections served at the same time; further ones wait to be accepted
+    #[arg(long, default_value_t = 16)]
+    connections: usize,
+    #[command(flatten)]
+    cache: CacheArgs,
+}
+
+pub fn serve(args: &ServeArgs) -> Result<(), String> {
+    if args.workers == 0 {
+        return Err("at least one worker is needed".to_string());
+    }
+    if args.connections == 0 {
+        return Err("at least one connection is needed".to_string());
+    }
+    let disk = DiskKeys::new(&args.cache.cache_dir);
+    let server = Server::bind(&args.listen, args.workers, args.queue, args.connections, disk)?;
+    eprintln!("mimc: listening on {}", server.local_addr());
+    server.run();
+    Ok(())
+}
+
+type Job = Box<dyn FnOnce() + Send>;
+
+pub struct Server {
+    listener: TcpListener,
+    connections: usize,
+    state: Arc<State>,
+}
+
+struct State {
+    keys: MemoryKeys,
+    jobs: SyncSender<Job>,
+    // Held here too so the queue stays open even with no worker threads
+    _receiver: Arc<Mutex<Receiver<Job>>>,
+    workers: usize,
+    queue_capacity: usize,
+    queued: AtomicUsize,
+    running: AtomicUsize,
+}
+
+impl Server {
+    pub fn bind(
+        addr: &str,
+        workers: usize,
+        queue_capacity: usize,
+        connections: usize,
+        disk: DiskKeys,
+    ) -> Result<Self, String> {
+        let listener = TcpListener::bind(addr).map_err(|e| format!("{}: {}", addr, e))?;
+        let (jobs, receiver) = mpsc::sync_channel(queue_capacity);
+        let receiver = Arc::new(Mutex::new(receiver));
+        for _ in 0..workers {
+            let receiver = receiver.clone();
+            thread::spawn(move || worker(&receiver));
+        }
+        let state = Arc::new(State {
+            keys: MemoryKeys::new(disk),
+            jobs,
+            _receiver: receiver,
+            workers,
+            queue_capacity,
+            queued: AtomicUsize::new(0),
+            running: AtomicUsize::new(0),
+        });
+        Ok(Self { listener, connections, state })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.listener.local_addr().expect("listener has a local address")
+    }
+
+    // Serves connections until the process exits. Each of `connections`
+    // threads accepts and serves one connection at a time.
+    pub fn run(self) {
+        for _ in 1..self.connections {
+            match self.listener.try_clone() {
+                Ok(listener) => {
+                    let state = self.state.clone();
+                    thread::spawn(move || accept(&listener, &state));
+                }
+                Err(e) => eprintln!("mimc: serving fewer connections: {}", e),
+            }
+        }
+        accept(&self.listener, &self.state);
+    }
+}
+
+fn accept(listener: &TcpListener, state: &Arc<State>) {
+    for stream in listener.incoming().flatten() {
+        // A broken connection only ends that connection
+        let _ = handle_connection(stream, state);
+    }
+}
+
+fn worker(receiver: &Mutex<Receiver<Job>>) {
+    loop {
+        let job = match receiver.lock() {
+            Ok(receiver) => receiver.recv(),
+            Err(_) => return,
+        };
+        match job {
+            Ok(job) => job(),
+            Err(_) => return,
+        }
+    }
+}
+
+fn handle_connection(stream: TcpStream, state: &Arc<State>) -> std::io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    let mut reader = BufReader::new(stream);
+    loop {
+        // One byte past the limit tells an oversized line from one that fits
+        let mut line = String::new();
+        if reader.by_ref().take(MAX_REQUEST_LEN as u64 + 1).read_line(&mut line)? == 0 {
+            break;
+        }
+        if line.len() > MAX_REQUEST_LEN && !line.ends_with('\n') {
+            let message = format!("request longer than {} bytes", MAX_REQUEST_LEN);
+            writeln!(writer, "{}", error_response(Value::Null, INVALID_REQUEST, &message))?;
+            reader.skip_until(b'\n')?;
+            continue;
+        }
+        if line.trim().is_empty() {
+            continue;
+        }
+        if let Some(response) = handle_request(&line, state) {
+            writeln!(writer, "{}", response)?;
+        }
+    }
+    Ok(())
+}
+
+// Returns None for notifications, which get no response
+fn handle_request(line: &str, state: &Arc<State>) -> Option<Value> {
+    let request: Map<String, Value> = match serde_json::from_str(line) {
+        Ok(Value::Object(request)) => request,
+        Ok(_) => return Some(error_response(Value::Null, INVALID_REQUEST, "request must be an object")),
+        Err(e) => return Some(error_response(Value::Null, PARSE_ERROR, &e.to_string())),
+    };
+    let id = request.get("id").cloned();
+    let reply = |result: Result<Value, (i64, String)>| {
+        let id = id.clone()?;
+        Some(match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err((code, message)) => error_response(id, code, &message),
+        })
+    };
+    let method = match (request.get("jsonrpc"), request.get("method")) {
+        (Some(version), Some(Value::String(method))) if version == "2.0" => method.clone(),
+        _ => return Some(error_response(id.unwrap_or(Value::Null), INVALID_REQUEST, "not a JSON-RPC 2.0 request")),
+    };
+
+    match method.as_str() {
+        "status" => reply(Ok(state.status())),
+        "prove" | "verify" => {
+            let params = match request.get("params") {
+                Some(Value::Object(params)) => params.clone(),
+                _ => return reply(Err((INVALID_PARAMS, format!("{} takes an object", method)))),
+            };
+            reply(state.submit(move |keys| {
+                if method == "prove" {
+                    prove_json(&params, keys).map_err(|e| (INVALID_PARAMS, e))
+                } else {
+                    match verify_json(&params, keys) {
+                        Ok(Ok(())) => Ok(json!({ "valid": true })),
+                        Ok(Err(e)) => Ok(json!({ "valid": false, "error": e.to_string() })),
+                        Err(e) => Err((INVALID_PARAMS, e)),
+                    }
+                }
+            }))
+        }
+        _ => reply(Err((METHOD_NOT_FOUND, format!("unknown method {:?}", method)))),
+    }
+}
+
+fn error_response(id: Value, code: i64, message: &str) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
+
+impl State {
+    // Runs `task` on a worker and waits for its result
+    fn submit(
+        self: &Arc<Self>,
+        task: impl FnOnce(&MemoryKeys) -> Result<Value, (i64, String)> + Send + 'static,
+    ) -> Result<Value, (i64, String)> {
+        let (reply, result) = mpsc::channel();
+        let state = self.clone();
+        let job: Job = Box::new(move || {
+            state.queued.fetch_sub(1, Ordering::SeqCst);
+            state.running.fetch_add(1, Ordering::SeqCst);
+            let outcome = panic::catch_unwind(AssertUnwindSafe(|| task(&state.keys)))
+                .unwrap_or_else(|_| Err((INTERNAL_ERROR, "request panicked".to_string())));
+            state.running.fetch_sub(1, Ordering::SeqCst);
+            let _ = reply.send(outcome);
+        });
+
+        // Count the job before it can be picked up so the counter never underflows
+        self.queued.fetch_add(1, Ordering::SeqCst);
+        if let Err(e) = self.jobs.try_send(job) {
+            self.queued.fetch_sub(1, Ordering::SeqCst);
+            return Err(match e {
+                TrySendError::Full(_) => (QUEUE_FULL, "queue is full".to_string()),
+                TrySendError::Disconnected(_) => (INTERNAL_ERROR, "no workers are running".to_string()),
+            });
+        }
+        result
+            .recv()
+            .unwrap_or_else(|_| Err((INTERNAL_ERROR, "worker stopped".to_string())))
+    }
+
+    fn status(&self) -> Value {
+        json!({
+            "workers": self.workers,
+            "queue_capacity": self.queue_capacity,
+            "queued": self.queued.load(Ordering::SeqCst),
+            "running": self.running.load(Ordering::SeqCst),
+            "cached_provers": self.keys.provers.lock().map(|m| m.len()).unwrap_or(0),
+            "cached_verifiers": self.keys.verifiers.lock().map(|m| m.len()).unwrap_or(0),
+        })
+    }
+}
+
+type Slot = Arc<OnceLock<Result<Arc<dyn Any + Send + Sync>, String>>>;
+
+// Provers and verifiers kept in memory once loaded from (or written to) the
+// disk cache. Concurrent requests for the same key wait for one keygen.
+pub struct MemoryKeys {
+    disk: DiskKeys,
+    provers: Mutex<HashMap<String, Slot>>,
+    verifiers: Mutex<HashMap<String, Slot>>,
+}
+
+impl MemoryKeys {
+    fn new(disk: DiskKeys) -> Self {
+        Self {
+            disk,
+            provers: Mutex::new(HashMap::new()),
+            verifiers: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn get_or_load<T: Any + Send + Sync>(
+        cache: &Mutex<HashMap<String, Slot>>,
+        name: String,
+        load: impl FnOnce() -> Result<Arc<T>, String>,
+    ) -> Result<Arc<T>, String> {
+        let slot = cache.lock().unwrap().entry(name.clone()).or_default().clone();
+        let loaded = slot.get_or_init(|| load().map(|value| value as Arc<dyn Any + Send + Sync>));
+        match loaded {
+            Ok(value) => value.clone().downcast::<T>().map_err(|_| format!("cached key {} has the wrong type", name)),
+            Err(e) => {
+                // Let the next request retry rather than caching the failure
+                let mut cache = cache.lock().unwrap();
+                if cache.get(&name).is_some_and(|cached| Arc::ptr_eq(cached, &slot)) {
+                    cache.remove(&name);
+                }
+                Err(e.clone())
+            }
+        }
+    }
+}
+
+impl KeySource for MemoryKeys {
+    fn prover<C: CurveAffine, S: MiMCStatement<C::Scalar> + Send + Sync + 'static>(
+        &self,
+        header: &Header,
+        shape: &S,
+    ) -> Result<Arc<MiMCProver<C, S>>, String> {
+        Self::get_or_load(&self.provers, header.key_name(), || self.disk.prover::<C, S>(header, shape))
+    }
+
+    fn verifier<C: CurveAffine, S: MiMCStatement<C::Scalar> + Send + Sync + 'static>(
+        &self,
+        header: &Header,
+        shape: &S,
+    ) -> Result<Arc<MiMCVerifier<C, S>>, String> {
+        Self::get_or_load(&self.verifiers, header.key_name(), || self.disk.verifier::<C, S>(header, shape))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::time::Duration;
+
+    struct Client {
+        reader: BufReader<TcpStream>,
+        writer: TcpStream,
+    }
+
+    impl Client {
+        fn connect(addr: SocketAddr) -> Self {
+            let writer = TcpStream::connect(addr).unwrap();
+            Self { reader: BufReader::new(writer.try_clone().unwrap()), writer }
+        }
+
+        fn send(&mut self, request: &str) {
+            writeln!(self.writer, "{}", request).unwrap();
+        }
+
+        fn call(&mut self, request: &str) -> Value {
+            self.send(request);
+            let mut line = String::new();
+            self.reader.read_line(&mut line).unwrap();
+            serde_json::from_str(&line).unwrap()
+        }
+    }
+
+    fn start(workers: usize, queue: usize, connections: usize, name: &str) -> (SocketAddr, std::path::PathBuf) {
+        let cache_dir = std::env::temp_dir().join(format!("mimc-serve-test-{}-{}", name, std::process::id()));
+        let server = Server::bind("127.0.0.1:0", workers, queue, connections, DiskKeys::new(&cache_dir)).unwrap();
+        let addr = server.local_addr();
+        thread::spawn(move || server.run());
+        (addr, cache_dir)
+    }
+
+    #[test]
+    fn test_serve_prove_and_verify() {
+        let (addr, cache_dir) = start(2, 4, 2, "prove");
+        let mut client = Client::connect(addr);
+
+        let statement = r#"{"circuit":"hash","curve":"vesta","inputs":{"message":"1"}}"#;
+        let response = client.call(&format!(r#"{{"jsonrpc":"2.0","id":1,"method":"prove","params":{}}}"#, statement));
+        assert_eq!(response["id"], 1);
+        let proof_file = response["result"].clone();
+        assert_eq!(proof_file["public"], json!(["hash"]));
+
+        let response = client.call(&json!({ "jsonrpc": "2.0", "id": 2, "method": "verify", "params": proof_file }).to_string());
+        assert_eq!(response["result"]["valid"], true);
+
+        let mut wrong_hash = proof_file;
+        wrong_hash["public_inputs"][0] = json!("7");
+        let response = client.call(&json!({ "jsonrpc": "2.0", "id": 3, "method": "verify", "params": wrong_hash }).to_string());
+        assert_eq!(response["result"]["valid"], false);
+
+        // The prover and verifier keys stay in memory for later requests
+        let status = client.call(r#"{"jsonrpc":"2.0","id":4,"method":"status"}"#);
+        assert_eq!(status["result"]["cached_provers"], 1);
+        assert_eq!(status["result"]["cached_verifiers"], 1);
+
+        let response = client.call(r#"{"jsonrpc":"2.0","id":5,"method":"prove","params":{"circuit":"hash"}}"#);
+        assert_eq!(response["error"]["code"], INVALID_PARAMS);
+        let response = client.call(r#"{"jsonrpc":"2.0","id":6,"method":"sign"}"#);
+        assert_eq!(response["error"]["code"], METHOD_NOT_FOUND);
+        let response = client.call("{not json");
+        assert_eq!(response["error"]["code"], PARSE_ERROR);
+
+        fs::remove_dir_all(&cache_dir).unwrap();
+    }
+
+    #[test]
+    fn test_serve_rejects_requests_when_queue_is_full() {
+        // With no workers the first request stays queued forever
+        let (addr, _) = start(0, 1, 2, "queue");
+        let request = r#"{"jsonrpc":"2.0","id":1,"method":"verify","params":{}}"#;
+        let mut blocked = Client::connect(addr);
+        blocked.send(request);
+
+        let mut client = Client::connect(addr);
+        while client.call(r#"{"jsonrpc":"2.0","id":0,"method":"status"}"#)["result"]["queued"] != 1 {
+            thread::sleep(Duration::from_millis(10));
+        }
+        let response = client.call(request);
+        assert_eq!(response["error"]["code"], QUEUE_FULL);
+    }
+
+    #[test]
+    fn test_serve_caps_connections() {
+        let (addr, _) = start(1, 1, 1, "connections");
+        let status = r#"{"jsonrpc":"2.0","id":0,"method":"status"}"#;
+        let mut first = Client::connect(addr);
+        assert_eq!(first.call(status)["id"], 0);
+
+        // The second connection is not served while the first is open
+        let mut second = Client::connect(addr);
+        second.send(status);
+        second.reader.get_ref().set_read_timeout(Some(Duration::from_millis(200))).unwrap();
+        let mut line = String::new();
+        assert!(second.reader.read_line(&mut line).is_err());
+
+        drop(first);
+        second.reader.get_ref().set_read_timeout(None).unwrap();
+        second.reader.read_line(&mut line).unwrap();
+        assert_eq!(serde_json::from_str::<Value>(&line).unwrap()["id"], 0);
+    }
+
+    #[test]
+    fn test_serve_rejects_oversized_requests() {
+        let (addr, _) = start(1, 1, 1, "oversized");
+        let mut client = Client::connect(addr);
+        let response = client.call(&"x".repeat(MAX_REQUEST_LEN + 1));
+        assert_eq!(response["error"]["code"], INVALID_REQUEST);
+
+        // The rest of the line is skipped and the connection stays usable
+        assert_eq!(client.call(r#"{"jsonrpc":"2.0","id":1,"method":"status"}"#)["id"], 1);
+    }
+}
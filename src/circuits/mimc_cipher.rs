@@ -0,0 +1,272 @@
+use halo2_proofs::{
+    plonk::{
+        Circuit, Column, Advice, Instance, ConstraintSystem, Error,
+    },
+    circuit::{
+        Layouter, SimpleFloorPlanner, Value,
+    },
+};
+use pasta_curves::{Fp, Fq};
+
+use crate::mimc::{
+    mimc_cipher::{MiMC5CipherConfig, MiMC5CipherChip},
+    primitives::mimc5_encrypt,
+};
+use super::Visibility;
+use crate::field::MiMCField;
+use crate::layout::{ColumnLabels, LabeledConfig};
+
+
+#[derive(Debug, Clone)]
+pub struct MiMC5CipherCircuitConfig {
+    input: Column<Advice>,
+    instance: Column<Instance>,
+    mimc_config: MiMC5CipherConfig,
+}
+
+impl LabeledConfig for MiMC5CipherCircuitConfig {
+    fn labels(&self) -> ColumnLabels {
+        ColumnLabels::default()
+            .column(self.input, "input")
+            .column(self.instance, "instance")
+            .extend(self.mimc_config.labels())
+    }
+}
+
+// Proves that a ciphertext is the MiMC5 encryption of a message under a key.
+// By default the message and key are private and the ciphertext is public.
+pub struct MiMC5CipherCircuit<F: MiMCField> {
+    pub message: F,
+    pub key: F,
+    pub message_visibility: Visibility,
+    pub key_visibility: Visibility,
+    pub ciphertext_visibility: Visibility,
+}
+
+pub type MiMC5CipherPallasCircuit = MiMC5CipherCircuit<Fp>;
+pub type MiMC5CipherVestaCircuit = MiMC5CipherCircuit<Fq>;
+
+impl<F: MiMCField> MiMC5CipherCircuit<F> {
+    pub fn new(message: F, key: F) -> Self {
+        Self::with_visibility(message, key, Visibility::Private, Visibility::Private, Visibility::Public)
+    }
+
+    pub fn with_visibility(
+        message: F,
+        key: F,
+        message_visibility: Visibility,
+        key_visibility: Visibility,
+        ciphertext_visibility: Visibility,
+    ) -> Self {
+        Self {
+            message,
+            key,
+            message_visibility,
+            key_visibility,
+            ciphertext_visibility,
+        }
+    }
+
+    pub fn ciphertext(&self) -> F {
+        let mut state = self.message;
+        mimc5_encrypt(&mut state, self.key, F::MIMC5_ROUND_CONSTANTS);
+        state
+    }
+
+    // The instance column expected by this circuit: the message, key and
+    // ciphertext in that order, each only if public
+    pub fn public_inputs(&self) -> Vec<F> {
+        let mut public_inputs = vec![];
+        if self.message_visibility.is_public() {
+            public_inputs.push(self.message);
+        }
+        if self.key_visibility.is_public() {
+            public_inputs.push(self.key);
+        }
+        if self.ciphertext_visibility.is_public() {
+            public_inputs.push(self.ciphertext());
+        }
+        public_inputs
+    }
+}
+
+impl<F: MiMCField> Clone for MiMC5CipherCircuit<F> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<F: MiMCField> Copy for MiMC5CipherCircuit<F> {}
+
+impl<F: MiMCField> Default for MiMC5CipherCircuit<F> {
+    fn default() -> Self {
+        Self::new(F::zero(), F::zero())
+    }
+}
+
+impl<F: MiMCField> Circuit<F> for MiMC5CipherCircuit<F> {
+    type Config = MiMC5CipherCircuitConfig;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self::with_visibility(
+            F::zero(),
+            F::zero(),
+            self.message_visibility,
+            self.key_visibility,
+            self.ciphertext_visibility,
+        )
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        let circuit_input = meta.advice_column();
+        meta.enable_equality(circuit_input);
+        let instance = meta.instance_column();
+        meta.enable_equality(instance);
+        let state = meta.advice_column();
+        let key_column = meta.advice_column();
+        let round_constants = meta.fixed_column();
+        Self::Config {
+            input: circuit_input,
+            instance,
+            mimc_config: MiMC5CipherChip::configure(meta, state, key_column, round_constants)
+        }
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        let chip = MiMC5CipherChip::construct(config.mimc_config);
+
+        let message = layouter.assign_region(
+            || "load message",
+            |mut region| {
+                region.assign_advice(
+                    || "load input message",
+                    config.input,
+                    0,
+                    || Value::known(self.message)
+                )
+            }
+        )?;
+
+        let key = layouter.assign_region(
+            || "load key",
+            |mut region| {
+                region.assign_advice(
+                    || "load encryption key",
+                    config.input,
+                    0,
+                    || Value::known(self.key)
+                )
+            }
+        )?;
+
+        let ciphertext = chip.encrypt_message(
+            layouter.namespace(|| "entire table"),
+            &message,
+            &key,
+        )?;
+
+        let mut instance_row = 0;
+        for (cell, visibility) in [
+            (&message, self.message_visibility),
+            (&key, self.key_visibility),
+            (&ciphertext, self.ciphertext_visibility),
+        ] {
+            if visibility.is_public() {
+                layouter.constrain_instance(cell.cell(), config.instance, instance_row)?;
+                instance_row += 1;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use halo2_proofs::dev::MockProver;
+
+    use crate::assignment::{AssignmentTable, ColumnRef, ReplayCircuit};
+    use crate::diagnostics::explain_circuit_failures;
+
+    #[test]
+    fn test_mimc5_pallas_cipher_circuit() {
+        let k = 7;
+
+        let circuit = MiMC5CipherPallasCircuit::new(Fp::from(1), Fp::from(2));
+        let prover = MockProver::run(k, &circuit, vec![circuit.public_inputs()]).unwrap();
+        prover.assert_satisfied();
+
+        let wrong_ciphertext = vec![circuit.ciphertext() + Fp::one()];
+        let prover = MockProver::run(k, &circuit, vec![wrong_ciphertext]).unwrap();
+        assert!(prover.verify().is_err());
+    }
+
+    #[test]
+    fn test_mimc5_vesta_cipher_circuit_with_public_key() {
+        let k = 7;
+
+        let circuit = MiMC5CipherVestaCircuit::with_visibility(
+            Fq::from(1),
+            Fq::from(2),
+            Visibility::Private,
+            Visibility::Public,
+            Visibility::Public,
+        );
+        let prover = MockProver::run(k, &circuit, vec![circuit.public_inputs()]).unwrap();
+        prover.assert_satisfied();
+
+        let wrong_key = vec![Fq::from(3), circuit.ciphertext()];
+        let prover = MockProver::run(k, &circuit, vec![wrong_key]).unwrap();
+        assert!(prover.verify().is_err());
+    }
+
+    // A witness that encrypts under another key in one round, with the later
+    // states computed from it and that key cell not copied from the key. The
+    // key comparison used to read the current row twice, so this witness
+    // satisfied every gate; it now fails exactly the comparisons of the
+    // changed key with its neighbours.
+    #[test]
+    fn test_mimc5_cipher_rejects_changed_round_key() {
+        let k = 7;
+        let (message, key, other_key, round) = (Fp::from(3), Fp::from(5), Fp::from(6), 37);
+
+        let circuit =
+            MiMC5CipherPallasCircuit::with_visibility(message, key, Visibility::Private, Visibility::Private, Visibility::Private);
+        let mut table = AssignmentTable::synthesize(k, &circuit, vec![vec![]]).unwrap();
+        // The state column is A1 and the key column A2, and the region
+        // starts at the row holding the message
+        let start = *table.advice[1].iter().find(|(_, value)| **value == Some(message)).unwrap().0;
+        let key_cell = (ColumnRef::Advice(2), start + round);
+        table.copies.retain(|(left, right)| *left != key_cell && *right != key_cell);
+        table.advice[2].insert(start + round, Some(other_key));
+        let rounds = Fp::MIMC5_ROUND_CONSTANTS.len();
+        let mut state = table.advice[1][&(start + round - 1)].unwrap();
+        for i in round..=rounds {
+            let round_key = if i == round { other_key } else { key };
+            let x = state + round_key + Fp::MIMC5_ROUND_CONSTANTS[i - 1];
+            state = x.square().square() * x;
+            table.advice[1].insert(start + i, Some(state));
+        }
+        table.advice[1].insert(start + rounds + 1, Some(state + key));
+
+        let replay = ReplayCircuit::<Fp, MiMC5CipherPallasCircuit>::new(table.clone()).unwrap();
+        let failures = MockProver::run(k, &replay, table.instance).unwrap().verify().unwrap_err();
+        let reports: Vec<_> = explain_circuit_failures::<Fp, MiMC5CipherPallasCircuit>(&failures)
+            .into_iter()
+            .map(|report| (report.round, report.message))
+            .collect();
+        assert_eq!(
+            reports,
+            vec![
+                (Some(37), "key 0x6 differs from the key 0x5 of the previous round".to_string()),
+                (Some(38), "key 0x5 differs from the key 0x6 of the previous round".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,314 @@
+use halo2_proofs::{
+    plonk::{
+        Circuit, Column, Advice, Instance, ConstraintSystem, Error,
+    },
+    circuit::{
+        Layouter, SimpleFloorPlanner, Value,
+    },
+};
+use pasta_curves::{Fp, Fq};
+
+use crate::mimc_feistel::{
+    mimc_feistel_cipher::{
+        MiMC5FeistelCipherConfig, MiMC5FeistelCipherChip
+    },
+    primitives::mimc5_feistel_encrypt,
+};
+use super::Visibility;
+use crate::field::MiMCField;
+use crate::layout::{ColumnLabels, LabeledConfig};
+
+
+#[derive(Debug, Clone)]
+pub struct MiMC5FeistelCipherCircuitConfig {
+    input: Column<Advice>,
+    instance: Column<Instance>,
+    mimc_config: MiMC5FeistelCipherConfig,
+}
+
+impl LabeledConfig for MiMC5FeistelCipherCircuitConfig {
+    fn labels(&self) -> ColumnLabels {
+        ColumnLabels::default()
+            .column(self.input, "input")
+            .column(self.instance, "instance")
+            .extend(self.mimc_config.labels())
+    }
+}
+
+// Proves that a two-element ciphertext is the MiMC5 Feistel encryption of a
+// message under a key. By default only the ciphertext is public.
+pub struct MiMC5FeistelCipherCircuit<F: MiMCField> {
+    pub message_left: F,
+    pub message_right: F,
+    pub key: F,
+    pub message_visibility: Visibility,
+    pub key_visibility: Visibility,
+    pub ciphertext_visibility: Visibility,
+}
+
+pub type MiMC5FeistelCipherPallasCircuit = MiMC5FeistelCipherCircuit<Fp>;
+pub type MiMC5FeistelCipherVestaCircuit = MiMC5FeistelCipherCircuit<Fq>;
+
+impl<F: MiMCField> MiMC5FeistelCipherCircuit<F> {
+    pub fn new(message_left: F, message_right: F, key: F) -> Self {
+        Self::with_visibility(
+            message_left,
+            message_right,
+            key,
+            Visibility::Private,
+            Visibility::Private,
+            Visibility::Public,
+        )
+    }
+
+    pub fn with_visibility(
+        message_left: F,
+        message_right: F,
+        key: F,
+        message_visibility: Visibility,
+        key_visibility: Visibility,
+        ciphertext_visibility: Visibility,
+    ) -> Self {
+        Self {
+            message_left,
+            message_right,
+            key,
+            message_visibility,
+            key_visibility,
+            ciphertext_visibility,
+        }
+    }
+
+    pub fn ciphertext(&self) -> (F, F) {
+        let mut state_left = self.message_left;
+        let mut state_right = self.message_right;
+        mimc5_feistel_encrypt(&mut state_left, &mut state_right, self.key, F::MIMC5_FEISTEL_ROUND_CONSTANTS);
+        (state_left, state_right)
+    }
+
+    // The instance column expected by this circuit: the message halves, the
+    // key and the ciphertext halves in that order, each only if public
+    pub fn public_inputs(&self) -> Vec<F> {
+        let mut public_inputs = vec![];
+        if self.message_visibility.is_public() {
+            public_inputs.extend([self.message_left, self.message_right]);
+        }
+        if self.key_visibility.is_public() {
+            public_inputs.push(self.key);
+        }
+        if self.ciphertext_visibility.is_public() {
+            let (ciphertext_left, ciphertext_right) = self.ciphertext();
+            public_inputs.extend([ciphertext_left, ciphertext_right]);
+        }
+        public_inputs
+    }
+}
+
+impl<F: MiMCField> Clone for MiMC5FeistelCipherCircuit<F> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<F: MiMCField> Copy for MiMC5FeistelCipherCircuit<F> {}
+
+impl<F: MiMCField> Default for MiMC5FeistelCipherCircuit<F> {
+    fn default() -> Self {
+        Self::new(F::zero(), F::zero(), F::zero())
+    }
+}
+
+impl<F: MiMCField> Circuit<F> for MiMC5FeistelCipherCircuit<F> {
+    type Config = MiMC5FeistelCipherCircuitConfig;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self::with_visibility(
+            F::zero(),
+            F::zero(),
+            F::zero(),
+            self.message_visibility,
+            self.key_visibility,
+            self.ciphertext_visibility,
+        )
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        let circuit_input = meta.advice_column();
+        meta.enable_equality(circuit_input);
+        let instance = meta.instance_column();
+        meta.enable_equality(instance);
+        let state_left = meta.advice_column();
+        let state_right = meta.advice_column();
+        let key_column = meta.advice_column();
+        let round_constants = meta.fixed_column();
+        Self::Config {
+            input: circuit_input,
+            instance,
+            mimc_config: MiMC5FeistelCipherChip::configure(meta, state_left, state_right, key_column, round_constants)
+        }
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        let chip = MiMC5FeistelCipherChip::construct(config.mimc_config);
+
+        let message_left = layouter.assign_region(
+            || "load left part of message",
+            |mut region| {
+                region.assign_advice(
+                    || "load input message",
+                    config.input,
+                    0,
+                    || Value::known(self.message_left)
+                )
+            }
+        )?;
+
+        let message_right = layouter.assign_region(
+            || "load right part of message",
+            |mut region| {
+                region.assign_advice(
+                    || "load input message",
+                    config.input,
+                    0,
+                    || Value::known(self.message_right)
+                )
+            }
+        )?;
+
+        let key = layouter.assign_region(
+            || "load key",
+            |mut region| {
+                region.assign_advice(
+                    || "load encryption key",
+                    config.input,
+                    0,
+                    || Value::known(self.key)
+                )
+            }
+        )?;
+
+        let (ciphertext_left, ciphertext_right) = chip.encrypt_message(
+            layouter.namespace(|| "entire table"),
+            &message_left,
+            &message_right,
+            &key,
+        )?;
+
+        let mut instance_row = 0;
+        for (cells, visibility) in [
+            (vec![&message_left, &message_right], self.message_visibility),
+            (vec![&key], self.key_visibility),
+            (vec![&ciphertext_left, &ciphertext_right], self.ciphertext_visibility),
+        ] {
+            if visibility.is_public() {
+                for cell in cells {
+                    layouter.constrain_instance(cell.cell(), config.instance, instance_row)?;
+                    instance_row += 1;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use halo2_proofs::dev::MockProver;
+
+    use crate::assignment::{AssignmentTable, ColumnRef, ReplayCircuit};
+    use crate::diagnostics::explain_circuit_failures;
+
+    #[test]
+    fn test_mimc5_feistel_pallas_cipher_circuit() {
+        let k = 8;
+
+        let circuit = MiMC5FeistelCipherPallasCircuit::new(Fp::from(1), Fp::from(2), Fp::from(3));
+        let prover = MockProver::run(k, &circuit, vec![circuit.public_inputs()]).unwrap();
+        prover.assert_satisfied();
+    }
+
+    #[test]
+    fn test_mimc5_feistel_vesta_cipher_circuit_with_public_key() {
+        let k = 8;
+
+        let circuit = MiMC5FeistelCipherVestaCircuit::with_visibility(
+            Fq::from(1),
+            Fq::from(2),
+            Fq::from(3),
+            Visibility::Private,
+            Visibility::Public,
+            Visibility::Public,
+        );
+        let public_inputs = circuit.public_inputs();
+        assert_eq!(public_inputs.len(), 3);
+        let prover = MockProver::run(k, &circuit, vec![public_inputs.clone()]).unwrap();
+        prover.assert_satisfied();
+
+        let mut wrong_key = public_inputs;
+        wrong_key[0] += Fq::one();
+        let prover = MockProver::run(k, &circuit, vec![wrong_key]).unwrap();
+        assert!(prover.verify().is_err());
+    }
+
+    // A witness that encrypts under another key in one round, with the later
+    // states computed from it and that key cell not copied from the key. It
+    // used to satisfy every gate, and now fails exactly the comparisons of
+    // the changed key with its neighbours.
+    #[test]
+    fn test_mimc5_feistel_cipher_rejects_changed_round_key() {
+        let k = 8;
+        let (left, right, key, other_key, round) = (Fp::from(1), Fp::from(2), Fp::from(5), Fp::from(6), 100);
+
+        let circuit = MiMC5FeistelCipherPallasCircuit::with_visibility(
+            left,
+            right,
+            key,
+            Visibility::Private,
+            Visibility::Private,
+            Visibility::Private,
+        );
+        let mut table = AssignmentTable::synthesize(k, &circuit, vec![vec![]]).unwrap();
+        // The state columns are A1 and A2 and the key column A3, and the
+        // region starts at the row holding the left message
+        let start = *table.advice[1].iter().find(|(_, value)| **value == Some(left)).unwrap().0;
+        let key_cell = (ColumnRef::Advice(3), start + round);
+        table.copies.retain(|(a, b)| *a != key_cell && *b != key_cell);
+        table.advice[3].insert(start + round, Some(other_key));
+        let rounds = Fp::MIMC5_FEISTEL_ROUND_CONSTANTS.len();
+        let mut state_left = table.advice[1][&(start + round - 1)].unwrap();
+        let mut state_right = table.advice[2][&(start + round - 1)].unwrap();
+        for i in round..=rounds {
+            let round_key = if i == round { other_key } else { key };
+            if i < rounds {
+                let x = state_left + round_key + Fp::MIMC5_FEISTEL_ROUND_CONSTANTS[i - 1];
+                (state_left, state_right) = (state_right + x.square().square() * x, state_left);
+            } else {
+                let x = state_left + round_key;
+                state_right += x.square().square() * x;
+            }
+            table.advice[1].insert(start + i, Some(state_left));
+            table.advice[2].insert(start + i, Some(state_right));
+        }
+
+        let replay = ReplayCircuit::<Fp, MiMC5FeistelCipherPallasCircuit>::new(table.clone()).unwrap();
+        let failures = MockProver::run(k, &replay, table.instance).unwrap().verify().unwrap_err();
+        let reports: Vec<_> = explain_circuit_failures::<Fp, MiMC5FeistelCipherPallasCircuit>(&failures)
+            .into_iter()
+            .map(|report| (report.round, report.message))
+            .collect();
+        assert_eq!(
+            reports,
+            vec![
+                (Some(100), "key 0x6 differs from the key 0x5 of the previous round".to_string()),
+                (Some(101), "key 0x5 differs from the key 0x6 of the previous round".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,209 @@
+use halo2_proofs::{
+    plonk::{
+        Circuit, Column, Advice, Instance, ConstraintSystem, Error,
+    },
+    circuit::{
+        Layouter, SimpleFloorPlanner, Value,
+    },
+};
+use pasta_curves::{Fp, Fq};
+
+use crate::mimc_feistel::{
+    mimc_feistel_hash::{
+        MiMC5FeistelHashConfig, MiMC5FeistelHashChip
+    },
+    primitives::mimc5_feistel_hash,
+};
+use super::Visibility;
+use crate::field::MiMCField;
+use crate::layout::{ColumnLabels, LabeledConfig};
+
+
+#[derive(Debug, Clone)]
+pub struct MiMC5FeistelHashCircuitConfig {
+    input: Column<Advice>,
+    instance: Column<Instance>,
+    mimc_config: MiMC5FeistelHashConfig,
+}
+
+impl LabeledConfig for MiMC5FeistelHashCircuitConfig {
+    fn labels(&self) -> ColumnLabels {
+        ColumnLabels::default()
+            .column(self.input, "input")
+            .column(self.instance, "instance")
+            .extend(self.mimc_config.labels())
+    }
+}
+
+// Proves knowledge of a two-element message and its MiMC5 Feistel hash. By
+// default the message is private and both halves of the hash are public.
+pub struct MiMC5FeistelHashCircuit<F: MiMCField> {
+    pub message_left: F,
+    pub message_right: F,
+    pub message_visibility: Visibility,
+    pub hash_visibility: Visibility,
+}
+
+pub type MiMC5FeistelHashPallasCircuit = MiMC5FeistelHashCircuit<Fp>;
+pub type MiMC5FeistelHashVestaCircuit = MiMC5FeistelHashCircuit<Fq>;
+
+impl<F: MiMCField> MiMC5FeistelHashCircuit<F> {
+    pub fn new(message_left: F, message_right: F) -> Self {
+        Self::with_visibility(message_left, message_right, Visibility::Private, Visibility::Public)
+    }
+
+    pub fn with_visibility(
+        message_left: F,
+        message_right: F,
+        message_visibility: Visibility,
+        hash_visibility: Visibility,
+    ) -> Self {
+        Self {
+            message_left,
+            message_right,
+            message_visibility,
+            hash_visibility,
+        }
+    }
+
+    pub fn message_hash(&self) -> (F, F) {
+        let mut state_left = self.message_left;
+        let mut state_right = self.message_right;
+        mimc5_feistel_hash(&mut state_left, &mut state_right, F::MIMC5_FEISTEL_ROUND_CONSTANTS);
+        (state_left, state_right)
+    }
+
+    // The instance column expected by this circuit: the message halves (if
+    // public) followed by the hash halves (if public)
+    pub fn public_inputs(&self) -> Vec<F> {
+        let mut public_inputs = vec![];
+        if self.message_visibility.is_public() {
+            public_inputs.extend([self.message_left, self.message_right]);
+        }
+        if self.hash_visibility.is_public() {
+            let (hash_left, hash_right) = self.message_hash();
+            public_inputs.extend([hash_left, hash_right]);
+        }
+        public_inputs
+    }
+}
+
+impl<F: MiMCField> Clone for MiMC5FeistelHashCircuit<F> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<F: MiMCField> Copy for MiMC5FeistelHashCircuit<F> {}
+
+impl<F: MiMCField> Default for MiMC5FeistelHashCircuit<F> {
+    fn default() -> Self {
+        Self::new(F::zero(), F::zero())
+    }
+}
+
+impl<F: MiMCField> Circuit<F> for MiMC5FeistelHashCircuit<F> {
+    type Config = MiMC5FeistelHashCircuitConfig;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self::with_visibility(F::zero(), F::zero(), self.message_visibility, self.hash_visibility)
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        let circuit_input = meta.advice_column();
+        meta.enable_equality(circuit_input);
+        let instance = meta.instance_column();
+        meta.enable_equality(instance);
+        let state_left = meta.advice_column();
+        let state_right = meta.advice_column();
+        let round_constants = meta.fixed_column();
+        Self::Config {
+            input: circuit_input,
+            instance,
+            mimc_config: MiMC5FeistelHashChip::configure(meta, state_left, state_right, round_constants)
+        }
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        let chip = MiMC5FeistelHashChip::construct(config.mimc_config);
+
+        let message_left = layouter.assign_region(
+            || "load left part of message",
+            |mut region| {
+                region.assign_advice(
+                    || "load input message",
+                    config.input,
+                    0,
+                    || Value::known(self.message_left)
+                )
+            }
+        )?;
+
+        let message_right = layouter.assign_region(
+            || "load right part of message",
+            |mut region| {
+                region.assign_advice(
+                    || "load input message",
+                    config.input,
+                    0,
+                    || Value::known(self.message_right)
+                )
+            }
+        )?;
+
+        let (hash_left, hash_right) = chip.hash_message(
+            layouter.namespace(|| "entire table"),
+            &message_left,
+            &message_right,
+        )?;
+
+        let mut instance_row = 0;
+        for (cells, visibility) in [
+            ([&message_left, &message_right], self.message_visibility),
+            ([&hash_left, &hash_right], self.hash_visibility),
+        ] {
+            if visibility.is_public() {
+                for cell in cells {
+                    layouter.constrain_instance(cell.cell(), config.instance, instance_row)?;
+                    instance_row += 1;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use halo2_proofs::dev::MockProver;
+
+    #[test]
+    fn test_mimc5_feistel_pallas_hash_circuit() {
+        let k = 8;
+
+        let circuit = MiMC5FeistelHashPallasCircuit::new(Fp::from(1), Fp::from(2));
+        let prover = MockProver::run(k, &circuit, vec![circuit.public_inputs()]).unwrap();
+        prover.assert_satisfied();
+
+        let (hash_left, hash_right) = circuit.message_hash();
+        let swapped_hash = vec![hash_right, hash_left];
+        let prover = MockProver::run(k, &circuit, vec![swapped_hash]).unwrap();
+        assert!(prover.verify().is_err());
+    }
+
+    #[test]
+    fn test_mimc5_feistel_vesta_hash_circuit() {
+        let k = 8;
+
+        let circuit = MiMC5FeistelHashVestaCircuit::new(Fq::from(1), Fq::from(2));
+        let prover = MockProver::run(k, &circuit, vec![circuit.public_inputs()]).unwrap();
+        prover.assert_satisfied();
+    }
+}
@@ -0,0 +1,189 @@
+use halo2_proofs::{
+    plonk::{
+        Circuit, Column, Advice, Instance, ConstraintSystem, Error,
+    },
+    circuit::{
+        Layouter, SimpleFloorPlanner, Value,
+    },
+};
+use pasta_curves::{Fp, Fq};
+
+use crate::mimc::{
+    mimc_hash::{MiMC5HashConfig, MiMC5HashChip},
+    primitives::mimc5_hash,
+};
+use super::Visibility;
+use crate::field::MiMCField;
+use crate::layout::{ColumnLabels, LabeledConfig};
+
+
+#[derive(Debug, Clone)]
+pub struct MiMC5HashCircuitConfig {
+    input: Column<Advice>,
+    instance: Column<Instance>,
+    mimc_config: MiMC5HashConfig,
+}
+
+impl LabeledConfig for MiMC5HashCircuitConfig {
+    fn labels(&self) -> ColumnLabels {
+        ColumnLabels::default()
+            .column(self.input, "input")
+            .column(self.instance, "instance")
+            .extend(self.mimc_config.labels())
+    }
+}
+
+// Proves knowledge of a message and its MiMC5 hash. By default the message is
+// private and the hash is the only public input, i.e. "I know a preimage of h".
+pub struct MiMC5HashCircuit<F: MiMCField> {
+    pub message: F,
+    pub message_visibility: Visibility,
+    pub hash_visibility: Visibility,
+}
+
+pub type MiMC5HashPallasCircuit = MiMC5HashCircuit<Fp>;
+pub type MiMC5HashVestaCircuit = MiMC5HashCircuit<Fq>;
+
+impl<F: MiMCField> MiMC5HashCircuit<F> {
+    pub fn new(message: F) -> Self {
+        Self::with_visibility(message, Visibility::Private, Visibility::Public)
+    }
+
+    pub fn with_visibility(
+        message: F,
+        message_visibility: Visibility,
+        hash_visibility: Visibility,
+    ) -> Self {
+        Self {
+            message,
+            message_visibility,
+            hash_visibility,
+        }
+    }
+
+    pub fn message_hash(&self) -> F {
+        let mut state = self.message;
+        mimc5_hash(&mut state, F::MIMC5_ROUND_CONSTANTS);
+        state
+    }
+
+    // The instance column expected by this circuit: the message (if public)
+    // followed by the hash (if public)
+    pub fn public_inputs(&self) -> Vec<F> {
+        let mut public_inputs = vec![];
+        if self.message_visibility.is_public() {
+            public_inputs.push(self.message);
+        }
+        if self.hash_visibility.is_public() {
+            public_inputs.push(self.message_hash());
+        }
+        public_inputs
+    }
+}
+
+impl<F: MiMCField> Clone for MiMC5HashCircuit<F> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<F: MiMCField> Copy for MiMC5HashCircuit<F> {}
+
+impl<F: MiMCField> Default for MiMC5HashCircuit<F> {
+    fn default() -> Self {
+        Self::new(F::zero())
+    }
+}
+
+impl<F: MiMCField> Circuit<F> for MiMC5HashCircuit<F> {
+    type Config = MiMC5HashCircuitConfig;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self::with_visibility(F::zero(), self.message_visibility, self.hash_visibility)
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        let circuit_input = meta.advice_column();
+        meta.enable_equality(circuit_input);
+        let instance = meta.instance_column();
+        meta.enable_equality(instance);
+        let state = meta.advice_column();
+        let round_constants = meta.fixed_column();
+        Self::Config {
+            input: circuit_input,
+            instance,
+            mimc_config: MiMC5HashChip::configure(meta, state, round_constants)
+        }
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        let chip = MiMC5HashChip::construct(config.mimc_config);
+
+        let message = layouter.assign_region(
+            || "load message",
+            |mut region| {
+                region.assign_advice(
+                    || "load input message",
+                    config.input,
+                    0,
+                    || Value::known(self.message)
+                )
+            }
+        )?;
+
+        let msg_hash = chip.hash_message(
+            layouter.namespace(|| "entire table"),
+            &message,
+        )?;
+
+        let mut instance_row = 0;
+        if self.message_visibility.is_public() {
+            layouter.constrain_instance(message.cell(), config.instance, instance_row)?;
+            instance_row += 1;
+        }
+        if self.hash_visibility.is_public() {
+            layouter.constrain_instance(msg_hash.cell(), config.instance, instance_row)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use halo2_proofs::dev::MockProver;
+
+    #[test]
+    fn test_mimc5_pallas_hash_circuit() {
+        let k = 7;
+
+        let circuit = MiMC5HashPallasCircuit::new(Fp::from(1));
+        let prover = MockProver::run(k, &circuit, vec![circuit.public_inputs()]).unwrap();
+        prover.assert_satisfied();
+
+        let wrong_hash = vec![circuit.message_hash() + Fp::one()];
+        let prover = MockProver::run(k, &circuit, vec![wrong_hash]).unwrap();
+        assert!(prover.verify().is_err());
+    }
+
+    #[test]
+    fn test_mimc5_vesta_hash_circuit_with_public_message() {
+        let k = 7;
+
+        let circuit = MiMC5HashVestaCircuit::with_visibility(Fq::from(1), Visibility::Public, Visibility::Public);
+        let public_inputs = circuit.public_inputs();
+        assert_eq!(public_inputs.len(), 2);
+        let prover = MockProver::run(k, &circuit, vec![public_inputs]).unwrap();
+        prover.assert_satisfied();
+
+        let wrong_message = vec![Fq::from(2), circuit.message_hash()];
+        let prover = MockProver::run(k, &circuit, vec![wrong_message]).unwrap();
+        assert!(prover.verify().is_err());
+    }
+}
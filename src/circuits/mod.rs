@@ -0,0 +1,99 @@
+pub mod mimc_hash;
+pub mod mimc_cipher;
+pub mod mimc_feistel_hash;
+pub mod mimc_feistel_cipher;
+
+// Whether a circuit input or output is exposed in the instance column.
+// Public values are placed in the instance column in the order in which the
+// circuit declares them (inputs first, then outputs), skipping private ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Visibility {
+    #[default]
+    Private,
+    Public,
+}
+
+impl Visibility {
+    pub fn is_public(&self) -> bool {
+        *self == Visibility::Public
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, path::PathBuf};
+
+    use halo2_proofs::{arithmetic::CurveAffine, plonk::keygen_vk, poly::commitment::Params};
+    use pasta_curves::{pallas, vesta};
+
+    use super::{
+        mimc_cipher::{MiMC5CipherPallasCircuit, MiMC5CipherVestaCircuit},
+        mimc_feistel_cipher::{MiMC5FeistelCipherPallasCircuit, MiMC5FeistelCipherVestaCircuit},
+        mimc_feistel_hash::{MiMC5FeistelHashPallasCircuit, MiMC5FeistelHashVestaCircuit},
+        mimc_hash::{MiMC5HashPallasCircuit, MiMC5HashVestaCircuit},
+    };
+    use crate::prover::MiMCStatement;
+
+    // Compares the pinned verifying key of the default shape of a circuit,
+    // as generated by `MiMCProver::new`, with the file checked in under
+    // src/circuits/snapshots. Any change to the gates, columns, rotations,
+    // fixed values or copy constraints changes the key and invalidates keys
+    // deployed by verifiers. Run with UPDATE_SNAPSHOTS=1 to rewrite the files
+    // after an intended change.
+    fn check_vk_snapshot<C: CurveAffine, S: MiMCStatement<C::Scalar>>(name: &str) {
+        let params: Params<C> = Params::new(S::K);
+        let vk = keygen_vk(&params, &S::default()).unwrap();
+        let pinned = format!("{:#?}\n", vk.pinned());
+
+        let path: PathBuf = [env!("CARGO_MANIFEST_DIR"), "src", "circuits", "snapshots", name].iter().collect();
+        if env::var_os("UPDATE_SNAPSHOTS").is_some() {
+            fs::write(&path, &pinned).unwrap();
+            return;
+        }
+        let expected = fs::read_to_string(&path)
+            .unwrap_or_else(|e| panic!("cannot read {}: {}; run with UPDATE_SNAPSHOTS=1 to create it", path.display(), e));
+        if let Some((line, (expected, found))) =
+            expected.lines().zip(pinned.lines()).enumerate().find(|(_, (expected, found))| expected != found)
+        {
+            panic!(
+                "verifying key of {} changed at line {}:\n  expected: {}\n  found:    {}\n\
+                 rerun with UPDATE_SNAPSHOTS=1 if the change is intended; it breaks deployed keys",
+                name,
+                line + 1,
+                expected.trim(),
+                found.trim()
+            );
+        }
+        assert_eq!(
+            expected.lines().count(),
+            pinned.lines().count(),
+            "verifying key of {} changed length; rerun with UPDATE_SNAPSHOTS=1 if the change is intended",
+            name
+        );
+    }
+
+    #[test]
+    fn test_mimc5_hash_vk_snapshots() {
+        check_vk_snapshot::<vesta::Affine, MiMC5HashPallasCircuit>("mimc5_hash_pallas.vk");
+        check_vk_snapshot::<pallas::Affine, MiMC5HashVestaCircuit>("mimc5_hash_vesta.vk");
+    }
+
+    #[test]
+    fn test_mimc5_cipher_vk_snapshots() {
+        check_vk_snapshot::<vesta::Affine, MiMC5CipherPallasCircuit>("mimc5_cipher_pallas.vk");
+        check_vk_snapshot::<pallas::Affine, MiMC5CipherVestaCircuit>("mimc5_cipher_vesta.vk");
+    }
+
+    #[test]
+    fn test_mimc5_feistel_hash_vk_snapshots() {
+        check_vk_snapshot::<vesta::Affine, MiMC5FeistelHashPallasCircuit>("mimc5_feistel_hash_pallas.vk");
+        check_vk_snapshot::<pallas::Affine, MiMC5FeistelHashVestaCircuit>("mimc5_feistel_hash_vesta.vk");
+    }
+
+    #[test]
+    fn test_mimc5_feistel_cipher_vk_snapshots() {
+        check_vk_snapshot::<vesta::Affine, MiMC5FeistelCipherPallasCircuit>("mimc5_feistel_cipher_pallas.vk");
+        check_vk_snapshot::<pallas::Affine, MiMC5FeistelCipherVestaCircuit>("mimc5_feistel_cipher_vesta.vk");
+    }
+}
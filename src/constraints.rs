@@ -0,0 +1,377 @@
+// Constraint system export, so the constraints of a circuit can be reviewed
+// and diffed between releases without reading the chip code.
+//
+// `constraint_system_json` configures a circuit and synthesizes it once with
+// its floor planner, and returns a JSON object with
+// - "columns": the number of advice, fixed and instance columns
+// - "degree": the degree of the constraint system
+// - "gates": each gate's constraints as polynomial expressions, with the
+//   cells they query
+// - "vacuous": the constraints that hold for any cell values, such as a cell
+//   minus itself, by gate name and constraint index
+// - "queries": every queried column and rotation
+// - "selectors": the rows each selector is enabled on
+// - "equality": the equality-enabled columns
+// - "constants": the fixed columns that hold constants
+// - "fixed": the values assigned to each fixed column, by row
+//
+// Columns are named as in halo2's `CircuitGates`: A, F and I followed by the
+// column index for advice, fixed and instance columns and S followed by the
+// selector index, with "@" and the rotation for a query. Selectors are listed
+// before they are compressed into fixed columns.
+//
+// The column and selector indices and the queries, equality and constant
+// columns are private to halo2, so they are read from the `Debug` output of
+// the pinned constraint system (see `debug_format.rs`), and an export fails
+// with `ConstraintsError::FormatChanged` if that output has changed shape.
+use std::collections::BTreeMap;
+use std::fmt;
+use std::marker::PhantomData;
+
+use halo2_proofs::{
+    arithmetic::FieldExt,
+    circuit::Value,
+    dev::CircuitGates,
+    plonk::{
+        Advice, Any, Assigned, Assignment, Circuit, Column, ConstraintSystem, Error, Fixed, FloorPlanner, Instance,
+        Selector,
+    },
+};
+use pasta_curves::group::ff::PrimeField;
+use rand_core::SeedableRng;
+use serde_json::{json, Map, Value as Json};
+
+use crate::circuits::{
+    mimc_cipher::MiMC5CipherPallasCircuit, mimc_feistel_cipher::MiMC5FeistelCipherPallasCircuit,
+    mimc_feistel_hash::MiMC5FeistelHashPallasCircuit, mimc_hash::MiMC5HashPallasCircuit,
+};
+use crate::cost::MiMC5FixedKeyCipherPallasCircuit;
+use crate::assignment::{constant_columns, ColumnRef};
+use crate::debug_format::{debug_index, parse_gates, FormatChanged, GateExpression, GateText, PinnedSystem, GATES};
+use crate::field::MiMCField;
+use crate::field_io::{format_field, parse_field, FieldFormat};
+use crate::rng::MiMCRng;
+
+#[derive(Debug)]
+pub enum ConstraintsError {
+    Synthesis(Error),
+    FormatChanged(FormatChanged),
+}
+
+impl fmt::Display for ConstraintsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConstraintsError::Synthesis(error) => write!(f, "synthesis failed: {:?}", error),
+            ConstraintsError::FormatChanged(error) => error.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for ConstraintsError {}
+
+impl From<Error> for ConstraintsError {
+    fn from(error: Error) -> Self {
+        ConstraintsError::Synthesis(error)
+    }
+}
+
+impl From<FormatChanged> for ConstraintsError {
+    fn from(error: FormatChanged) -> Self {
+        ConstraintsError::FormatChanged(error)
+    }
+}
+
+pub fn constraint_system_json<F, C>(circuit: &C) -> Result<Json, ConstraintsError>
+where
+    F: MiMCField + PrimeField<Repr = [u8; 32]>,
+    C: Circuit<F>,
+{
+    let mut cs = ConstraintSystem::default();
+    let config = C::configure(&mut cs);
+    let constants = constant_columns(&cs)?;
+    let mut recorder = FixedRecorder::default();
+    C::FloorPlanner::synthesize(&mut recorder, circuit, config, constants)?;
+
+    let pinned = PinnedSystem::of(&cs)?;
+    let queries = |queries: &[(ColumnRef, i32)]| {
+        queries
+            .iter()
+            .map(|(column, rotation)| json!({ "column": column.to_string(), "rotation": rotation }))
+            .collect::<Vec<_>>()
+    };
+    let columns = |columns: &[ColumnRef]| columns.iter().map(ColumnRef::to_string).collect::<Vec<_>>();
+    let gates = parse_gates(&CircuitGates::collect::<F, C>().to_string())?;
+
+    let selectors: Map<String, Json> = (0..pinned.simple_selectors.len())
+        .map(|i| {
+            let rows = recorder.selectors.get(&i).cloned().unwrap_or_default();
+            (format!("S{}", i), json!(rows))
+        })
+        .collect();
+    let fixed: Map<String, Json> = (0..pinned.fixed)
+        .map(|i| {
+            let assigned = recorder.fixed.get(&i);
+            let rows = assigned.and_then(|values| values.keys().last()).map_or(0, |last| last + 1);
+            let values: Vec<Json> = (0..rows)
+                .map(|row| match assigned.and_then(|values| values.get(&row)) {
+                    Some(value) => json!(format_field(value, FieldFormat::Hex)),
+                    None => Json::Null,
+                })
+                .collect();
+            (format!("F{}", i), json!(values))
+        })
+        .collect();
+
+    Ok(json!({
+        "columns": {
+            "advice": pinned.advice,
+            "fixed": pinned.fixed,
+            "instance": pinned.instance,
+        },
+        "degree": cs.degree(),
+        "gates": gates_json(&gates)?,
+        "vacuous": vacuous_constraints::<F>(&gates)?,
+        "queries": {
+            "advice": queries(&pinned.advice_queries),
+            "fixed": queries(&pinned.fixed_queries),
+            "instance": queries(&pinned.instance_queries),
+        },
+        "selectors": selectors,
+        "equality": columns(&pinned.equality),
+        "constants": columns(&pinned.constants),
+        "fixed": fixed,
+    }))
+}
+
+// Constraint systems of every MiMC chip, configured as in `chip_costs`
+pub fn chip_constraint_systems() -> Result<Vec<(&'static str, Json)>, ConstraintsError> {
+    Ok(vec![
+        ("MiMC5 hash", constraint_system_json(&MiMC5HashPallasCircuit::default())?),
+        ("MiMC5 cipher", constraint_system_json(&MiMC5CipherPallasCircuit::default())?),
+        (
+            "MiMC5 fixed key cipher",
+            constraint_system_json(&MiMC5FixedKeyCipherPallasCircuit::default())?,
+        ),
+        ("MiMC5 Feistel hash", constraint_system_json(&MiMC5FeistelHashPallasCircuit::default())?),
+        ("MiMC5 Feistel cipher", constraint_system_json(&MiMC5FeistelCipherPallasCircuit::default())?),
+    ])
+}
+
+fn gates_json(gates: &[GateText]) -> Result<Json, FormatChanged> {
+    gates
+        .iter()
+        .map(|gate| {
+            let constraints = gate
+                .constraints
+                .iter()
+                .map(|constraint| {
+                    Ok(json!({
+                        "name": constraint.name,
+                        "expression": constraint.expression,
+                        "queries": GateExpression::parse(&constraint.expression)?.queries(),
+                    }))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(json!({ "name": gate.name, "constraints": constraints }))
+        })
+        .collect()
+}
+
+// Constraints that hold whatever the cells hold, such as the difference of a
+// query and itself, which constrain nothing. A constraint is reported if it
+// evaluates to zero at pseudorandom cell values, which a nonzero polynomial
+// of degree d does with probability at most d / p. The values come from a
+// fixed seed so that an export is the same on every run.
+fn vacuous_constraints<F: MiMCField + PrimeField<Repr = [u8; 32]>>(gates: &[GateText]) -> Result<Json, FormatChanged> {
+    let mut rng = MiMCRng::<F>::seed_from_u64(0);
+    let mut values = BTreeMap::new();
+    let mut constants = BTreeMap::new();
+    let mut vacuous = vec![];
+    for gate in gates {
+        for (index, constraint) in gate.constraints.iter().enumerate() {
+            let expression = GateExpression::parse(&constraint.expression)?;
+            for query in expression.queries() {
+                values.entry(query).or_insert_with(|| F::random(&mut rng));
+            }
+            for constant in expression.constants() {
+                let value = parse_field::<F>(&constant).map_err(|_| GATES)?;
+                constants.insert(constant, value);
+            }
+            if expression.evaluate(&|constant| constants[constant], &|query| values[query]).is_zero_vartime() {
+                vacuous.push(json!({
+                    "gate": gate.name,
+                    "constraint": index,
+                    "expression": constraint.expression,
+                }));
+            }
+        }
+    }
+    Ok(json!(vacuous))
+}
+
+// Records the values of fixed cells and the rows selectors are enabled on,
+// by index
+#[derive(Debug)]
+struct FixedRecorder<F> {
+    fixed: BTreeMap<usize, BTreeMap<usize, F>>,
+    selectors: BTreeMap<usize, Vec<usize>>,
+    _marker: PhantomData<F>,
+}
+
+impl<F> Default for FixedRecorder<F> {
+    fn default() -> Self {
+        Self {
+            fixed: BTreeMap::new(),
+            selectors: BTreeMap::new(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<F: FieldExt> Assignment<F> for FixedRecorder<F> {
+    fn enter_region<NR, N>(&mut self, _: N)
+    where
+        NR: Into<String>,
+        N: FnOnce() -> NR,
+    {
+    }
+
+    fn exit_region(&mut self) {}
+
+    fn enable_selector<A, AR>(&mut self, _: A, selector: &Selector, row: usize) -> Result<(), Error>
+    where
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        self.selectors.entry(debug_index(selector)).or_default().push(row);
+        Ok(())
+    }
+
+    fn query_instance(&self, _: Column<Instance>, _: usize) -> Result<Value<F>, Error> {
+        Ok(Value::unknown())
+    }
+
+    fn assign_advice<V, VR, A, AR>(&mut self, _: A, _: Column<Advice>, _: usize, _: V) -> Result<(), Error>
+    where
+        V: FnOnce() -> Value<VR>,
+        VR: Into<Assigned<F>>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        Ok(())
+    }
+
+    fn assign_fixed<V, VR, A, AR>(&mut self, _: A, column: Column<Fixed>, row: usize, to: V) -> Result<(), Error>
+    where
+        V: FnOnce() -> Value<VR>,
+        VR: Into<Assigned<F>>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        let values = self.fixed.entry(debug_index(&column)).or_default();
+        to().map(|value| values.insert(row, value.into().evaluate()));
+        Ok(())
+    }
+
+    fn copy(&mut self, _: Column<Any>, _: usize, _: Column<Any>, _: usize) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn fill_from_row(&mut self, _: Column<Fixed>, _: usize, _: Value<Assigned<F>>) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn push_namespace<NR, N>(&mut self, _: N)
+    where
+        NR: Into<String>,
+        N: FnOnce() -> NR,
+    {
+    }
+
+    fn pop_namespace(&mut self, _: Option<String>) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pasta_curves::Fp;
+
+    #[test]
+    fn test_chip_constraint_systems() {
+        let systems = chip_constraint_systems().unwrap();
+        assert_eq!(systems.len(), 5);
+        for (name, system) in &systems {
+            assert_eq!(system["degree"], 6, "{}", name);
+            // Every round constant is exported
+            assert!(system["fixed"]["F0"].as_array().unwrap().len() >= 110, "{}", name);
+        }
+
+        let (_, hash) = &systems[0];
+        assert_eq!(hash["columns"], json!({ "advice": 2, "fixed": 1, "instance": 1 }));
+        let gate = &hash["gates"][0];
+        assert_eq!(gate["name"], "MiMC5 hash rounds");
+        assert_eq!(gate["constraints"][0]["queries"], json!(["A1@-1", "A1@0", "F0@-1", "S0"]));
+        assert_eq!(hash["selectors"]["S0"].as_array().unwrap().len(), 110);
+        assert!(hash["equality"].as_array().unwrap().contains(&json!("I0")));
+        assert_eq!(hash["constants"], json!([]));
+
+        // The fixed key cipher circuit loads its messages as constants
+        let (_, fixed_key_cipher) = &systems[2];
+        assert_eq!(fixed_key_cipher["constants"], json!(["F1"]));
+        assert_eq!(fixed_key_cipher["fixed"]["F1"], json!([format_field(&Fp::zero(), FieldFormat::Hex)]));
+    }
+
+    #[test]
+    fn test_cipher_constraint_system_key_queries() {
+        // The key queries of the cipher gates are visible without reading
+        // the chip
+        let (_, cipher) = &chip_constraint_systems().unwrap()[1];
+        let gates = cipher["gates"].as_array().unwrap();
+        assert_eq!(gates.len(), 2);
+        let advice_queries = cipher["queries"]["advice"].as_array().unwrap();
+        for gate in gates {
+            for query in gate["constraints"][0]["queries"].as_array().unwrap() {
+                let query = query.as_str().unwrap();
+                if let Some((column, rotation)) = query.split_once('@') {
+                    if column.starts_with('A') {
+                        assert!(advice_queries.contains(&json!({
+                            "column": column,
+                            "rotation": rotation.parse::<i32>().unwrap(),
+                        })));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_vacuous_constraints() {
+        // The key of each cipher round is compared with the key of the
+        // previous row, not with itself
+        for (name, system) in chip_constraint_systems().unwrap() {
+            assert_eq!(system["vacuous"], json!([]), "{}", name);
+        }
+
+        let gates = parse_gates(
+            "keys:\n- S0 * (A2@0 - A2@0)\n- S0 * (A2@-1 - A2@0)\n- double:\n  S1 * (A1@0 + A1@0 - (A1@0 * 0x2))\n",
+        )
+        .unwrap();
+        assert_eq!(
+            vacuous_constraints::<Fp>(&gates).unwrap(),
+            json!([
+                { "gate": "keys", "constraint": 0, "expression": "S0 * (A2@0 - A2@0)" },
+                { "gate": "keys", "constraint": 2, "expression": "S1 * (A1@0 + A1@0 - (A1@0 * 0x2))" },
+            ])
+        );
+
+        // A constant that is not a field element is a format change
+        let gates = parse_gates("double:\n- S1 * (A1@0 - (A1@0 * 0xzz))\n").unwrap();
+        assert_eq!(vacuous_constraints::<Fp>(&gates).unwrap_err(), GATES);
+    }
+
+    #[test]
+    fn test_constraint_system_json_is_deterministic() {
+        let circuit = MiMC5HashPallasCircuit::default();
+        assert_eq!(constraint_system_json(&circuit).unwrap(), constraint_system_json(&circuit).unwrap());
+    }
+}
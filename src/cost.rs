@@ -0,0 +1,462 @@
+// Circuit cost and capacity reporting.
+//
+// `LayoutCost::measure` synthesizes a circuit once with its floor planner,
+// recording the rows, columns and selectors used overall and by each region.
+// `chip_costs` applies it to a circuit with one invocation of each MiMC chip
+// and reads the cost of an invocation off the chip's region, which gives the
+// minimum `k` for a number of invocations, the invocations that fit in a
+// given `k` and an estimate of the proof size.
+//
+// Column counts are of the columns a circuit assigns, copies or reads from.
+use std::collections::HashSet;
+use std::marker::PhantomData;
+
+use halo2_proofs::{
+    arithmetic::Field,
+    circuit::{Layouter, SimpleFloorPlanner, Value},
+    dev,
+    plonk::{
+        Advice, Any, Assigned, Assignment, Circuit, Column, ConstraintSystem, Error, Fixed, FloorPlanner, Instance,
+        Selector,
+    },
+};
+use pasta_curves::{group::prime::PrimeGroup, vesta, Fp};
+
+use crate::assignment::constant_columns;
+use crate::circuits::{
+    mimc_cipher::MiMC5CipherPallasCircuit, mimc_feistel_cipher::MiMC5FeistelCipherPallasCircuit,
+    mimc_feistel_hash::MiMC5FeistelHashPallasCircuit, mimc_hash::MiMC5HashPallasCircuit,
+};
+use crate::field::MiMCField;
+use crate::layout::{ColumnLabels, LabeledConfig};
+use crate::mimc::mimc_fixed_key_cipher::{MiMC5FixedKeyCipherChip, MiMC5FixedKeyCipherConfig};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegionCost {
+    pub name: String,
+    pub advice_columns: usize,
+    pub fixed_columns: usize,
+    pub selectors: usize,
+    pub rows: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LayoutCost {
+    pub advice_columns: usize,
+    pub fixed_columns: usize,
+    pub instance_columns: usize,
+    // Distinct selectors enabled during synthesis
+    pub selectors: usize,
+    pub max_degree: usize,
+    // Rows from the first row to the last assigned one
+    pub rows: usize,
+    // Constant cells the floor planner assigned outside the regions
+    pub constants: usize,
+    pub regions: Vec<RegionCost>,
+    // Rows at the end of the table reserved for blinding
+    unusable_rows: usize,
+    minimum_rows: usize,
+}
+
+impl LayoutCost {
+    pub fn measure<F: Field, C: Circuit<F>>(circuit: &C) -> Result<Self, Error> {
+        let mut cs = ConstraintSystem::default();
+        let config = C::configure(&mut cs);
+        let constants = constant_columns(&cs)?;
+        let mut recorder = LayoutRecorder::default();
+        C::FloorPlanner::synthesize(&mut recorder, circuit, config, constants)?;
+
+        let count = |column_type: Any| recorder.columns.iter().filter(|column| *column.column_type() == column_type).count();
+        Ok(Self {
+            advice_columns: count(Any::Advice),
+            fixed_columns: count(Any::Fixed),
+            instance_columns: count(Any::Instance),
+            selectors: recorder.selectors.len(),
+            max_degree: cs.degree(),
+            rows: recorder.rows,
+            constants: recorder.constants,
+            regions: recorder.regions,
+            unusable_rows: cs.blinding_factors() + 1,
+            minimum_rows: cs.minimum_rows(),
+        })
+    }
+
+    // The smallest k whose table fits the measured rows
+    pub fn min_k(&self) -> u32 {
+        min_k(self.rows, self.unusable_rows, self.minimum_rows)
+    }
+}
+
+fn min_k(rows: usize, unusable_rows: usize, minimum_rows: usize) -> u32 {
+    let mut k = 1;
+    while (1usize << k) < (rows + unusable_rows).max(minimum_rows) {
+        k += 1;
+    }
+    k
+}
+
+// The proof size in bytes of a circuit over G's scalar field at a given k
+pub fn proof_size<G: PrimeGroup, C: Circuit<G::Scalar>>(k: u32, circuit: &C) -> usize {
+    dev::CircuitCost::<G, C>::measure(k as usize, circuit).proof_size(1).into()
+}
+
+// The cost of one invocation of a chip. Invocations are laid out one after
+// another in the chip's columns, so N of them take N times the rows.
+#[derive(Debug, Clone)]
+pub struct ChipCost {
+    pub name: &'static str,
+    pub advice_columns: usize,
+    pub fixed_columns: usize,
+    pub selectors: usize,
+    pub rows_per_invocation: usize,
+    pub max_degree: usize,
+    unusable_rows: usize,
+    minimum_rows: usize,
+    proof_size: fn(u32) -> usize,
+}
+
+impl ChipCost {
+    // Measures the largest region of a circuit that invokes the chip once
+    fn measure<G: PrimeGroup, C: Circuit<G::Scalar> + Default>(name: &'static str) -> Self {
+        let layout = LayoutCost::measure(&C::default()).expect("MiMC circuits synthesize without witnesses");
+        let region = layout
+            .regions
+            .iter()
+            .max_by_key(|region| region.rows)
+            .expect("MiMC circuits have a chip region");
+        Self {
+            name,
+            advice_columns: region.advice_columns,
+            fixed_columns: region.fixed_columns,
+            selectors: region.selectors,
+            rows_per_invocation: region.rows,
+            max_degree: layout.max_degree,
+            unusable_rows: layout.unusable_rows,
+            minimum_rows: layout.minimum_rows,
+            proof_size: |k| proof_size::<G, C>(k, &C::default()),
+        }
+    }
+
+    // The smallest k that fits `invocations` invocations of the chip
+    pub fn min_k(&self, invocations: usize) -> u32 {
+        min_k(invocations * self.rows_per_invocation, self.unusable_rows, self.minimum_rows)
+    }
+
+    // The number of invocations that fit in a table of 2^k rows
+    pub fn capacity(&self, k: u32) -> usize {
+        (1usize << k).saturating_sub(self.unusable_rows) / self.rows_per_invocation
+    }
+
+    // Estimated proof size in bytes of a circuit made of `invocations`
+    // invocations of the chip and a column for its inputs. Proofs depend on
+    // the number of rows only through k.
+    pub fn proof_size(&self, invocations: usize) -> usize {
+        (self.proof_size)(self.min_k(invocations))
+    }
+}
+
+// The cost of each MiMC chip
+#[derive(Debug, Clone)]
+pub struct ChipCosts {
+    pub mimc5_hash: ChipCost,
+    pub mimc5_cipher: ChipCost,
+    pub mimc5_fixed_key_cipher: ChipCost,
+    pub mimc5_feistel_hash: ChipCost,
+    pub mimc5_feistel_cipher: ChipCost,
+}
+
+impl ChipCosts {
+    pub fn iter(&self) -> impl Iterator<Item = &ChipCost> {
+        [
+            &self.mimc5_hash,
+            &self.mimc5_cipher,
+            &self.mimc5_fixed_key_cipher,
+            &self.mimc5_feistel_hash,
+            &self.mimc5_feistel_cipher,
+        ]
+        .into_iter()
+    }
+}
+
+// Costs of every MiMC chip. Pallas and Vesta chips share their layouts, so
+// the costs are measured with the Pallas chips; proof sizes are for Vesta
+// commitments, whose scalar field is the Pallas base field.
+pub fn chip_costs() -> ChipCosts {
+    ChipCosts {
+        mimc5_hash: ChipCost::measure::<vesta::Point, MiMC5HashPallasCircuit>("MiMC5 hash"),
+        mimc5_cipher: ChipCost::measure::<vesta::Point, MiMC5CipherPallasCircuit>("MiMC5 cipher"),
+        mimc5_fixed_key_cipher: ChipCost::measure::<vesta::Point, MiMC5FixedKeyCipherPallasCircuit>(
+            "MiMC5 fixed key cipher",
+        ),
+        mimc5_feistel_hash: ChipCost::measure::<vesta::Point, MiMC5FeistelHashPallasCircuit>("MiMC5 Feistel hash"),
+        mimc5_feistel_cipher: ChipCost::measure::<vesta::Point, MiMC5FeistelCipherPallasCircuit>("MiMC5 Feistel cipher"),
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct MiMC5FixedKeyCipherCircuitConfig {
+    input: Column<Advice>,
+    mimc_config: MiMC5FixedKeyCipherConfig,
+}
+
+impl LabeledConfig for MiMC5FixedKeyCipherCircuitConfig {
+    fn labels(&self) -> ColumnLabels {
+        ColumnLabels::default().column(self.input, "input").extend(self.mimc_config.labels())
+    }
+}
+
+// Encrypts the constant messages 0, 1, ... under the fixed key 1, one chip
+// invocation each. It has no public inputs and proves nothing about its
+// ciphertexts; it gives the fixed key cipher chip a circuit to be measured
+// and inspected with, like the other chips have in `circuits`.
+pub(crate) struct MiMC5FixedKeyCipherCircuit<F: MiMCField> {
+    invocations: usize,
+    _marker: PhantomData<F>,
+}
+
+pub(crate) type MiMC5FixedKeyCipherPallasCircuit = MiMC5FixedKeyCipherCircuit<Fp>;
+
+impl<F: MiMCField> MiMC5FixedKeyCipherCircuit<F> {
+    pub(crate) fn with_invocations(invocations: usize) -> Self {
+        Self {
+            invocations,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<F: MiMCField> Default for MiMC5FixedKeyCipherCircuit<F> {
+    fn default() -> Self {
+        Self::with_invocations(1)
+    }
+}
+
+impl<F: MiMCField> Circuit<F> for MiMC5FixedKeyCipherCircuit<F> {
+    type Config = MiMC5FixedKeyCipherCircuitConfig;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self::with_invocations(self.invocations)
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        let input = meta.advice_column();
+        meta.enable_equality(input);
+        let state = meta.advice_column();
+        let round_keys = meta.fixed_column();
+        let constants = meta.fixed_column();
+        meta.enable_constant(constants);
+        Self::Config {
+            input,
+            mimc_config: MiMC5FixedKeyCipherChip::configure(meta, state, round_keys),
+        }
+    }
+
+    fn synthesize(&self, config: Self::Config, mut layouter: impl Layouter<F>) -> Result<(), Error> {
+        let chip = MiMC5FixedKeyCipherChip::construct(config.mimc_config, F::one());
+        for i in 0..self.invocations {
+            let message = layouter.assign_region(
+                || "load message",
+                |mut region| region.assign_advice_from_constant(|| "message", config.input, 0, F::from(i as u64)),
+            )?;
+            chip.encrypt_message(layouter.namespace(|| format!("encryption {}", i)), &message)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct RegionRecord {
+    name: String,
+    advice: HashSet<Column<Advice>>,
+    fixed: HashSet<Column<Fixed>>,
+    selectors: HashSet<Selector>,
+    rows: Option<(usize, usize)>,
+}
+
+impl RegionRecord {
+    fn touch(&mut self, row: usize) {
+        self.rows = Some(match self.rows {
+            None => (row, row),
+            Some((first, last)) => (first.min(row), last.max(row)),
+        });
+    }
+}
+
+#[derive(Debug, Default)]
+struct LayoutRecorder {
+    region: Option<RegionRecord>,
+    regions: Vec<RegionCost>,
+    columns: HashSet<Column<Any>>,
+    selectors: HashSet<Selector>,
+    rows: usize,
+    constants: usize,
+}
+
+impl LayoutRecorder {
+    fn touch(&mut self, row: usize) {
+        self.rows = self.rows.max(row + 1);
+        if let Some(region) = &mut self.region {
+            region.touch(row);
+        }
+    }
+}
+
+impl<F: Field> Assignment<F> for LayoutRecorder {
+    fn enter_region<NR, N>(&mut self, name: N)
+    where
+        NR: Into<String>,
+        N: FnOnce() -> NR,
+    {
+        self.region = Some(RegionRecord {
+            name: name().into(),
+            advice: HashSet::new(),
+            fixed: HashSet::new(),
+            selectors: HashSet::new(),
+            rows: None,
+        });
+    }
+
+    fn exit_region(&mut self) {
+        if let Some(region) = self.region.take() {
+            self.regions.push(RegionCost {
+                name: region.name,
+                advice_columns: region.advice.len(),
+                fixed_columns: region.fixed.len(),
+                selectors: region.selectors.len(),
+                rows: region.rows.map_or(0, |(first, last)| last - first + 1),
+            });
+        }
+    }
+
+    fn enable_selector<A, AR>(&mut self, _: A, selector: &Selector, row: usize) -> Result<(), Error>
+    where
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        self.touch(row);
+        self.selectors.insert(*selector);
+        if let Some(region) = &mut self.region {
+            region.selectors.insert(*selector);
+        }
+        Ok(())
+    }
+
+    // Reading an instance cell does not count as using its column here, as
+    // this takes `&self`; copies from instance cells do
+    fn query_instance(&self, _: Column<Instance>, _: usize) -> Result<Value<F>, Error> {
+        Ok(Value::unknown())
+    }
+
+    fn assign_advice<V, VR, A, AR>(&mut self, _: A, column: Column<Advice>, row: usize, _: V) -> Result<(), Error>
+    where
+        V: FnOnce() -> Value<VR>,
+        VR: Into<Assigned<F>>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        self.touch(row);
+        self.columns.insert(column.into());
+        if let Some(region) = &mut self.region {
+            region.advice.insert(column);
+        }
+        Ok(())
+    }
+
+    fn assign_fixed<V, VR, A, AR>(&mut self, _: A, column: Column<Fixed>, row: usize, _: V) -> Result<(), Error>
+    where
+        V: FnOnce() -> Value<VR>,
+        VR: Into<Assigned<F>>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        self.touch(row);
+        self.columns.insert(column.into());
+        match &mut self.region {
+            Some(region) => {
+                region.fixed.insert(column);
+            }
+            // The floor planner assigns the constants after the regions
+            None => self.constants += 1,
+        }
+        Ok(())
+    }
+
+    fn copy(&mut self, left: Column<Any>, _: usize, right: Column<Any>, _: usize) -> Result<(), Error> {
+        self.columns.insert(left);
+        self.columns.insert(right);
+        Ok(())
+    }
+
+    fn fill_from_row(&mut self, _: Column<Fixed>, _: usize, _: Value<Assigned<F>>) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn push_namespace<NR, N>(&mut self, _: N)
+    where
+        NR: Into<String>,
+        N: FnOnce() -> NR,
+    {
+    }
+
+    fn pop_namespace(&mut self, _: Option<String>) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use halo2_proofs::dev::MockProver;
+
+    use pasta_curves::Fq;
+
+    use crate::prover::MiMCStatement;
+
+    type MiMC5FixedKeyCipherVestaCircuit = MiMC5FixedKeyCipherCircuit<Fq>;
+
+    #[test]
+    fn test_chip_costs() {
+        let costs = chip_costs();
+        let rows: Vec<_> = costs.iter().map(|cost| (cost.name, cost.rows_per_invocation)).collect();
+        assert_eq!(
+            rows,
+            vec![
+                ("MiMC5 hash", 111),
+                ("MiMC5 cipher", 112),
+                ("MiMC5 fixed key cipher", 112),
+                ("MiMC5 Feistel hash", 221),
+                ("MiMC5 Feistel cipher", 221),
+            ]
+        );
+        for cost in costs.iter() {
+            // Selector times the fifth power
+            assert_eq!(cost.max_degree, 6, "{}", cost.name);
+            assert!(cost.capacity(cost.min_k(10)) >= 10);
+            assert!(cost.capacity(cost.min_k(10) - 1) < 10);
+            assert!(cost.proof_size(1) > 0);
+            assert!(cost.proof_size(1000) > cost.proof_size(1));
+        }
+        let cipher = &costs.mimc5_cipher;
+        assert_eq!((cipher.advice_columns, cipher.fixed_columns, cipher.selectors), (2, 1, 2));
+
+        // The statements' hard-coded k is the measured minimum
+        assert_eq!(LayoutCost::measure(&MiMC5HashPallasCircuit::default()).unwrap().min_k(), MiMC5HashPallasCircuit::K);
+        assert_eq!(
+            LayoutCost::measure(&MiMC5FeistelCipherPallasCircuit::default()).unwrap().min_k(),
+            MiMC5FeistelCipherPallasCircuit::K
+        );
+    }
+
+    #[test]
+    fn test_chip_capacity_matches_mock_prover() {
+        let cost = chip_costs().mimc5_fixed_key_cipher;
+        let circuit = MiMC5FixedKeyCipherVestaCircuit::with_invocations(5);
+        let layout = LayoutCost::measure(&circuit).unwrap();
+        assert_eq!(layout.rows, 5 * cost.rows_per_invocation);
+        // One constant per message, in a fixed column of its own
+        assert_eq!((layout.constants, layout.fixed_columns), (5, 2));
+
+        let k = cost.min_k(5);
+        MockProver::run(k, &circuit, vec![]).unwrap().assert_satisfied();
+        assert!(MockProver::run(k - 1, &circuit, vec![]).is_err());
+    }
+}
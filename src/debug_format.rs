@@ -0,0 +1,578 @@
+// Parsers for the printed form of halo2 types whose fields are private, used
+// by the constraint export, the assignment replay, the layouts and the
+// failure diagnostics.
+//
+// halo2_proofs 0.2 does not expose the column and selector indices, the
+// queries, equality and constant columns of a constraint system, the gate
+// polynomials or the failure metadata, so they are read back from `Debug`
+// and `Display` output:
+// - `PinnedSystem` parses the `Debug` output of the pinned constraint
+//   system, which halo2 hashes into verifying keys
+// - `parse_gates` and `GateExpression` parse the `Display` output of
+//   `CircuitGates`
+// - `metadata_*` parse the `Debug` output of the `dev::metadata` types that
+//   `VerifyFailure` carries
+//
+// None of these formats is promised to be stable, which is why Cargo.toml
+// pins the exact halo2_proofs version. `PinnedSystem`, `parse_gates` and
+// `GateExpression` return `FormatChanged` on text of an unexpected shape
+// instead of reading it as zero columns or no queries, and the tests pin the
+// parsed values of a small constraint system and of each metadata type, so
+// a halo2 upgrade that changes a format fails them.
+use std::fmt::{self, Debug};
+
+use halo2_proofs::{arithmetic::Field, plonk::{ConstraintSystem, Error}};
+
+use crate::assignment::ColumnRef;
+
+// The printed form of a halo2_proofs type no longer has the shape these
+// parsers expect. Holds the name of the type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FormatChanged(pub(crate) &'static str);
+
+impl fmt::Display for FormatChanged {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "the printed form of halo2_proofs' {} has changed, see src/debug_format.rs", self.0)
+    }
+}
+
+impl std::error::Error for FormatChanged {}
+
+// For the synthesis helpers that return halo2's error, which has no variant
+// for this; `Synthesis` is its catch-all
+impl From<FormatChanged> for Error {
+    fn from(_: FormatChanged) -> Self {
+        Error::Synthesis
+    }
+}
+
+const PINNED: FormatChanged = FormatChanged("PinnedConstraintSystem");
+pub(crate) const GATES: FormatChanged = FormatChanged("CircuitGates");
+
+pub(crate) const FORMAT_CHANGED: &str = "the printed form of a halo2_proofs type has changed, see src/debug_format.rs";
+
+// The columns, selectors and queries of a configured constraint system
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct PinnedSystem {
+    pub(crate) advice: usize,
+    pub(crate) fixed: usize,
+    pub(crate) instance: usize,
+    // Whether each selector is simple, by index. A complex selector is
+    // printed as `Selector(i, false)` in the gates
+    pub(crate) simple_selectors: Vec<bool>,
+    pub(crate) advice_queries: Vec<(ColumnRef, i32)>,
+    pub(crate) instance_queries: Vec<(ColumnRef, i32)>,
+    pub(crate) fixed_queries: Vec<(ColumnRef, i32)>,
+    pub(crate) equality: Vec<ColumnRef>,
+    pub(crate) constants: Vec<ColumnRef>,
+}
+
+impl PinnedSystem {
+    pub(crate) fn of<F: Field>(cs: &ConstraintSystem<F>) -> Result<Self, FormatChanged> {
+        Self::parse(&format!("{:?}", cs.pinned()))
+    }
+
+    fn parse(pinned: &str) -> Result<Self, FormatChanged> {
+        let count = |name: &str, next: &str| pinned_field(pinned, name, next)?.parse::<usize>().map_err(|_| PINNED);
+        let gates = pinned_field(pinned, "gates", "advice_queries")?;
+        let simple_selectors = (0..count("num_selectors", "gates")?)
+            .map(|i| !gates.contains(&format!("Selector({}, false)", i)))
+            .collect();
+        let queries = |name: &str, next: &str| {
+            parse_columns(pinned_field(pinned, name, next)?)?
+                .into_iter()
+                .map(|(column, rotation)| Ok((column, rotation.ok_or(PINNED)?)))
+                .collect::<Result<_, _>>()
+        };
+        let columns = |name: &str, next: &str| {
+            Ok(parse_columns(pinned_field(pinned, name, next)?)?
+                .into_iter()
+                .map(|(column, _)| column)
+                .collect())
+        };
+        Ok(Self {
+            advice: count("num_advice_columns", "num_instance_columns")?,
+            fixed: count("num_fixed_columns", "num_advice_columns")?,
+            instance: count("num_instance_columns", "num_selectors")?,
+            simple_selectors,
+            advice_queries: queries("advice_queries", "instance_queries")?,
+            instance_queries: queries("instance_queries", "fixed_queries")?,
+            fixed_queries: queries("fixed_queries", "permutation")?,
+            equality: columns("permutation", "lookups")?,
+            constants: columns("constants", "minimum_degree")?,
+        })
+    }
+}
+
+// The text of a field of the pinned constraint system, given the name of the
+// field after it
+fn pinned_field<'a>(pinned: &'a str, name: &str, next: &str) -> Result<&'a str, FormatChanged> {
+    let start = pinned.find(&format!(" {}: ", name)).ok_or(PINNED)? + name.len() + 3;
+    let end = pinned[start..].find(&format!(", {}: ", next)).ok_or(PINNED)?;
+    Ok(&pinned[start..start + end])
+}
+
+// The columns listed in the `Debug` output of columns or (column, rotation)
+// pairs, with the rotations if any
+fn parse_columns(text: &str) -> Result<Vec<(ColumnRef, Option<i32>)>, FormatChanged> {
+    text.split("Column { index: ")
+        .skip(1)
+        .map(|column| {
+            let (index, rest) = column.split_once(", column_type: ").ok_or(PINNED)?;
+            let index = index.parse().map_err(|_| PINNED)?;
+            let column = match rest.split(' ').next() {
+                Some("Advice") => ColumnRef::Advice(index),
+                Some("Fixed") => ColumnRef::Fixed(index),
+                Some("Instance") => ColumnRef::Instance(index),
+                _ => return Err(PINNED),
+            };
+            let rotation = rest
+                .split("Rotation(")
+                .nth(1)
+                .map(|rotation| rotation.split(')').next().and_then(|r| r.parse().ok()).ok_or(PINNED))
+                .transpose()?;
+            Ok((column, rotation))
+        })
+        .collect()
+}
+
+// The first number in a `Debug` string, which is the index of a `Column`
+// or a `Selector`
+pub(crate) fn debug_index(value: &impl Debug) -> usize {
+    let debug = format!("{:?}", value);
+    let digits: String = debug
+        .chars()
+        .skip_while(|c| !c.is_ascii_digit())
+        .take_while(|c| c.is_ascii_digit())
+        .collect();
+    digits.parse().expect(FORMAT_CHANGED)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct GateText {
+    pub(crate) name: String,
+    pub(crate) constraints: Vec<ConstraintText>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ConstraintText {
+    // Empty for unnamed constraints
+    pub(crate) name: String,
+    pub(crate) expression: String,
+}
+
+// Gates from the `Display` output of `CircuitGates`, which lists each gate
+// name followed by its constraints as "- expression" or "- name:" and the
+// indented expression, then the total operation counts
+pub(crate) fn parse_gates(text: &str) -> Result<Vec<GateText>, FormatChanged> {
+    let mut gates: Vec<GateText> = vec![];
+    let mut lines = text.lines().peekable();
+    while let Some(line) = lines.next() {
+        if let Some(constraint) = line.strip_prefix("- ") {
+            let (name, expression) = match lines.peek().and_then(|next| next.strip_prefix("  ")) {
+                Some(expression) => {
+                    lines.next();
+                    (constraint.trim_end_matches(':'), expression)
+                }
+                None => ("", constraint),
+            };
+            gates.last_mut().ok_or(GATES)?.constraints.push(ConstraintText {
+                name: name.to_string(),
+                expression: expression.to_string(),
+            });
+        } else if let Some(name) = line.strip_suffix(':') {
+            gates.push(GateText {
+                name: name.to_string(),
+                constraints: vec![],
+            });
+        } else if !line.starts_with("Total ") {
+            return Err(GATES);
+        }
+    }
+    Ok(gates)
+}
+
+// A gate polynomial as printed by `CircuitGates`, with cells queried as "S0"
+// or "A1@-1" and constants as 0, 1, -1 or hex
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum GateExpression {
+    Constant(String),
+    Query(String),
+    Negated(Box<GateExpression>),
+    Sum(Box<GateExpression>, Box<GateExpression>),
+    Product(Box<GateExpression>, Box<GateExpression>),
+}
+
+impl GateExpression {
+    pub(crate) fn parse(text: &str) -> Result<Self, FormatChanged> {
+        let tokens = tokenize(text)?;
+        let mut position = 0;
+        let expression = parse_sum(&tokens, &mut position)?;
+        if position != tokens.len() {
+            return Err(GATES);
+        }
+        Ok(expression)
+    }
+
+    // The cells the expression queries, sorted and without repeats
+    pub(crate) fn queries(&self) -> Vec<String> {
+        let mut queries = vec![];
+        self.collect_queries(&mut queries);
+        queries.sort();
+        queries.dedup();
+        queries
+    }
+
+    // The constants in the expression, sorted and without repeats
+    pub(crate) fn constants(&self) -> Vec<String> {
+        let mut constants = vec![];
+        self.collect_constants(&mut constants);
+        constants.sort();
+        constants.dedup();
+        constants
+    }
+
+    fn collect_queries(&self, queries: &mut Vec<String>) {
+        match self {
+            GateExpression::Constant(_) => {}
+            GateExpression::Query(query) => queries.push(query.clone()),
+            GateExpression::Negated(a) => a.collect_queries(queries),
+            GateExpression::Sum(a, b) | GateExpression::Product(a, b) => {
+                a.collect_queries(queries);
+                b.collect_queries(queries);
+            }
+        }
+    }
+
+    fn collect_constants(&self, constants: &mut Vec<String>) {
+        match self {
+            GateExpression::Constant(constant) => constants.push(constant.clone()),
+            GateExpression::Query(_) => {}
+            GateExpression::Negated(a) => a.collect_constants(constants),
+            GateExpression::Sum(a, b) | GateExpression::Product(a, b) => {
+                a.collect_constants(constants);
+                b.collect_constants(constants);
+            }
+        }
+    }
+
+    pub(crate) fn evaluate<F: Field>(&self, constant: &impl Fn(&str) -> F, query: &impl Fn(&str) -> F) -> F {
+        match self {
+            GateExpression::Constant(value) => constant(value),
+            GateExpression::Query(name) => query(name),
+            GateExpression::Negated(a) => -a.evaluate(constant, query),
+            GateExpression::Sum(a, b) => a.evaluate(constant, query) + b.evaluate(constant, query),
+            GateExpression::Product(a, b) => a.evaluate(constant, query) * b.evaluate(constant, query),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Open,
+    Close,
+    Plus,
+    Minus,
+    Times,
+    Atom(String),
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, FormatChanged> {
+    let mut tokens = vec![];
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            ' ' => {}
+            '(' => tokens.push(Token::Open),
+            ')' => tokens.push(Token::Close),
+            '+' => tokens.push(Token::Plus),
+            '-' => tokens.push(Token::Minus),
+            '*' => tokens.push(Token::Times),
+            c if c.is_ascii_alphanumeric() => {
+                let mut atom = c.to_string();
+                while let Some(&next) = chars.peek() {
+                    // Rotations are signed, as in "A1@-1"
+                    if next.is_ascii_alphanumeric() || next == '@' || (next == '-' && atom.ends_with('@')) {
+                        atom.push(next);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                tokens.push(Token::Atom(atom));
+            }
+            _ => return Err(GATES),
+        }
+    }
+    Ok(tokens)
+}
+
+fn parse_sum(tokens: &[Token], position: &mut usize) -> Result<GateExpression, FormatChanged> {
+    let mut sum = parse_product(tokens, position)?;
+    while let Some(token @ (Token::Plus | Token::Minus)) = tokens.get(*position) {
+        *position += 1;
+        let term = parse_product(tokens, position)?;
+        let term = match token {
+            Token::Minus => GateExpression::Negated(Box::new(term)),
+            _ => term,
+        };
+        sum = GateExpression::Sum(Box::new(sum), Box::new(term));
+    }
+    Ok(sum)
+}
+
+fn parse_product(tokens: &[Token], position: &mut usize) -> Result<GateExpression, FormatChanged> {
+    let mut product = parse_factor(tokens, position)?;
+    while tokens.get(*position) == Some(&Token::Times) {
+        *position += 1;
+        product = GateExpression::Product(Box::new(product), Box::new(parse_factor(tokens, position)?));
+    }
+    Ok(product)
+}
+
+fn parse_factor(tokens: &[Token], position: &mut usize) -> Result<GateExpression, FormatChanged> {
+    *position += 1;
+    match tokens.get(*position - 1) {
+        Some(Token::Minus) => Ok(GateExpression::Negated(Box::new(parse_factor(tokens, position)?))),
+        Some(Token::Open) => {
+            let expression = parse_sum(tokens, position)?;
+            if tokens.get(*position) != Some(&Token::Close) {
+                return Err(GATES);
+            }
+            *position += 1;
+            Ok(expression)
+        }
+        Some(Token::Atom(atom)) if atom.starts_with(|c: char| c.is_ascii_digit()) => {
+            Ok(GateExpression::Constant(atom.clone()))
+        }
+        Some(Token::Atom(atom)) => {
+            let mut chars = atom.chars();
+            let is_query = matches!(chars.next(), Some('A' | 'F' | 'I' | 'S'))
+                && chars.next().is_some_and(|c| c.is_ascii_digit());
+            if !is_query {
+                return Err(GATES);
+            }
+            Ok(GateExpression::Query(atom.clone()))
+        }
+        _ => Err(GATES),
+    }
+}
+
+// The name of a `dev::metadata::Region` or `dev::metadata::Gate`
+pub(crate) fn metadata_name(metadata: &impl Debug) -> Option<String> {
+    debug_str(&format!("{:?}", metadata), "name").map(String::from)
+}
+
+// The gate name and constraint index of a `dev::metadata::Constraint`
+pub(crate) fn metadata_constraint(metadata: &impl Debug) -> Option<(String, usize)> {
+    let debug = format!("{:?}", metadata);
+    let gate = debug_str(&debug, "name")?;
+    let index = debug_number(&debug, "}, index")?;
+    Some((gate.to_string(), usize::try_from(index).ok()?))
+}
+
+// The column of a `dev::metadata::Column`
+pub(crate) fn metadata_column(metadata: &impl Debug) -> Option<ColumnRef> {
+    column_ref(&format!("{:?}", metadata))
+}
+
+// The column and rotation of a `dev::metadata::VirtualCell`
+pub(crate) fn metadata_cell(metadata: &impl Debug) -> Option<(ColumnRef, i32)> {
+    let debug = format!("{:?}", metadata);
+    let rotation = debug_number(&debug, "rotation")?;
+    Some((column_ref(&debug)?, i32::try_from(rotation).ok()?))
+}
+
+// The string field `name: "..."` of a `Debug` string
+fn debug_str<'a>(debug: &'a str, name: &str) -> Option<&'a str> {
+    let start = debug.find(&format!("{}: \"", name))? + name.len() + 3;
+    let end = debug[start..].find('"')?;
+    Some(&debug[start..start + end])
+}
+
+// The number field `name: 12` of a `Debug` string
+fn debug_number(debug: &str, name: &str) -> Option<i64> {
+    let start = debug.find(&format!("{}: ", name))? + name.len() + 2;
+    let number: String = debug[start..]
+        .chars()
+        .enumerate()
+        .take_while(|(i, c)| c.is_ascii_digit() || (*i == 0 && *c == '-'))
+        .map(|(_, c)| c)
+        .collect();
+    number.parse().ok()
+}
+
+// The column in the `Debug` output of halo2's column metadata
+fn column_ref(debug: &str) -> Option<ColumnRef> {
+    let index = usize::try_from(debug_number(debug, "index")?).ok()?;
+    let start = debug.find("column_type: ")? + "column_type: ".len();
+    match &debug[start..] {
+        rest if rest.starts_with("Advice") => Some(ColumnRef::Advice(index)),
+        rest if rest.starts_with("Fixed") => Some(ColumnRef::Fixed(index)),
+        rest if rest.starts_with("Instance") => Some(ColumnRef::Instance(index)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use halo2_proofs::{
+        dev::{metadata, CircuitGates},
+        circuit::{Layouter, SimpleFloorPlanner},
+        plonk::{Advice, Any, Circuit, Column, Error, Expression, Fixed, Instance, Selector},
+        poly::Rotation,
+    };
+    use pasta_curves::Fp;
+
+    // A constraint system with every kind of column, a simple and a complex
+    // selector, a named and an unnamed constraint, a constant and a negative
+    // rotation
+    #[derive(Default)]
+    struct FormatCircuit;
+
+    #[derive(Debug, Clone)]
+    #[allow(dead_code)]
+    struct FormatConfig {
+        advice: [Column<Advice>; 2],
+        fixed: Column<Fixed>,
+        instance: Column<Instance>,
+        simple: Selector,
+        complex: Selector,
+    }
+
+    impl Circuit<Fp> for FormatCircuit {
+        type Config = FormatConfig;
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+            let advice = [meta.advice_column(), meta.advice_column()];
+            let fixed = meta.fixed_column();
+            let instance = meta.instance_column();
+            let simple = meta.selector();
+            let complex = meta.complex_selector();
+            meta.enable_equality(instance);
+            meta.enable_constant(fixed);
+            meta.create_gate("double", |meta| {
+                let s = meta.query_selector(simple);
+                let a = meta.query_advice(advice[0], Rotation::cur());
+                let b = meta.query_advice(advice[1], Rotation::cur());
+                vec![("b = 2a", s * (b - a * Expression::Constant(Fp::from(2))))]
+            });
+            meta.create_gate("copy", |meta| {
+                let s = meta.query_selector(complex);
+                let a = meta.query_advice(advice[0], Rotation::prev());
+                let f = meta.query_fixed(fixed, Rotation::cur());
+                let i = meta.query_instance(instance, Rotation::cur());
+                vec![s * (a + f - i)]
+            });
+            FormatConfig {
+                advice,
+                fixed,
+                instance,
+                simple,
+                complex,
+            }
+        }
+
+        fn synthesize(&self, _: Self::Config, _: impl Layouter<Fp>) -> Result<(), Error> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_pinned_system_format() {
+        let mut cs = ConstraintSystem::<Fp>::default();
+        let config = FormatCircuit::configure(&mut cs);
+        assert_eq!(
+            PinnedSystem::of(&cs).unwrap(),
+            PinnedSystem {
+                advice: 2,
+                fixed: 1,
+                instance: 1,
+                simple_selectors: vec![true, false],
+                advice_queries: vec![(ColumnRef::Advice(0), 0), (ColumnRef::Advice(1), 0), (ColumnRef::Advice(0), -1)],
+                instance_queries: vec![(ColumnRef::Instance(0), 0)],
+                fixed_queries: vec![(ColumnRef::Fixed(0), 0)],
+                equality: vec![ColumnRef::Instance(0), ColumnRef::Fixed(0)],
+                constants: vec![ColumnRef::Fixed(0)],
+            }
+        );
+        assert_eq!(debug_index(&config.advice[1]), 1);
+        assert_eq!(debug_index(&config.complex), 1);
+    }
+
+    #[test]
+    fn test_gates_format() {
+        let gates = parse_gates(&CircuitGates::collect::<Fp, FormatCircuit>().to_string()).unwrap();
+        let texts: Vec<(&str, Vec<(&str, &str)>)> = gates
+            .iter()
+            .map(|gate| {
+                let constraints = gate
+                    .constraints
+                    .iter()
+                    .map(|constraint| (constraint.name.as_str(), constraint.expression.as_str()))
+                    .collect();
+                (gate.name.as_str(), constraints)
+            })
+            .collect();
+        assert_eq!(
+            texts,
+            vec![
+                ("double", vec![("b = 2a", "S0 * (A1@0 - (A0@0 * 0x2))")]),
+                ("copy", vec![("", "S1 * (A0@-1 + F0@0 - I0@0)")]),
+            ]
+        );
+
+        let copy = GateExpression::parse(&gates[1].constraints[0].expression).unwrap();
+        assert_eq!(copy.queries(), vec!["A0@-1", "F0@0", "I0@0", "S1"]);
+        let value = |query: &str| match query {
+            "A0@-1" => Fp::from(5),
+            "F0@0" => Fp::from(7),
+            "I0@0" => Fp::from(2),
+            _ => Fp::from(3),
+        };
+        assert_eq!(copy.evaluate(&|_| unreachable!(), &value), Fp::from(30));
+
+        // Binary and unary minus, and nesting
+        let expression = GateExpression::parse("-(A1@-1 - S0) * -1 - A1@-1").unwrap();
+        let constant = |value: &str| if value == "1" { Fp::one() } else { unreachable!() };
+        assert_eq!(expression.evaluate(&constant, &value), -Fp::from(3));
+    }
+
+    #[test]
+    fn test_format_drift() {
+        // Text of another shape is an error, not zero columns or a panic
+        let mut cs = ConstraintSystem::<Fp>::default();
+        FormatCircuit::configure(&mut cs);
+        let pinned = format!("{:?}", cs.pinned());
+        assert_eq!(PinnedSystem::parse(&pinned.replace("num_fixed_columns", "fixed_columns")), Err(PINNED));
+        assert_eq!(PinnedSystem::parse(&pinned.replace("Rotation(-1)", "Rotation(prev)")), Err(PINNED));
+        assert_eq!(PinnedSystem::parse(&pinned.replace("column_type: Fixed", "column_type: Constant")), Err(PINNED));
+
+        assert_eq!(parse_gates("- S0 * A0@0\n"), Err(GATES));
+        assert_eq!(parse_gates("double:\n- S0 * A1@0\n= 1 multiplication\n"), Err(GATES));
+        assert_eq!(GateExpression::parse("S0 * (A1@0 - A0@0"), Err(GATES));
+        assert_eq!(GateExpression::parse("S0 * A1[0]"), Err(GATES));
+        assert_eq!(GateExpression::parse("S0 * advice(1)"), Err(GATES));
+        assert_eq!(GateExpression::parse("S0 A1@0"), Err(GATES));
+    }
+
+    #[test]
+    fn test_metadata_format() {
+        let column = metadata::Column::from((Any::Fixed, 3));
+        assert_eq!(metadata_column(&column), Some(ColumnRef::Fixed(3)));
+        let cell = metadata::VirtualCell::from((metadata::Column::from((Any::Advice, 2)), -1));
+        assert_eq!(metadata_cell(&cell), Some((ColumnRef::Advice(2), -1)));
+        let gate = metadata::Gate::from((4, "MiMC5 hash rounds"));
+        assert_eq!(metadata_name(&gate).as_deref(), Some("MiMC5 hash rounds"));
+        let constraint = metadata::Constraint::from((gate, 1, "state"));
+        assert_eq!(metadata_constraint(&constraint), Some(("MiMC5 hash rounds".to_string(), 1)));
+        let region = metadata::Region::from((6, "MiMC5 table"));
+        assert_eq!(metadata_name(&region).as_deref(), Some("MiMC5 table"));
+    }
+}
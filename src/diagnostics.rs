@@ -0,0 +1,523 @@
+// Explanations of `MockProver` failures in terms of the MiMC rounds.
+//
+// In the chip regions the row offset of a cell is the round whose output it
+// holds, so a failure at offset i of a "MiMC5 ... table" region is reported
+// as round i. Failed round constraints are recomputed from the cell values
+// the failure carries, giving the expected output next to the assigned one,
+// e.g. "round 37: state mismatch, expected 0x1f.. got 0x2a.. (previous state
+// .., key .., constant c36 = ..)". Columns are named by the labels of the
+// circuit's configuration. Failures outside the MiMC gates and regions keep
+// halo2's own description.
+//
+// The failure metadata is private to halo2, so the gate, region and cell
+// details are read from its `Debug` output (see `debug_format.rs`).
+use std::fmt;
+
+use halo2_proofs::{
+    arithmetic::FieldExt,
+    dev::{FailureLocation, VerifyFailure},
+    plonk::{Circuit, ConstraintSystem},
+};
+use pasta_curves::group::ff::PrimeField;
+
+use crate::assignment::ColumnRef;
+use crate::debug_format::{metadata_cell, metadata_column, metadata_constraint, metadata_name};
+use crate::field_io::{format_field, parse_field, FieldFormat};
+use crate::layout::{ColumnLabels, LabeledConfig};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FailureReport {
+    pub region: Option<String>,
+    // The round whose row failed, for failures in a chip region
+    pub round: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for FailureReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.round, &self.region) {
+            (Some(round), _) => write!(f, "round {}: {}", round, self.message),
+            (None, Some(region)) => write!(f, "{}: {}", region, self.message),
+            (None, None) => write!(f, "{}", self.message),
+        }
+    }
+}
+
+// Explains failures of circuit `C`, labelling columns by its configuration
+pub fn explain_circuit_failures<F, C>(failures: &[VerifyFailure]) -> Vec<FailureReport>
+where
+    F: FieldExt + PrimeField<Repr = [u8; 32]>,
+    C: Circuit<F>,
+    C::Config: LabeledConfig,
+{
+    let labels = C::configure(&mut ConstraintSystem::default()).labels();
+    explain_failures::<F>(failures, &labels)
+}
+
+pub fn explain_failures<F: FieldExt + PrimeField<Repr = [u8; 32]>>(
+    failures: &[VerifyFailure],
+    labels: &ColumnLabels,
+) -> Vec<FailureReport> {
+    failures
+        .iter()
+        .map(|failure| explain_failure::<F>(failure, labels))
+        .collect()
+}
+
+pub fn explain_failure<F: FieldExt + PrimeField<Repr = [u8; 32]>>(
+    failure: &VerifyFailure,
+    labels: &ColumnLabels,
+) -> FailureReport {
+    let label = |column: ColumnRef| match labels.column_label(column) {
+        Some(label) => format!("{} ({})", label, column),
+        None => column.to_string(),
+    };
+    match failure {
+        VerifyFailure::ConstraintNotSatisfied {
+            constraint,
+            location: FailureLocation::InRegion { region, offset },
+            cell_values,
+        } => {
+            let (gate, index) = metadata_constraint(constraint).unwrap_or_default();
+            let cells: Vec<(Option<&str>, i32, &str)> = cell_values
+                .iter()
+                .map(|(cell, value)| {
+                    let cell = metadata_cell(cell);
+                    (
+                        cell.and_then(|(column, _)| labels.column_label(column)),
+                        cell.map_or(0, |(_, rotation)| rotation),
+                        value.as_str(),
+                    )
+                })
+                .collect();
+            let region = metadata_name(region);
+            match explain_round_constraint::<F>(&gate, index, *offset, &cells) {
+                Some((round, message)) => FailureReport { region, round, message },
+                None => FailureReport {
+                    region: None,
+                    round: None,
+                    message: failure.to_string(),
+                },
+            }
+        }
+        VerifyFailure::CellNotAssigned {
+            gate,
+            region,
+            gate_offset,
+            column,
+            offset,
+        } => {
+            let region = metadata_name(region);
+            let cell_row = *gate_offset as isize + offset;
+            FailureReport {
+                round: region
+                    .as_deref()
+                    .filter(|region| is_round_region(region))
+                    .map(|_| *gate_offset),
+                message: format!(
+                    "{} at offset {} is not assigned but gate '{}' uses it",
+                    label(ColumnRef::of(column)),
+                    cell_row,
+                    metadata_name(gate).unwrap_or_default()
+                ),
+                region,
+            }
+        }
+        VerifyFailure::Permutation { column, location } => {
+            let column = metadata_column(column).map_or_else(|| format!("{:?}", column), label);
+            match location {
+                FailureLocation::InRegion { region, offset } => {
+                    let region = metadata_name(region);
+                    FailureReport {
+                        round: region
+                            .as_deref()
+                            .filter(|region| is_round_region(region))
+                            .map(|_| *offset),
+                        message: format!("{} at offset {} differs from a cell it is copied to", column, offset),
+                        region,
+                    }
+                }
+                FailureLocation::OutsideRegion { row } => FailureReport {
+                    region: None,
+                    round: None,
+                    message: format!("{} at row {} differs from a cell it is copied to", column, row),
+                },
+            }
+        }
+        _ => FailureReport {
+            region: None,
+            round: None,
+            message: failure.to_string(),
+        },
+    }
+}
+
+// Regions whose offsets are round numbers
+fn is_round_region(name: &str) -> bool {
+    name.starts_with("MiMC5") && name.contains("table")
+}
+
+// The round and explanation of a failed constraint of a MiMC gate, given
+// the (label, rotation, value) of the cells it queries
+fn explain_round_constraint<F: FieldExt + PrimeField<Repr = [u8; 32]>>(
+    gate: &str,
+    index: usize,
+    offset: usize,
+    cells: &[(Option<&str>, i32, &str)],
+) -> Option<(Option<usize>, String)> {
+    let raw = |label: &str, rotation: i32| {
+        cells
+            .iter()
+            .find(|(cell_label, cell_rotation, _)| *cell_label == Some(label) && *cell_rotation == rotation)
+            .map(|(_, _, value)| *value)
+    };
+    let value = |label: &str, rotation: i32| raw(label, rotation).and_then(parse_value::<F>);
+    let pow5 = |x: F| x.square().square() * x;
+    // Cells that are not queried, such as the key of a hash, are zero
+    let key = value("key", 0).unwrap_or_else(F::zero);
+    let key_context = raw("key", 0).map_or(String::new(), |key| format!(", key {}", key));
+    let mismatch = |what: &str, expected: Option<F>, got: Option<&str>, context: String| {
+        let expected = expected.map_or("?".to_string(), |expected| format_value(&expected));
+        format!(
+            "{} mismatch, expected {} got {} ({})",
+            what,
+            expected,
+            got.unwrap_or("?"),
+            context
+        )
+    };
+    let constant = |column: &str, what: &str, name: &str| {
+        format!(
+            "{} {}{} = {}",
+            what,
+            name,
+            offset.wrapping_sub(1),
+            raw(column, -1).unwrap_or("?")
+        )
+    };
+
+    let explanation = match (gate, index) {
+        ("MiMC5 hash rounds" | "MiMC5 encryption rounds", 0) => {
+            let expected = value("state", -1)
+                .zip(value("round constants", -1))
+                .map(|(x, c)| pow5(x + key + c));
+            let context = format!(
+                "previous state {}{}, {}",
+                raw("state", -1).unwrap_or("?"),
+                key_context,
+                constant("round constants", "constant", "c")
+            );
+            (Some(offset), mismatch("state", expected, raw("state", 0), context))
+        }
+        ("MiMC5 fixed key encryption rounds", _) => {
+            let expected = value("state", -1)
+                .zip(value("round keys", -1))
+                .map(|(x, k)| pow5(x + k));
+            let context = format!(
+                "previous state {}, {}",
+                raw("state", -1).unwrap_or("?"),
+                constant("round keys", "round key", "rk")
+            );
+            (Some(offset), mismatch("state", expected, raw("state", 0), context))
+        }
+        ("post rounds key addition", _) => {
+            let expected = value("state", -1).zip(value("key", -1)).map(|(x, k)| x + k);
+            let context = format!(
+                "last state {}, key {}",
+                raw("state", -1).unwrap_or("?"),
+                raw("key", -1).unwrap_or("?")
+            );
+            (None, mismatch("final key addition", expected, raw("state", 0), context))
+        }
+        ("post rounds fixed key addition", _) => {
+            let expected = value("state", -1).zip(value("round keys", -1)).map(|(x, k)| x + k);
+            let context = format!(
+                "last state {}, key {}",
+                raw("state", -1).unwrap_or("?"),
+                raw("round keys", -1).unwrap_or("?")
+            );
+            (None, mismatch("final key addition", expected, raw("state", 0), context))
+        }
+        ("MiMC5 encryption rounds", 1) | ("MiMC5 Feistel encryption inner rounds" | "MiMC5 Feistel last round", 2) => (
+            Some(offset),
+            format!(
+                "key {} differs from the key {} of the previous round",
+                raw("key", 0).unwrap_or("?"),
+                raw("key", -1).unwrap_or("?")
+            ),
+        ),
+        ("MiMC5 Feistel inner rounds" | "MiMC5 Feistel encryption inner rounds", 0) => {
+            let expected = value("state_left", -1)
+                .zip(value("state_right", -1))
+                .zip(value("round constants", -1))
+                .map(|((l, r), c)| r + pow5(l + key + c));
+            let context = format!(
+                "previous left {}, previous right {}{}, {}",
+                raw("state_left", -1).unwrap_or("?"),
+                raw("state_right", -1).unwrap_or("?"),
+                key_context,
+                constant("round constants", "constant", "c")
+            );
+            (
+                Some(offset),
+                mismatch("left state", expected, raw("state_left", 0), context),
+            )
+        }
+        ("MiMC5 Feistel inner rounds" | "MiMC5 Feistel encryption inner rounds", 1) => {
+            let context = "the right state takes the previous left state".to_string();
+            (
+                Some(offset),
+                mismatch("right state", value("state_left", -1), raw("state_right", 0), context),
+            )
+        }
+        ("MiMC5 Feistel last round", 0) => {
+            let context = "the last round keeps the left state".to_string();
+            (
+                Some(offset),
+                mismatch("left state", value("state_left", -1), raw("state_left", 0), context),
+            )
+        }
+        ("MiMC5 Feistel last round", 1) => {
+            let expected = value("state_left", -1)
+                .zip(value("state_right", -1))
+                .map(|(l, r)| r + pow5(l + key));
+            let context = format!(
+                "previous left {}, previous right {}{}",
+                raw("state_left", -1).unwrap_or("?"),
+                raw("state_right", -1).unwrap_or("?"),
+                key_context
+            );
+            (
+                Some(offset),
+                mismatch("right state", expected, raw("state_right", 0), context),
+            )
+        }
+        ("MiMC sponge absorb", _) => {
+            let expected = value("state_left", -1)
+                .zip(value("state_right", -1))
+                .map(|(l, v)| l + v);
+            let context = format!(
+                "left state {}, absorbed value {}",
+                raw("state_left", -1).unwrap_or("?"),
+                raw("state_right", -1).unwrap_or("?")
+            );
+            (
+                None,
+                mismatch("absorbed state", expected, raw("state_left", 0), context),
+            )
+        }
+        _ => return None,
+    };
+    Some(explanation)
+}
+
+// Values are printed by MockProver as 0, 1, -1 or hex without leading zeros
+fn parse_value<F: FieldExt + PrimeField<Repr = [u8; 32]>>(value: &str) -> Option<F> {
+    match value.strip_prefix('-') {
+        Some(value) => parse_field::<F>(value).ok().map(|value| -value),
+        None => parse_field::<F>(value).ok(),
+    }
+}
+
+fn format_value<F: FieldExt + PrimeField<Repr = [u8; 32]>>(value: &F) -> String {
+    if *value == -F::one() {
+        return "-1".to_string();
+    }
+    let hex = format_field(value, FieldFormat::Hex);
+    match hex.trim_start_matches("0x").trim_start_matches('0') {
+        "" => "0".to_string(),
+        "1" => "1".to_string(),
+        digits => format!("0x{}", digits),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use halo2_proofs::dev::MockProver;
+    use pasta_curves::Fp;
+
+    use crate::assignment::{AssignmentTable, ReplayCircuit};
+    use crate::circuits::{
+        mimc_cipher::MiMC5CipherPallasCircuit, mimc_feistel_hash::MiMC5FeistelHashPallasCircuit, Visibility,
+    };
+    use crate::circuits::{mimc_feistel_cipher::MiMC5FeistelCipherPallasCircuit, mimc_hash::MiMC5HashPallasCircuit};
+    use crate::field::MiMCField;
+    use crate::mimc::primitives::{mimc5_encrypt_trace, mimc5_hash_trace};
+    use crate::mimc_feistel::primitives::{mimc5_feistel_encrypt_trace, mimc5_feistel_hash_trace};
+
+    // Replays the assignment of `circuit` with one advice cell changed and
+    // explains the failures
+    fn edited_failures<C: Circuit<Fp>>(
+        circuit: &C,
+        k: u32,
+        instance: Vec<Fp>,
+        column: usize,
+        row: usize,
+    ) -> Vec<FailureReport>
+    where
+        C::Config: LabeledConfig,
+    {
+        let mut table = AssignmentTable::synthesize(k, circuit, vec![instance]).unwrap();
+        let value = table.advice[column].get_mut(&row).unwrap();
+        *value = value.map(|v| v + Fp::one());
+        let prover = MockProver::run(k, &ReplayCircuit::<Fp, C>::new(table.clone()).unwrap(), table.instance).unwrap();
+        explain_circuit_failures::<Fp, C>(&prover.verify().unwrap_err())
+    }
+
+    #[test]
+    fn test_explain_cipher_round_failure() {
+        let (message, key) = (Fp::from(3), Fp::from(5));
+        let circuit = MiMC5CipherPallasCircuit::new(message, key);
+        // The chip region starts at row 0, so the replayed row is the round;
+        // the state column is A1
+        let reports = edited_failures(&circuit, 7, circuit.public_inputs(), 1, 37);
+
+        let trace = mimc5_encrypt_trace(message, key, Fp::MIMC5_ROUND_CONSTANTS);
+        let round_37 = &reports[0];
+        assert_eq!(round_37.round, Some(37));
+        let expected = format!(
+            "state mismatch, expected {} got {}",
+            format_value(&trace[37]),
+            format_value(&(trace[37] + Fp::one()))
+        );
+        assert!(round_37.message.starts_with(&expected), "{}", round_37);
+        assert!(round_37.message.contains("key 0x5, constant c36 = 0x"), "{}", round_37);
+        assert!(round_37.to_string().starts_with("round 37: state mismatch"));
+        // The next round reads the edited state
+        assert!(reports.iter().any(|report| report.round == Some(38)));
+    }
+
+    #[test]
+    fn test_explain_feistel_failures() {
+        let (left, right) = (Fp::from(1), Fp::from(2));
+        let circuit = MiMC5FeistelHashPallasCircuit::new(left, right);
+        // state_right is A2
+        let reports = edited_failures(&circuit, 8, circuit.public_inputs(), 2, 10);
+        let trace = mimc5_feistel_hash_trace(left, right, Fp::MIMC5_FEISTEL_ROUND_CONSTANTS);
+        let round_10 = reports.iter().find(|report| report.round == Some(10)).unwrap();
+        assert_eq!(
+            round_10.message,
+            format!(
+                "right state mismatch, expected {} got {} (the right state takes the previous left state)",
+                format_value(&trace[9].0),
+                format_value(&(trace[10].1 + Fp::one()))
+            )
+        );
+
+        // A public input that does not match its cell
+        let circuit = MiMC5CipherPallasCircuit::with_visibility(
+            Fp::from(3),
+            Fp::from(5),
+            Visibility::Public,
+            Visibility::Private,
+            Visibility::Public,
+        );
+        let mut instance = circuit.public_inputs();
+        instance[0] += Fp::one();
+        let failures = MockProver::run(7, &circuit, vec![instance])
+            .unwrap()
+            .verify()
+            .unwrap_err();
+        let reports = explain_circuit_failures::<Fp, MiMC5CipherPallasCircuit>(&failures);
+        assert!(
+            reports
+                .iter()
+                .any(|report| report.message.starts_with("instance (I0) at row 0 differs")),
+            "{:?}",
+            reports
+        );
+    }
+
+    #[test]
+    fn test_explain_hash_round_failure() {
+        let message = Fp::from(7);
+        let circuit = MiMC5HashPallasCircuit::new(message);
+        // The state column is A1, and a hash has no key
+        let reports = edited_failures(&circuit, 7, circuit.public_inputs(), 1, 37);
+        let trace = mimc5_hash_trace(message, Fp::MIMC5_ROUND_CONSTANTS);
+        let round_37 = reports.iter().find(|report| report.round == Some(37)).unwrap();
+        let expected = format!(
+            "state mismatch, expected {} got {} (previous state {}, constant c36 = 0x",
+            format_value(&trace[37]),
+            format_value(&(trace[37] + Fp::one())),
+            format_value(&trace[36])
+        );
+        assert!(round_37.message.starts_with(&expected), "{}", round_37);
+    }
+
+    #[test]
+    fn test_explain_key_mismatch() {
+        let (message, key) = (Fp::from(3), Fp::from(5));
+        let circuit = MiMC5CipherPallasCircuit::new(message, key);
+        // The key column is A2. The edited key differs from the key of
+        // round 36 and from the key of round 38
+        let reports = edited_failures(&circuit, 7, circuit.public_inputs(), 2, 37);
+        let key_reports: Vec<_> = reports
+            .iter()
+            .filter(|report| report.message.contains("of the previous round"))
+            .map(|report| (report.round, report.message.as_str()))
+            .collect();
+        assert_eq!(
+            key_reports,
+            vec![
+                (Some(37), "key 0x6 differs from the key 0x5 of the previous round"),
+                (Some(38), "key 0x5 differs from the key 0x6 of the previous round"),
+            ]
+        );
+
+        let circuit = MiMC5FeistelCipherPallasCircuit::new(Fp::from(1), Fp::from(2), key);
+        // The Feistel key column is A3, and row 220 is the last round
+        let reports = edited_failures(&circuit, 8, circuit.public_inputs(), 3, 220);
+        assert!(
+            reports.iter().any(|report| report.round == Some(220)
+                && report.message == "key 0x6 differs from the key 0x5 of the previous round"),
+            "{:?}",
+            reports
+        );
+    }
+
+    #[test]
+    fn test_explain_feistel_cipher_failures() {
+        let (left, right, key) = (Fp::from(1), Fp::from(2), Fp::from(5));
+        let circuit = MiMC5FeistelCipherPallasCircuit::new(left, right, key);
+        let trace = mimc5_feistel_encrypt_trace(left, right, key, Fp::MIMC5_FEISTEL_ROUND_CONSTANTS);
+
+        // An inner round; state_left is A1
+        let reports = edited_failures(&circuit, 8, circuit.public_inputs(), 1, 10);
+        let round_10 = reports.iter().find(|report| report.round == Some(10)).unwrap();
+        let expected = format!(
+            "left state mismatch, expected {} got {} (previous left {}, previous right {}, key 0x5, constant c9 = 0x",
+            format_value(&trace[10].0),
+            format_value(&(trace[10].0 + Fp::one())),
+            format_value(&trace[9].0),
+            format_value(&trace[9].1)
+        );
+        assert!(round_10.message.starts_with(&expected), "{}", round_10);
+
+        // The last round keeps the left state and updates the right one,
+        // which is A2
+        let reports = edited_failures(&circuit, 8, circuit.public_inputs(), 1, 220);
+        let round_220 = reports.iter().find(|report| report.round == Some(220)).unwrap();
+        assert_eq!(
+            round_220.message,
+            format!(
+                "left state mismatch, expected {} got {} (the last round keeps the left state)",
+                format_value(&trace[219].0),
+                format_value(&(trace[220].0 + Fp::one()))
+            )
+        );
+        let reports = edited_failures(&circuit, 8, circuit.public_inputs(), 2, 220);
+        let round_220 = reports.iter().find(|report| report.round == Some(220)).unwrap();
+        assert_eq!(
+            round_220.message,
+            format!(
+                "right state mismatch, expected {} got {} (previous left {}, previous right {}, key 0x5)",
+                format_value(&trace[220].1),
+                format_value(&(trace[220].1 + Fp::one())),
+                format_value(&trace[219].0),
+                format_value(&trace[219].1)
+            )
+        );
+    }
+}
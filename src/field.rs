@@ -0,0 +1,100 @@
+use halo2_proofs::arithmetic::FieldExt;
+use pasta_curves::{Fp, Fq};
+
+use crate::mimc::{
+    primitives::{mimc5_decrypt, mimc5_encrypt, PALLAS_INV_5, VESTA_INV_5},
+    round_constants::{MIMC_PALLAS_ROUND_CONSTANTS, MIMC_VESTA_ROUND_CONSTANTS},
+};
+use crate::mimc_feistel::{
+    primitives::{mimc5_feistel_decrypt, mimc5_feistel_encrypt},
+    round_constants::{MIMC_FEISTEL_PALLAS_ROUND_CONSTANTS, MIMC_FEISTEL_VESTA_ROUND_CONSTANTS},
+};
+
+// A field the MiMC chips can be instantiated over. Supporting a new field
+// means implementing this trait: the chips are generic over it and take their
+// round constants, and with them their number of rounds, from here. The
+// native primitives over the field's constants come with it, for the code
+// that is generic over the field, such as the sponge and the RNG.
+pub trait MiMCField: FieldExt {
+    // One constant per MiMC5 round
+    const MIMC5_ROUND_CONSTANTS: &'static [Self];
+
+    // One constant per MiMC5 Feistel round. The last round uses no constant,
+    // so the last one is never read.
+    const MIMC5_FEISTEL_ROUND_CONSTANTS: &'static [Self];
+
+    // The inverse of 5 modulo p - 1 as little-endian 64-bit limbs, the
+    // exponent of the fifth root taken by MiMC5 decryption
+    const INV_5: [u64; 4];
+
+    // MiMC5 encryption. Hashing is encryption under the zero key.
+    fn encrypt(state: &mut Self, key: Self) {
+        mimc5_encrypt(state, key, Self::MIMC5_ROUND_CONSTANTS);
+    }
+
+    fn decrypt(state: &mut Self, key: Self) {
+        mimc5_decrypt(state, key, Self::MIMC5_ROUND_CONSTANTS, &Self::INV_5);
+    }
+
+    // The MiMC5 Feistel permutation, which is the MiMC5 Feistel hash
+    fn permute(state_l: &mut Self, state_r: &mut Self) {
+        Self::feistel_encrypt(state_l, state_r, Self::zero());
+    }
+
+    fn feistel_encrypt(state_l: &mut Self, state_r: &mut Self, key: Self) {
+        mimc5_feistel_encrypt(state_l, state_r, key, Self::MIMC5_FEISTEL_ROUND_CONSTANTS);
+    }
+
+    fn feistel_decrypt(state_l: &mut Self, state_r: &mut Self, key: Self) {
+        mimc5_feistel_decrypt(state_l, state_r, key, Self::MIMC5_FEISTEL_ROUND_CONSTANTS);
+    }
+}
+
+impl MiMCField for Fp {
+    const MIMC5_ROUND_CONSTANTS: &'static [Self] = &MIMC_PALLAS_ROUND_CONSTANTS;
+    const MIMC5_FEISTEL_ROUND_CONSTANTS: &'static [Self] = &MIMC_FEISTEL_PALLAS_ROUND_CONSTANTS;
+    const INV_5: [u64; 4] = PALLAS_INV_5;
+}
+
+impl MiMCField for Fq {
+    const MIMC5_ROUND_CONSTANTS: &'static [Self] = &MIMC_VESTA_ROUND_CONSTANTS;
+    const MIMC5_FEISTEL_ROUND_CONSTANTS: &'static [Self] = &MIMC_FEISTEL_VESTA_ROUND_CONSTANTS;
+    const INV_5: [u64; 4] = VESTA_INV_5;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mimc::primitives::{mimc5_decrypt_vesta, mimc5_encrypt_pallas};
+    use crate::mimc_feistel::primitives::{mimc5_feistel_encrypt_vesta, mimc5_feistel_hash_pallas};
+
+    #[test]
+    fn test_field_primitives() {
+        let (message, key) = (Fp::from(3), Fp::from(5));
+        let (mut state, mut expected) = (message, message);
+        Fp::encrypt(&mut state, key);
+        mimc5_encrypt_pallas(&mut expected, key);
+        assert_eq!(state, expected);
+        Fp::decrypt(&mut state, key);
+        assert_eq!(state, message);
+
+        let mut state = (Fp::from(1), Fp::from(2));
+        let mut expected = state;
+        Fp::permute(&mut state.0, &mut state.1);
+        mimc5_feistel_hash_pallas(&mut expected.0, &mut expected.1);
+        assert_eq!(state, expected);
+
+        let (mut state, mut expected) = (Fq::from(7), Fq::from(7));
+        Fq::decrypt(&mut state, Fq::from(2));
+        mimc5_decrypt_vesta(&mut expected, Fq::from(2));
+        assert_eq!(state, expected);
+
+        let mut state = (Fq::from(1), Fq::from(2));
+        let mut expected = state;
+        Fq::feistel_encrypt(&mut state.0, &mut state.1, Fq::from(3));
+        mimc5_feistel_encrypt_vesta(&mut expected.0, &mut expected.1, Fq::from(3));
+        assert_eq!(state, expected);
+        Fq::feistel_decrypt(&mut state.0, &mut state.1, Fq::from(3));
+        assert_eq!(state, (Fq::from(1), Fq::from(2)));
+    }
+}
@@ -0,0 +1,139 @@
+// Text encodings for Pallas and Vesta base field elements: "0x"-prefixed
+// big-endian hex or plain decimal. Values at or above the modulus are rejected
+// rather than silently reduced.
+use std::fmt;
+
+use pasta_curves::group::ff::PrimeField;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldFormat {
+    Hex,
+    Decimal,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseFieldError {
+    Empty,
+    InvalidDigit(char),
+    // The value does not fit in 256 bits or is not below the field modulus
+    OutOfRange,
+}
+
+impl fmt::Display for ParseFieldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseFieldError::Empty => write!(f, "empty field element"),
+            ParseFieldError::InvalidDigit(c) => write!(f, "invalid digit {:?} in field element", c),
+            ParseFieldError::OutOfRange => write!(f, "field element is not below the field modulus"),
+        }
+    }
+}
+
+impl std::error::Error for ParseFieldError {}
+
+// Parses "0x..." as big-endian hex and anything else as decimal
+pub fn parse_field<F: PrimeField<Repr = [u8; 32]>>(s: &str) -> Result<F, ParseFieldError> {
+    let s = s.trim();
+    let limbs = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => parse_limbs(hex, 16)?,
+        None => parse_limbs(s, 10)?,
+    };
+    let mut repr = [0u8; 32];
+    for (chunk, limb) in repr.chunks_exact_mut(8).zip(limbs) {
+        chunk.copy_from_slice(&limb.to_le_bytes());
+    }
+    Option::from(F::from_repr(repr)).ok_or(ParseFieldError::OutOfRange)
+}
+
+pub fn format_field<F: PrimeField<Repr = [u8; 32]>>(value: &F, format: FieldFormat) -> String {
+    let repr = value.to_repr();
+    match format {
+        FieldFormat::Hex => {
+            let digits: String = repr.iter().rev().map(|b| format!("{:02x}", b)).collect();
+            format!("0x{}", digits)
+        }
+        FieldFormat::Decimal => {
+            let mut limbs = [0u64; 4];
+            for (limb, chunk) in limbs.iter_mut().zip(repr.chunks_exact(8)) {
+                *limb = u64::from_le_bytes(chunk.try_into().unwrap());
+            }
+            format_limbs_decimal(limbs)
+        }
+    }
+}
+
+// Little-endian 64-bit limbs of a non-negative integer in the given radix
+fn parse_limbs(digits: &str, radix: u32) -> Result<[u64; 4], ParseFieldError> {
+    if digits.is_empty() {
+        return Err(ParseFieldError::Empty);
+    }
+    let mut limbs = [0u64; 4];
+    for c in digits.chars().filter(|c| *c != '_') {
+        let digit = c.to_digit(radix).ok_or(ParseFieldError::InvalidDigit(c))?;
+        let mut carry = digit as u128;
+        for limb in limbs.iter_mut() {
+            let wide = (*limb as u128) * (radix as u128) + carry;
+            *limb = wide as u64;
+            carry = wide >> 64;
+        }
+        if carry != 0 {
+            return Err(ParseFieldError::OutOfRange);
+        }
+    }
+    Ok(limbs)
+}
+
+fn format_limbs_decimal(mut limbs: [u64; 4]) -> String {
+    // Peel off base 10^19 digits, the largest power of ten in a u64
+    const BASE: u128 = 10_000_000_000_000_000_000;
+    let mut groups = vec![];
+    while limbs.iter().any(|limb| *limb != 0) {
+        let mut remainder = 0u128;
+        for limb in limbs.iter_mut().rev() {
+            let wide = (remainder << 64) | (*limb as u128);
+            *limb = (wide / BASE) as u64;
+            remainder = wide % BASE;
+        }
+        groups.push(remainder as u64);
+    }
+    match groups.split_last() {
+        None => "0".to_string(),
+        Some((most_significant, rest)) => {
+            let mut s = most_significant.to_string();
+            for group in rest.iter().rev() {
+                s.push_str(&format!("{:019}", group));
+            }
+            s
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pasta_curves::{Fp, Fq};
+    use pasta_curves::arithmetic::FieldExt;
+
+    #[test]
+    fn test_field_round_trip() {
+        let values = [Fp::zero(), Fp::one(), Fp::from(1u64 << 63) * Fp::from(u64::MAX), -Fp::one()];
+        for value in values {
+            for format in [FieldFormat::Hex, FieldFormat::Decimal] {
+                let s = format_field(&value, format);
+                assert_eq!(parse_field::<Fp>(&s), Ok(value), "{}", s);
+            }
+        }
+        assert_eq!(format_field(&Fq::from(12345), FieldFormat::Decimal), "12345");
+        assert_eq!(parse_field::<Fq>("0x3039"), Ok(Fq::from(12345)));
+    }
+
+    #[test]
+    fn test_field_rejects_bad_input() {
+        assert_eq!(parse_field::<Fp>(""), Err(ParseFieldError::Empty));
+        assert_eq!(parse_field::<Fp>("12a"), Err(ParseFieldError::InvalidDigit('a')));
+        // The modulus itself and anything wider than 256 bits are out of range
+        let modulus = Fp::MODULUS;
+        assert_eq!(parse_field::<Fp>(modulus), Err(ParseFieldError::OutOfRange));
+        assert_eq!(parse_field::<Fp>(&format!("0x1{}", "0".repeat(64))), Err(ParseFieldError::OutOfRange));
+    }
+}
@@ -0,0 +1,252 @@
+// A streaming hasher over field elements and bytes built on the MiMC5 Feistel
+// sponge (see `MiMCSponge`).
+//
+// The input is a sequence of segments. Consecutive `update` calls form one
+// segment of field elements and consecutive `update_bytes` calls one segment
+// of bytes, packed into 31-byte little-endian field elements. Each segment is
+// absorbed followed by a tag 2 * length + kind, where the length counts
+// elements for field segments (kind 0) and bytes for byte segments (kind 1).
+// Reading tags back from the end recovers every segment, so different inputs
+// never absorb the same sequence. An empty update is a no-op, so it neither
+// opens nor closes a segment. `finalize` closes the last segment and squeezes
+// once.
+use digest::{
+    consts::U32, FixedOutput, FixedOutputReset, HashMarker, Output, OutputSizeUser, Reset, Update,
+};
+use pasta_curves::{group::ff::PrimeField, Fp, Fq};
+
+use crate::merkle::{bytes_to_field_elements, BYTES_PER_ELEMENT};
+use crate::field::MiMCField;
+use crate::mimc_feistel::sponge::MiMCSponge;
+
+// Initial capacity of the hasher sponge
+pub const HASHER_DOMAIN: u64 = u64::from_le_bytes(*b"MiMCHash");
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Segment {
+    Fields(u64),
+    Bytes(u64),
+}
+
+#[derive(Debug, Clone)]
+pub struct MiMCHasher<F: MiMCField> {
+    sponge: MiMCSponge<F>,
+    segment: Option<Segment>,
+    // Bytes not yet packed into a full element
+    pending: Vec<u8>,
+}
+
+pub type MiMCHasherPallas = MiMCHasher<Fp>;
+pub type MiMCHasherVesta = MiMCHasher<Fq>;
+
+impl<F: MiMCField> MiMCHasher<F> {
+    pub fn new() -> Self {
+        Self {
+            sponge: MiMCSponge::new(HASHER_DOMAIN),
+            segment: None,
+            pending: Vec::with_capacity(BYTES_PER_ELEMENT),
+        }
+    }
+
+    pub fn update(&mut self, elements: &[F]) {
+        if elements.is_empty() {
+            return;
+        }
+        if !matches!(self.segment, Some(Segment::Fields(_))) {
+            self.close_segment();
+            self.segment = Some(Segment::Fields(0));
+        }
+        for element in elements {
+            self.sponge.absorb(*element);
+        }
+        if let Some(Segment::Fields(count)) = &mut self.segment {
+            *count += elements.len() as u64;
+        }
+    }
+
+    pub fn update_bytes(&mut self, mut bytes: &[u8]) {
+        if bytes.is_empty() {
+            return;
+        }
+        if !matches!(self.segment, Some(Segment::Bytes(_))) {
+            self.close_segment();
+            self.segment = Some(Segment::Bytes(0));
+        }
+        if let Some(Segment::Bytes(count)) = &mut self.segment {
+            *count += bytes.len() as u64;
+        }
+        if !self.pending.is_empty() {
+            let n = bytes.len().min(BYTES_PER_ELEMENT - self.pending.len());
+            self.pending.extend_from_slice(&bytes[..n]);
+            bytes = &bytes[n..];
+            if self.pending.len() < BYTES_PER_ELEMENT {
+                return;
+            }
+            let pending = std::mem::take(&mut self.pending);
+            self.absorb_bytes(&pending);
+        }
+        let full = bytes.len() - bytes.len() % BYTES_PER_ELEMENT;
+        self.absorb_bytes(&bytes[..full]);
+        self.pending.extend_from_slice(&bytes[full..]);
+    }
+
+    pub fn finalize(mut self) -> F {
+        self.close_segment();
+        self.sponge.squeeze()
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::new();
+    }
+
+    fn absorb_bytes(&mut self, bytes: &[u8]) {
+        for element in bytes_to_field_elements::<F>(bytes) {
+            self.sponge.absorb(element);
+        }
+    }
+
+    fn close_segment(&mut self) {
+        let tag = match self.segment.take() {
+            None => return,
+            Some(Segment::Fields(count)) => 2 * count as u128,
+            Some(Segment::Bytes(count)) => {
+                let pending = std::mem::take(&mut self.pending);
+                self.absorb_bytes(&pending);
+                2 * count as u128 + 1
+            }
+        };
+        self.sponge.absorb(F::from_u128(tag));
+    }
+}
+
+impl<F: MiMCField> Default for MiMCHasher<F> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// The digest crate interface hashes bytes and outputs the little-endian
+// encoding of the resulting field element
+impl<F: MiMCField + PrimeField<Repr = [u8; 32]>> HashMarker for MiMCHasher<F> {}
+
+impl<F: MiMCField> OutputSizeUser for MiMCHasher<F> {
+    type OutputSize = U32;
+}
+
+impl<F: MiMCField> Update for MiMCHasher<F> {
+    fn update(&mut self, data: &[u8]) {
+        self.update_bytes(data);
+    }
+}
+
+impl<F: MiMCField + PrimeField<Repr = [u8; 32]>> FixedOutput for MiMCHasher<F> {
+    fn finalize_into(self, out: &mut Output<Self>) {
+        out.copy_from_slice(&MiMCHasher::finalize(self).to_repr());
+    }
+}
+
+impl<F: MiMCField> Reset for MiMCHasher<F> {
+    fn reset(&mut self) {
+        MiMCHasher::reset(self);
+    }
+}
+
+impl<F: MiMCField + PrimeField<Repr = [u8; 32]>> FixedOutputReset for MiMCHasher<F> {
+    fn finalize_into_reset(&mut self, out: &mut Output<Self>) {
+        let hasher = std::mem::take(self);
+        FixedOutput::finalize_into(hasher, out);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use digest::Digest;
+
+    #[test]
+    fn test_mimc_hasher_streaming() {
+        let elements: Vec<Fp> = (1..=5).map(Fp::from).collect();
+        let mut hasher = MiMCHasherPallas::new();
+        hasher.update(&elements);
+        let digest = hasher.finalize();
+
+        let mut chunked = MiMCHasherPallas::new();
+        chunked.update(&elements[..2]);
+        chunked.update(&[]);
+        chunked.update(&elements[2..]);
+        assert_eq!(chunked.finalize(), digest);
+
+        // Elements then their count, as in the segment encoding
+        let mut sponge = MiMCSponge::new(HASHER_DOMAIN);
+        for element in &elements {
+            sponge.absorb(*element);
+        }
+        sponge.absorb(Fp::from(10));
+        assert_eq!(sponge.squeeze(), digest);
+
+        let bytes: Vec<u8> = (0..100).collect();
+        let mut whole = MiMCHasherVesta::new();
+        whole.update_bytes(&bytes);
+        let whole = whole.finalize();
+        let mut split = MiMCHasherVesta::new();
+        for chunk in bytes.chunks(13) {
+            split.update_bytes(chunk);
+        }
+        assert_eq!(split.finalize(), whole);
+    }
+
+    #[test]
+    fn test_mimc_hasher_separates_inputs() {
+        let hash = |f: &dyn Fn(&mut MiMCHasherPallas)| {
+            let mut hasher = MiMCHasherPallas::new();
+            f(&mut hasher);
+            hasher.finalize()
+        };
+        let packed = bytes_to_field_elements::<Fp>(b"abc");
+        let digests = [
+            hash(&|_| {}),
+            hash(&|h| h.update(&[Fp::zero()])),
+            hash(&|h| h.update_bytes(b"abc")),
+            hash(&|h| h.update_bytes(b"abc\0")),
+            hash(&|h| h.update(&packed)),
+            hash(&|h| {
+                h.update_bytes(b"ab");
+                h.update(&[Fp::zero()]);
+                h.update_bytes(b"c");
+            }),
+        ];
+        for (i, a) in digests.iter().enumerate() {
+            for b in &digests[i + 1..] {
+                assert_ne!(a, b);
+            }
+        }
+
+        // Empty updates are no-ops and do not split a segment
+        assert_eq!(hash(&|h| h.update(&[])), digests[0]);
+        assert_eq!(hash(&|h| h.update_bytes(b"")), digests[0]);
+        assert_eq!(hash(&|h| Update::update(h, b"")), digests[0]);
+        let split = hash(&|h| {
+            h.update_bytes(b"ab");
+            h.update(&[]);
+            h.update_bytes(b"c");
+        });
+        assert_eq!(split, digests[2]);
+    }
+
+    #[test]
+    fn test_mimc_hasher_digest_traits() {
+        let mut hasher = MiMCHasherPallas::new();
+        hasher.update_bytes(b"hello world");
+        let expected = hasher.finalize().to_repr();
+
+        let output = Digest::finalize(<MiMCHasherPallas as Digest>::new().chain_update(b"hello ").chain_update(b"world"));
+        assert_eq!(output[..], expected[..]);
+        assert_eq!(<MiMCHasherPallas as Digest>::digest(b"hello world")[..], expected[..]);
+
+        let mut reused = <MiMCHasherPallas as Digest>::new();
+        Digest::update(&mut reused, b"something else");
+        Digest::reset(&mut reused);
+        Digest::update(&mut reused, b"hello world");
+        assert_eq!(reused.finalize_reset()[..], expected[..]);
+    }
+}
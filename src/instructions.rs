@@ -0,0 +1,249 @@
+// Instruction traits of the MiMC chips, in the style of the halo2_gadgets
+// instruction traits. Gadgets written against them work with any chip that
+// implements them, while configuring and constructing a chip stay on the chip
+// type. A block is one cell for MiMC5 and a (left, right) pair of cells
+// for MiMC5 Feistel. The hash chips have no key column and the cipher chips
+// no hash, so hashing and the cipher operations are separate traits.
+use halo2_proofs::{
+    arithmetic::FieldExt,
+    circuit::{AssignedCell, Chip, Layouter},
+    plonk::Error,
+};
+
+use crate::field::MiMCField;
+use crate::mimc::{
+    mimc_cipher::MiMC5CipherChip, mimc_fixed_key_cipher::MiMC5FixedKeyCipherChip, mimc_hash::MiMC5HashChip,
+};
+use crate::mimc_feistel::{mimc_feistel_cipher::MiMC5FeistelCipherChip, mimc_feistel_hash::MiMC5FeistelHashChip};
+
+// The (left, right) halves of a MiMC5 Feistel message, state or ciphertext
+pub type FeistelBlock<F> = (AssignedCell<F, F>, AssignedCell<F, F>);
+
+pub trait MiMCInstructions<F: FieldExt>: Chip<F> {
+    type Block;
+
+    fn hash(&self, layouter: impl Layouter<F>, message: &Self::Block) -> Result<Self::Block, Error>;
+}
+
+pub trait MiMCCipherInstructions<F: FieldExt>: Chip<F> {
+    type Block;
+    // `()` for chips whose key is fixed at construction
+    type Key;
+
+    fn encrypt(&self, layouter: impl Layouter<F>, message: &Self::Block, key: &Self::Key) -> Result<Self::Block, Error>;
+
+    // Returns the plaintext, constrained to encrypt to `ciphertext`
+    fn decrypt(&self, layouter: impl Layouter<F>, ciphertext: &Self::Block, key: &Self::Key) -> Result<Self::Block, Error>;
+}
+
+impl<F: MiMCField> MiMCInstructions<F> for MiMC5HashChip<F> {
+    type Block = AssignedCell<F, F>;
+
+    fn hash(&self, layouter: impl Layouter<F>, message: &Self::Block) -> Result<Self::Block, Error> {
+        self.hash_message(layouter, message)
+    }
+}
+
+impl<F: MiMCField> MiMCInstructions<F> for MiMC5FeistelHashChip<F> {
+    type Block = FeistelBlock<F>;
+
+    fn hash(&self, layouter: impl Layouter<F>, message: &Self::Block) -> Result<Self::Block, Error> {
+        self.hash_message(layouter, &message.0, &message.1)
+    }
+}
+
+impl<F: MiMCField> MiMCCipherInstructions<F> for MiMC5CipherChip<F> {
+    type Block = AssignedCell<F, F>;
+    type Key = AssignedCell<F, F>;
+
+    fn encrypt(&self, layouter: impl Layouter<F>, message: &Self::Block, key: &Self::Key) -> Result<Self::Block, Error> {
+        self.encrypt_message(layouter, message, key)
+    }
+
+    fn decrypt(&self, layouter: impl Layouter<F>, ciphertext: &Self::Block, key: &Self::Key) -> Result<Self::Block, Error> {
+        self.decrypt_message(layouter, ciphertext, key)
+    }
+}
+
+impl<F: MiMCField> MiMCCipherInstructions<F> for MiMC5FixedKeyCipherChip<F> {
+    type Block = AssignedCell<F, F>;
+    type Key = ();
+
+    fn encrypt(&self, layouter: impl Layouter<F>, message: &Self::Block, _: &()) -> Result<Self::Block, Error> {
+        self.encrypt_message(layouter, message)
+    }
+
+    fn decrypt(&self, layouter: impl Layouter<F>, ciphertext: &Self::Block, _: &()) -> Result<Self::Block, Error> {
+        self.decrypt_message(layouter, ciphertext)
+    }
+}
+
+impl<F: MiMCField> MiMCCipherInstructions<F> for MiMC5FeistelCipherChip<F> {
+    type Block = FeistelBlock<F>;
+    type Key = AssignedCell<F, F>;
+
+    fn encrypt(&self, layouter: impl Layouter<F>, message: &Self::Block, key: &Self::Key) -> Result<Self::Block, Error> {
+        self.encrypt_message(layouter, &message.0, &message.1, key)
+    }
+
+    fn decrypt(&self, layouter: impl Layouter<F>, ciphertext: &Self::Block, key: &Self::Key) -> Result<Self::Block, Error> {
+        self.decrypt_message(layouter, &ciphertext.0, &ciphertext.1, key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use halo2_proofs::{
+        circuit::{SimpleFloorPlanner, Value},
+        dev::MockProver,
+        plonk::{Advice, Circuit, Column, ConstraintSystem, Instance},
+    };
+    use pasta_curves::Fp;
+
+    use crate::mimc::mimc_cipher::{MiMC5CipherConfig, MiMC5CipherPallasChip};
+    use crate::mimc::primitives::mimc5_encrypt_pallas;
+    use crate::mimc_feistel::mimc_feistel_cipher::{MiMC5FeistelCipherConfig, MiMC5FeistelCipherPallasChip};
+    use crate::mimc_feistel::primitives::mimc5_feistel_encrypt_pallas;
+
+    #[derive(Debug, Clone)]
+    struct DecryptConfig<C> {
+        input: Column<Advice>,
+        instance: Column<Instance>,
+        chip_config: C,
+    }
+
+    // Loads a ciphertext and key, decrypts through the instructions and
+    // exposes the plaintext as the only public input
+    #[derive(Default)]
+    struct MiMC5DecryptCircuit {
+        ciphertext: Fp,
+        key: Fp,
+    }
+
+    impl Circuit<Fp> for MiMC5DecryptCircuit {
+        type Config = DecryptConfig<MiMC5CipherConfig>;
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self::default()
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+            let input = meta.advice_column();
+            meta.enable_equality(input);
+            let instance = meta.instance_column();
+            meta.enable_equality(instance);
+            let state = meta.advice_column();
+            let key_column = meta.advice_column();
+            let round_constants = meta.fixed_column();
+            DecryptConfig {
+                input,
+                instance,
+                chip_config: MiMC5CipherPallasChip::configure(meta, state, key_column, round_constants),
+            }
+        }
+
+        fn synthesize(&self, config: Self::Config, mut layouter: impl Layouter<Fp>) -> Result<(), Error> {
+            let chip = MiMC5CipherPallasChip::construct(config.chip_config);
+            let (ciphertext, key) = layouter.assign_region(
+                || "load ciphertext and key",
+                |mut region| {
+                    let ciphertext = region.assign_advice(|| "ciphertext", config.input, 0, || Value::known(self.ciphertext))?;
+                    let key = region.assign_advice(|| "key", config.input, 1, || Value::known(self.key))?;
+                    Ok((ciphertext, key))
+                },
+            )?;
+            let message = chip.decrypt(layouter.namespace(|| "decrypt"), &ciphertext, &key)?;
+            layouter.constrain_instance(message.cell(), config.instance, 0)
+        }
+    }
+
+    #[test]
+    fn test_mimc5_decrypt_instruction() {
+        let (message, key) = (Fp::from(3), Fp::from(5));
+        let mut ciphertext = message;
+        mimc5_encrypt_pallas(&mut ciphertext, key);
+
+        let circuit = MiMC5DecryptCircuit { ciphertext, key };
+        let prover = MockProver::run(9, &circuit, vec![vec![message]]).unwrap();
+        prover.assert_satisfied();
+
+        // A wrong plaintext cannot be exposed
+        let prover = MockProver::run(9, &circuit, vec![vec![message + Fp::one()]]).unwrap();
+        assert!(prover.verify().is_err());
+    }
+
+    // Encrypts a public message under a private key and decrypts it again,
+    // exposing the message, ciphertext and plaintext
+    #[derive(Default)]
+    struct MiMC5FeistelRoundTripCircuit {
+        message: (Fp, Fp),
+        key: Fp,
+    }
+
+    impl Circuit<Fp> for MiMC5FeistelRoundTripCircuit {
+        type Config = DecryptConfig<MiMC5FeistelCipherConfig>;
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self::default()
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+            let input = meta.advice_column();
+            meta.enable_equality(input);
+            let instance = meta.instance_column();
+            meta.enable_equality(instance);
+            let state_left = meta.advice_column();
+            let state_right = meta.advice_column();
+            let key_column = meta.advice_column();
+            let round_constants = meta.fixed_column();
+            DecryptConfig {
+                input,
+                instance,
+                chip_config: MiMC5FeistelCipherPallasChip::configure(
+                    meta,
+                    state_left,
+                    state_right,
+                    key_column,
+                    round_constants,
+                ),
+            }
+        }
+
+        fn synthesize(&self, config: Self::Config, mut layouter: impl Layouter<Fp>) -> Result<(), Error> {
+            let chip = MiMC5FeistelCipherPallasChip::construct(config.chip_config);
+            let (message, key) = layouter.assign_region(
+                || "load message and key",
+                |mut region| {
+                    let left = region.assign_advice(|| "left message", config.input, 0, || Value::known(self.message.0))?;
+                    let right = region.assign_advice(|| "right message", config.input, 1, || Value::known(self.message.1))?;
+                    let key = region.assign_advice(|| "key", config.input, 2, || Value::known(self.key))?;
+                    Ok(((left, right), key))
+                },
+            )?;
+            let ciphertext = chip.encrypt(layouter.namespace(|| "encrypt"), &message, &key)?;
+            let plaintext = chip.decrypt(layouter.namespace(|| "decrypt"), &ciphertext, &key)?;
+            for (row, cell) in [&message.0, &message.1, &ciphertext.0, &ciphertext.1, &plaintext.0, &plaintext.1]
+                .into_iter()
+                .enumerate()
+            {
+                layouter.constrain_instance(cell.cell(), config.instance, row)?;
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_mimc5_feistel_round_trip_instructions() {
+        let (message, key) = ((Fp::from(1), Fp::from(2)), Fp::from(7));
+        let (mut left, mut right) = message;
+        mimc5_feistel_encrypt_pallas(&mut left, &mut right, key);
+
+        let circuit = MiMC5FeistelRoundTripCircuit { message, key };
+        let public_inputs = vec![message.0, message.1, left, right, message.0, message.1];
+        let prover = MockProver::run(10, &circuit, vec![public_inputs]).unwrap();
+        prover.assert_satisfied();
+    }
+}
@@ -0,0 +1,328 @@
+// Text rendering of region layouts, for terminals and CI logs where the
+// `dev-graph` PNGs are not available.
+//
+// `region_layouts` synthesizes a circuit once with its floor planner and
+// returns a table per region with a line per row, relative to the start of
+// the region. Each cell holds the annotation it was assigned with and each
+// selector column holds 1 on the rows it is enabled on and 0 elsewhere, as in
+// the tables of `spec/mimc-halo2.tex`. Columns are headed by their labels,
+// when given, and by their names in `constraints.rs`.
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use halo2_proofs::{
+    arithmetic::Field,
+    circuit::Value,
+    plonk::{
+        Advice, Any, Assigned, Assignment, Circuit, Column, ConstraintSystem, Error, Fixed, FloorPlanner, Instance,
+        Selector,
+    },
+};
+
+use crate::assignment::{constant_columns, ColumnRef};
+use crate::circuits::{
+    mimc_cipher::MiMC5CipherPallasCircuit, mimc_feistel_cipher::MiMC5FeistelCipherPallasCircuit,
+    mimc_feistel_hash::MiMC5FeistelHashPallasCircuit, mimc_hash::MiMC5HashPallasCircuit,
+};
+use crate::debug_format::debug_index;
+use crate::cost::MiMC5FixedKeyCipherPallasCircuit;
+
+// Labels of the columns and selectors of a configuration, in display order
+#[derive(Debug, Clone, Default)]
+pub struct ColumnLabels {
+    columns: Vec<(ColumnRef, String)>,
+    selectors: Vec<(usize, String)>,
+}
+
+impl ColumnLabels {
+    pub fn column<C: Into<Column<Any>>>(mut self, column: C, label: &str) -> Self {
+        self.columns.push((ColumnRef::of(&column.into()), label.to_string()));
+        self
+    }
+
+    pub fn selector(mut self, selector: Selector, label: &str) -> Self {
+        self.selectors.push((debug_index(&selector), label.to_string()));
+        self
+    }
+
+    pub fn extend(mut self, other: ColumnLabels) -> Self {
+        self.columns.extend(other.columns);
+        self.selectors.extend(other.selectors);
+        self
+    }
+
+    pub(crate) fn column_label(&self, column: ColumnRef) -> Option<&str> {
+        self.columns.iter().find(|(labelled, _)| *labelled == column).map(|(_, label)| label.as_str())
+    }
+}
+
+// A configuration that can name its columns and selectors
+pub trait LabeledConfig {
+    fn labels(&self) -> ColumnLabels;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegionLayout {
+    pub name: String,
+    pub header: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+impl RegionLayout {
+    // The first `head` and last `tail` rows, with a row of "..." in between
+    pub fn elided(&self, head: usize, tail: usize) -> Self {
+        if self.rows.len() <= head + tail + 1 {
+            return self.clone();
+        }
+        let mut rows = self.rows[..head].to_vec();
+        rows.push(vec!["...".to_string(); self.header.len()]);
+        rows.extend_from_slice(&self.rows[self.rows.len() - tail..]);
+        Self {
+            name: self.name.clone(),
+            header: self.header.clone(),
+            rows,
+        }
+    }
+
+    // A Markdown table padded to line up in plain text
+    pub fn to_markdown(&self) -> String {
+        let widths: Vec<usize> = (0..self.header.len())
+            .map(|i| self.rows.iter().map(|row| row[i].len()).chain([self.header[i].len(), 3]).max().unwrap_or(3))
+            .collect();
+        let line = |cells: &[String]| {
+            let cells: Vec<String> = cells.iter().zip(&widths).map(|(cell, width)| format!("{:width$}", cell, width = width)).collect();
+            format!("| {} |\n", cells.join(" | "))
+        };
+        let mut table = String::new();
+        writeln!(table, "{}\n", self.name).unwrap();
+        table += &line(&self.header);
+        let rules: Vec<String> = widths.iter().map(|width| "-".repeat(*width)).collect();
+        table += &line(&rules);
+        for row in &self.rows {
+            table += &line(row);
+        }
+        table
+    }
+}
+
+pub fn region_layouts<F: Field, C: Circuit<F>>(circuit: &C, labels: &ColumnLabels) -> Result<Vec<RegionLayout>, Error> {
+    let mut cs = ConstraintSystem::default();
+    let config = C::configure(&mut cs);
+    let constants = constant_columns(&cs)?;
+    let mut recorder = RegionRecorder::default();
+    C::FloorPlanner::synthesize(&mut recorder, circuit, config, constants)?;
+    Ok(recorder.regions.iter().map(|region| region.layout(labels)).collect())
+}
+
+// Region layouts labelled by the circuit's own configuration
+pub fn labeled_region_layouts<F: Field, C: Circuit<F>>(circuit: &C) -> Result<Vec<RegionLayout>, Error>
+where
+    C::Config: LabeledConfig,
+{
+    // Columns are identified by index, so a configuration from a fresh
+    // constraint system labels the columns of any other
+    let labels = C::configure(&mut ConstraintSystem::default()).labels();
+    region_layouts(circuit, &labels)
+}
+
+// The chip region of each MiMC chip, configured as in `chip_costs`
+pub fn chip_layouts() -> Vec<(&'static str, RegionLayout)> {
+    let chip_region = |name, layouts: Result<Vec<RegionLayout>, Error>| {
+        let layouts = layouts.expect("MiMC circuits synthesize without witnesses");
+        let region = layouts.into_iter().max_by_key(|layout| layout.rows.len()).expect("MiMC circuits have a chip region");
+        (name, region)
+    };
+    vec![
+        chip_region("MiMC5 hash", labeled_region_layouts(&MiMC5HashPallasCircuit::default())),
+        chip_region("MiMC5 cipher", labeled_region_layouts(&MiMC5CipherPallasCircuit::default())),
+        chip_region("MiMC5 fixed key cipher", labeled_region_layouts(&MiMC5FixedKeyCipherPallasCircuit::default())),
+        chip_region("MiMC5 Feistel hash", labeled_region_layouts(&MiMC5FeistelHashPallasCircuit::default())),
+        chip_region("MiMC5 Feistel cipher", labeled_region_layouts(&MiMC5FeistelCipherPallasCircuit::default())),
+    ]
+}
+
+#[derive(Debug, Default)]
+struct RegionRecord {
+    name: String,
+    cells: BTreeMap<(ColumnRef, usize), String>,
+    selectors: BTreeMap<usize, BTreeSet<usize>>,
+}
+
+impl RegionRecord {
+    fn layout(&self, labels: &ColumnLabels) -> RegionLayout {
+        let rows = self.cells.keys().map(|(_, row)| *row).chain(self.selectors.values().flatten().copied());
+        let (first, last) = match (rows.clone().min(), rows.max()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => (0, 0),
+        };
+
+        // Labelled columns in label order, then the others by name
+        let used: BTreeSet<ColumnRef> = self.cells.keys().map(|(column, _)| *column).collect();
+        let mut columns: Vec<(ColumnRef, String)> = labels
+            .columns
+            .iter()
+            .filter(|(column, _)| used.contains(column))
+            .map(|(column, label)| (*column, format!("{} ({})", label, column)))
+            .collect();
+        columns.extend(
+            used.iter()
+                .filter(|column| !labels.columns.iter().any(|(labelled, _)| labelled == *column))
+                .map(|column| (*column, column.to_string())),
+        );
+        let mut selectors: Vec<(usize, String)> = labels
+            .selectors
+            .iter()
+            .filter(|(selector, _)| self.selectors.contains_key(selector))
+            .map(|(selector, label)| (*selector, format!("{} (S{})", label, selector)))
+            .collect();
+        selectors.extend(
+            self.selectors
+                .keys()
+                .filter(|selector| !labels.selectors.iter().any(|(labelled, _)| labelled == *selector))
+                .map(|selector| (*selector, format!("S{}", selector))),
+        );
+
+        let mut header = vec!["row".to_string()];
+        header.extend(columns.iter().map(|(_, label)| label.clone()));
+        header.extend(selectors.iter().map(|(_, label)| label.clone()));
+        let rows = (first..=last)
+            .map(|row| {
+                let mut line = vec![(row - first).to_string()];
+                line.extend(columns.iter().map(|(column, _)| self.cells.get(&(*column, row)).cloned().unwrap_or_default()));
+                line.extend(selectors.iter().map(|(selector, _)| {
+                    if self.selectors[selector].contains(&row) { "1" } else { "0" }.to_string()
+                }));
+                line
+            })
+            .collect();
+        RegionLayout {
+            name: self.name.clone(),
+            header,
+            rows,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct RegionRecorder {
+    region: Option<RegionRecord>,
+    regions: Vec<RegionRecord>,
+}
+
+impl RegionRecorder {
+    fn record(&mut self, column: ColumnRef, row: usize, annotation: String) {
+        if let Some(region) = &mut self.region {
+            region.cells.insert((column, row), annotation.trim().to_string());
+        }
+    }
+}
+
+impl<F: Field> Assignment<F> for RegionRecorder {
+    fn enter_region<NR, N>(&mut self, name: N)
+    where
+        NR: Into<String>,
+        N: FnOnce() -> NR,
+    {
+        self.region = Some(RegionRecord {
+            name: name().into(),
+            ..Default::default()
+        });
+    }
+
+    fn exit_region(&mut self) {
+        if let Some(region) = self.region.take() {
+            self.regions.push(region);
+        }
+    }
+
+    fn enable_selector<A, AR>(&mut self, _: A, selector: &Selector, row: usize) -> Result<(), Error>
+    where
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        if let Some(region) = &mut self.region {
+            region.selectors.entry(debug_index(selector)).or_default().insert(row);
+        }
+        Ok(())
+    }
+
+    fn query_instance(&self, _: Column<Instance>, _: usize) -> Result<Value<F>, Error> {
+        Ok(Value::unknown())
+    }
+
+    fn assign_advice<V, VR, A, AR>(&mut self, annotation: A, column: Column<Advice>, row: usize, _: V) -> Result<(), Error>
+    where
+        V: FnOnce() -> Value<VR>,
+        VR: Into<Assigned<F>>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        self.record(ColumnRef::of(&column.into()), row, annotation().into());
+        Ok(())
+    }
+
+    fn assign_fixed<V, VR, A, AR>(&mut self, annotation: A, column: Column<Fixed>, row: usize, _: V) -> Result<(), Error>
+    where
+        V: FnOnce() -> Value<VR>,
+        VR: Into<Assigned<F>>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        self.record(ColumnRef::of(&column.into()), row, annotation().into());
+        Ok(())
+    }
+
+    fn copy(&mut self, _: Column<Any>, _: usize, _: Column<Any>, _: usize) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn fill_from_row(&mut self, _: Column<Fixed>, _: usize, _: Value<Assigned<F>>) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn push_namespace<NR, N>(&mut self, _: N)
+    where
+        NR: Into<String>,
+        N: FnOnce() -> NR,
+    {
+    }
+
+    fn pop_namespace(&mut self, _: Option<String>) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chip_layouts() {
+        let layouts = chip_layouts();
+        let rows: Vec<_> = layouts.iter().map(|(name, layout)| (*name, layout.rows.len())).collect();
+        assert_eq!(
+            rows,
+            vec![
+                ("MiMC5 hash", 111),
+                ("MiMC5 cipher", 112),
+                ("MiMC5 fixed key cipher", 112),
+                ("MiMC5 Feistel hash", 221),
+                ("MiMC5 Feistel cipher", 221),
+            ]
+        );
+
+        let (_, cipher) = &layouts[1];
+        assert_eq!(
+            cipher.header,
+            vec!["row", "state (A1)", "key (A2)", "round constants (F0)", "s_in_rounds (S0)", "s_post_rounds (S1)"]
+        );
+        assert_eq!(cipher.rows[1], vec!["1", "round 1 output", "key in row 1", "round constant 1", "1", "0"]);
+        assert_eq!(cipher.rows[111], vec!["111", "final state", "", "", "0", "1"]);
+
+        let table = cipher.elided(2, 1).to_markdown();
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines.len(), 8);
+        assert_eq!(lines[0], "MiMC5 table");
+        assert!(lines[2].starts_with("| row | state (A1)"));
+        assert!(lines[6].starts_with("| ... | ..."));
+        assert!(lines.iter().skip(2).all(|line| line.len() == lines[2].len()));
+    }
+}
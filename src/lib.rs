@@ -1,2 +1,18 @@
 pub mod mimc;
-pub mod mimc_feistel;
\ No newline at end of file
+pub mod mimc_feistel;
+pub mod circuits;
+pub mod prover;
+pub mod artifacts;
+pub mod field_io;
+pub mod merkle;
+pub mod transcript;
+pub mod rng;
+pub mod hasher;
+pub mod cost;
+pub mod constraints;
+pub mod assignment;
+pub mod layout;
+pub mod diagnostics;
+pub mod debug_format;
+pub mod instructions;
+pub mod field;
\ No newline at end of file
@@ -0,0 +1,116 @@
+// Merkle roots over byte strings using the MiMC5 Feistel sponge.
+//
+// A leaf is a sponge in the `MERKLE_LEAF_DOMAIN` that absorbs the byte length
+// and then the bytes packed into 31-byte little-endian field elements, which
+// always fit below the Pallas and Vesta moduli. An interior node is a sponge
+// in the `MERKLE_NODE_DOMAIN` that absorbs its two children, so a node can
+// never be passed off as a leaf or the other way round. The last node of a
+// level with an odd number of nodes is carried up to the next level as is.
+use halo2_proofs::arithmetic::FieldExt;
+use pasta_curves::{Fp, Fq};
+
+use crate::field::MiMCField;
+use crate::mimc_feistel::sponge::MiMCSponge;
+
+pub const MERKLE_LEAF_DOMAIN: u64 = u64::from_le_bytes(*b"MiMCLeaf");
+pub const MERKLE_NODE_DOMAIN: u64 = u64::from_le_bytes(*b"MiMCNode");
+
+// Number of message bytes packed into one field element
+pub const BYTES_PER_ELEMENT: usize = 31;
+
+pub fn bytes_to_field_elements<F: FieldExt>(bytes: &[u8]) -> Vec<F> {
+    bytes
+        .chunks(BYTES_PER_ELEMENT)
+        .map(|chunk| {
+            let mut wide = [0u8; 64];
+            wide[..chunk.len()].copy_from_slice(chunk);
+            F::from_bytes_wide(&wide)
+        })
+        .collect()
+}
+
+pub fn merkle_leaf<F: MiMCField>(bytes: &[u8]) -> F {
+    let mut sponge = MiMCSponge::new(MERKLE_LEAF_DOMAIN);
+    sponge.absorb(F::from(bytes.len() as u64));
+    for element in bytes_to_field_elements::<F>(bytes) {
+        sponge.absorb(element);
+    }
+    sponge.squeeze()
+}
+
+pub fn merkle_node<F: MiMCField>(left: F, right: F) -> F {
+    let mut sponge = MiMCSponge::new(MERKLE_NODE_DOMAIN);
+    sponge.absorb(left);
+    sponge.absorb(right);
+    sponge.squeeze()
+}
+
+// Root of the tree whose leaves are the given byte strings, or None if there
+// are no leaves
+pub fn merkle_root<F: MiMCField, B: AsRef<[u8]>>(leaves: &[B]) -> Option<F> {
+    let mut level: Vec<F> = leaves.iter().map(|leaf| merkle_leaf(leaf.as_ref())).collect();
+    if level.is_empty() {
+        return None;
+    }
+    while level.len() > 1 {
+        level = level
+            .chunks(2)
+            .map(|pair| match pair {
+                [left, right] => merkle_node(*left, *right),
+                [last] => *last,
+                _ => unreachable!(),
+            })
+            .collect();
+    }
+    Some(level[0])
+}
+
+pub fn merkle_root_pallas<B: AsRef<[u8]>>(leaves: &[B]) -> Option<Fp> {
+    merkle_root(leaves)
+}
+
+pub fn merkle_root_vesta<B: AsRef<[u8]>>(leaves: &[B]) -> Option<Fq> {
+    merkle_root(leaves)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merkle_root_shape() {
+        assert_eq!(merkle_root_pallas::<&[u8]>(&[]), None);
+
+        let single = merkle_root_pallas(&[b"a"]).unwrap();
+        assert_eq!(single, merkle_leaf(b"a"));
+
+        // The third leaf has no sibling and is carried up unchanged
+        let leaves = [b"a", b"b", b"c"];
+        let hashes: Vec<Fp> = leaves.iter().map(|leaf| merkle_leaf(*leaf)).collect();
+        let expected = merkle_node(merkle_node(hashes[0], hashes[1]), hashes[2]);
+        assert_eq!(merkle_root_pallas(&leaves), Some(expected));
+
+        // Not padded with a zero node
+        let padded = merkle_node(merkle_node(hashes[0], hashes[1]), merkle_node(hashes[2], Fp::zero()));
+        assert_ne!(merkle_root_pallas(&leaves), Some(padded));
+    }
+
+    #[test]
+    fn test_merkle_domain_separation() {
+        // Absorbing the children in the leaf domain does not give the node
+        let (left, right) = (Fp::from(1), Fp::from(2));
+        let mut sponge = MiMCSponge::new(MERKLE_LEAF_DOMAIN);
+        sponge.absorb(left);
+        sponge.absorb(right);
+        assert_ne!(merkle_node(left, right), sponge.squeeze());
+    }
+
+    #[test]
+    fn test_merkle_leaf_encoding() {
+        // Trailing zero bytes and chunk boundaries change the leaf
+        let leaf = |bytes: &[u8]| merkle_leaf::<Fq>(bytes);
+        assert_ne!(leaf(b"ab"), leaf(b"ab\0"));
+        assert_ne!(leaf(&[1u8; 31]), leaf(&[1u8; 32]));
+        assert_eq!(bytes_to_field_elements::<Fq>(&[7u8; 32]).len(), 2);
+    }
+}
@@ -1,16 +1,18 @@
 use halo2_proofs::{
-    arithmetic::FieldExt,
     plonk::{
-        Column, Advice, Fixed, Selector, ConstraintSystem, Expression, Error,
+        Column, Advice, Fixed, Instance, Selector, ConstraintSystem, Expression, Error,
     },
     poly::Rotation,
     circuit::{
-        Layouter, AssignedCell, Value,
+        Chip, Layouter, AssignedCell, Value,
     },
 };
 use pasta_curves::{Fp, Fq};
+use std::slice;
 
-use super::round_constants::{MIMC_PALLAS_ROUND_CONSTANTS, MIMC_VESTA_ROUND_CONSTANTS};
+use super::primitives::mimc5_encrypt_trace;
+use crate::field::MiMCField;
+use crate::layout::{ColumnLabels, LabeledConfig};
 
 
 #[allow(unused_variables, dead_code)]
@@ -23,14 +25,70 @@ pub struct MiMC5CipherConfig {
     s_post_rounds: Selector,
 }
 
-pub trait MiMC5CipherChip<F: FieldExt> {
-    fn construct(config: MiMC5CipherConfig) -> Self;
+impl LabeledConfig for MiMC5CipherConfig {
+    fn labels(&self) -> ColumnLabels {
+        ColumnLabels::default()
+            .column(self.state, "state")
+            .column(self.key_column, "key")
+            .column(self.round_constants, "round constants")
+            .selector(self.s_in_rounds, "s_in_rounds")
+            .selector(self.s_post_rounds, "s_post_rounds")
+    }
+}
+
+impl MiMC5CipherConfig {
+    pub fn state(&self) -> Column<Advice> {
+        self.state
+    }
+
+    pub fn key_column(&self) -> Column<Advice> {
+        self.key_column
+    }
+
+    pub fn round_constants(&self) -> Column<Fixed> {
+        self.round_constants
+    }
+
+    pub fn s_in_rounds(&self) -> Selector {
+        self.s_in_rounds
+    }
+
+    pub fn s_post_rounds(&self) -> Selector {
+        self.s_post_rounds
+    }
+}
+
+pub struct MiMC5CipherChip<F: MiMCField> {
+    config: MiMC5CipherConfig,
+    // Round constants loaded once at construction and shared by every invocation
+    round_constants: Vec<F>,
+}
 
-    fn get_round_constants() -> Vec<F>;
+pub type MiMC5CipherPallasChip = MiMC5CipherChip<Fp>;
+pub type MiMC5CipherVestaChip = MiMC5CipherChip<Fq>;
 
-    fn get_config(&self) -> &MiMC5CipherConfig;
+impl<F: MiMCField> Chip<F> for MiMC5CipherChip<F> {
+    type Config = MiMC5CipherConfig;
+    type Loaded = Vec<F>;
 
-    fn configure(
+    fn config(&self) -> &Self::Config {
+        &self.config
+    }
+
+    fn loaded(&self) -> &Self::Loaded {
+        &self.round_constants
+    }
+}
+
+impl<F: MiMCField> MiMC5CipherChip<F> {
+    pub fn construct(config: MiMC5CipherConfig) -> Self {
+        Self {
+            config,
+            round_constants: F::MIMC5_ROUND_CONSTANTS.to_vec(),
+        }
+    }
+
+    pub fn configure(
         meta: &mut ConstraintSystem<F>,
         state: Column<Advice>,
         key_column: Column<Advice>,
@@ -41,7 +99,6 @@ pub trait MiMC5CipherChip<F: FieldExt> {
 
         meta.enable_equality(state);
         meta.enable_equality(key_column);
-        meta.enable_constant(round_constants);
 
         //  state                    | key_column   | round_constants   | selector
         //  x0 = message             |  key         |     c0            | 
@@ -61,7 +118,7 @@ pub trait MiMC5CipherChip<F: FieldExt> {
             };
             let prev_state = meta.query_advice(state, Rotation::prev());
             let key = meta.query_advice(key_column, Rotation::cur());
-            let prev_key = meta.query_advice(key_column, Rotation::cur());
+            let prev_key = meta.query_advice(key_column, Rotation::prev());
             let rc = meta.query_fixed(round_constants, Rotation::prev());
             let current_state = meta.query_advice(state, Rotation::cur());
             vec![
@@ -87,116 +144,207 @@ pub trait MiMC5CipherChip<F: FieldExt> {
         }
     }
 
-    fn encrypt_message(
+    pub fn encrypt_message(
         &self,
-        mut layouter: impl Layouter<F>,
+        layouter: impl Layouter<F>,
         message: &AssignedCell<F, F>,
         key: &AssignedCell<F, F>,
     ) -> Result<AssignedCell<F,F>, Error> {
-        let config = self.get_config();
+        let mut ciphertexts = self.encrypt_messages(layouter, slice::from_ref(message), key)?;
+        Ok(ciphertexts.remove(0))
+    }
+
+    // Encrypts the messages under one key in one region, each invocation in
+    // the rows below the previous one. Every row of the region is assigned
+    // once, the round constants of each invocation included.
+    pub fn encrypt_messages(
+        &self,
+        mut layouter: impl Layouter<F>,
+        messages: &[AssignedCell<F, F>],
+        key: &AssignedCell<F, F>,
+    ) -> Result<Vec<AssignedCell<F,F>>, Error> {
+        let config = self.config();
+
+        let round_constant_values = self.loaded();
+        let rounds = round_constant_values.len();
 
-        let round_constant_values = Self::get_round_constants();
+        let traces: Vec<_> = messages
+            .iter()
+            .map(|message| message.value().zip(key.value()).map(|(message, key)| {
+                mimc5_encrypt_trace(*message, *key, round_constant_values)
+            }))
+            .collect();
 
         layouter.assign_region(
             || "MiMC5 table",
             |mut region| {
+                messages.iter().zip(traces.iter()).enumerate().map(|(invocation, (message, trace))| {
+                    let offset = invocation * (rounds + 2);
 
-                region.assign_advice(
-                    || "message to be hashed",
+                    message.copy_advice(
+                        || "message to be hashed",
+                        &mut region,
+                        config.state,
+                        offset,
+                    )?;
+
+                    key.copy_advice(
+                        || "key in row 0",
+                        &mut region,
+                        config.key_column,
+                        offset,
+                    )?;
+
+                    for i in 1..=rounds {
+                        config.s_in_rounds.enable(&mut region, offset+i)?;
+                        region.assign_fixed(
+                            || format!("round constant {:?}", i-1),
+                            config.round_constants,
+                            offset+i-1,
+                            || Value::known(round_constant_values[i-1]) // i starts at 1
+                        )?;
+
+                        key.copy_advice(
+                            || format!("key in row {:?} ", i),
+                            &mut region,
+                            config.key_column,
+                            offset+i,
+                        )?;
+
+                        region.assign_advice(
+                            || format!("round {:?} output", i),
+                            config.state,
+                            offset+i,
+                            || trace.as_ref().map(|trace| trace[i])
+                        )?;
+                    }
+
+                    config.s_post_rounds.enable(&mut region, offset+rounds+1)?;
+                    region.assign_advice(
+                        || "final state",
+                        config.state,
+                        offset+rounds+1,
+                        || trace.as_ref().zip(key.value()).map(|(trace, key)| trace[rounds] + key)
+                    )
+                }).collect()
+            }
+        )
+    }
+
+    // Encrypts under a key that is a public input. The key is loaded from
+    // `key_instance` at `key_row` and copied into every row of the key column.
+    // The instance column must have equality enabled.
+    pub fn encrypt_message_with_instance_key(
+        &self,
+        mut layouter: impl Layouter<F>,
+        message: &AssignedCell<F, F>,
+        key_instance: Column<Instance>,
+        key_row: usize,
+    ) -> Result<AssignedCell<F,F>, Error> {
+        let config = self.config();
+
+        let round_constant_values = self.loaded();
+
+        layouter.assign_region(
+            || "MiMC5 table with instance key",
+            |mut region| {
+
+                message.copy_advice(
+                    || "message to be encrypted",
+                    &mut region,
                     config.state,
                     0,
-                    || message.value().copied(),
                 )?;
 
-                region.assign_advice(
-                    || format!("key in row 0"),
+                let key_cell =
+                region.assign_advice_from_instance(
+                    || "key in row 0",
+                    key_instance,
+                    key_row,
                     config.key_column,
                     0,
-                    || key.value().copied(),
                 )?;
 
-
-                let pow_5 = |v: Value<F>| { v*v*v*v*v };
-
-                let mut current_state = message.value().copied();
+                // The key is only known once read from the instance column,
+                // which the floor planner's shape pass does not do, so this
+                // runs the rounds once per invocation
+                let trace = message.value().zip(key_cell.value()).map(|(message, key)| {
+                    mimc5_encrypt_trace(*message, *key, round_constant_values)
+                });
 
                 for i in 1..=round_constant_values.len() {
                     config.s_in_rounds.enable(&mut region, i)?;
                     region.assign_fixed(
-                        || format!("round constant {:?}", i),
+                        || format!("round constant {:?}", i-1),
                         config.round_constants,
                         i-1,
                         || Value::known(round_constant_values[i-1]) // i starts at 1
                     )?;
 
-                    region.assign_advice(
+                    key_cell.copy_advice(
                         || format!("key in row {:?} ", i),
+                        &mut region,
                         config.key_column,
                         i,
-                        || key.value().copied()
                     )?;
 
-                    current_state = pow_5( current_state + key.value().copied() + Value::known(round_constant_values[i-1]));
                     region.assign_advice(
                         || format!("round {:?} output", i),
                         config.state,
                         i,
-                        || current_state
+                        || trace.as_ref().map(|trace| trace[i])
                     )?;
                 }
 
-                current_state = current_state + key.value().copied();
-
+                config.s_post_rounds.enable(&mut region, round_constant_values.len()+1)?;
                 let ciphertext =
                 region.assign_advice(
                     || "final state",
                     config.state,
                     round_constant_values.len()+1,
-                    || current_state
+                    || trace.as_ref().zip(key_cell.value()).map(|(trace, key)| trace[round_constant_values.len()] + key)
                 )?;
                 Ok(ciphertext)
             }
         )
     }
-}
 
-pub struct MiMC5CipherPallasChip {
-    config: MiMC5CipherConfig
-}
-
-impl MiMC5CipherChip<Fp> for MiMC5CipherPallasChip {
-    fn construct(config: MiMC5CipherConfig) -> Self {
-        Self {
-            config,
-        }
-    }
-
-    fn get_config(&self) -> &MiMC5CipherConfig {
-        &self.config
-    }
-
-    fn get_round_constants() -> Vec<Fp> {
-        MIMC_PALLAS_ROUND_CONSTANTS.to_vec()
-    }
-}
-
-pub struct MiMC5CipherVestaChip {
-    config: MiMC5CipherConfig
-}
-
-impl MiMC5CipherChip<Fq> for MiMC5CipherVestaChip {
-    fn construct(config: MiMC5CipherConfig) -> Self {
-        Self {
-            config,
-        }
-    }
+    // The rounds cannot be inverted by low-degree gates, so decryption
+    // witnesses the plaintext and constrains its encryption to `ciphertext`.
+    // The ciphertext's column must have equality enabled.
+    pub fn decrypt_message(
+        &self,
+        mut layouter: impl Layouter<F>,
+        ciphertext: &AssignedCell<F, F>,
+        key: &AssignedCell<F, F>,
+    ) -> Result<AssignedCell<F,F>, Error> {
+        let config = self.config();
 
-    fn get_config(&self) -> &MiMC5CipherConfig {
-        &self.config
-    }
+        let message = layouter.assign_region(
+            || "MiMC5 decryption",
+            |mut region| {
+                region.assign_advice(
+                    || "plaintext",
+                    config.state,
+                    0,
+                    || ciphertext.value().zip(key.value()).map(|(ciphertext, key)| {
+                        let mut state = *ciphertext;
+                        F::decrypt(&mut state, *key);
+                        state
+                    })
+                )
+            }
+        )?;
 
-    fn get_round_constants() -> Vec<Fq> {
-        MIMC_VESTA_ROUND_CONSTANTS.to_vec()
+        let reencrypted = self.encrypt_message(layouter.namespace(|| "re-encryption"), &message, key)?;
+        layouter.assign_region(
+            || "constrain ciphertext",
+            |mut region| {
+                let ciphertext = ciphertext.copy_advice(|| "ciphertext", &mut region, config.state, 0)?;
+                region.constrain_equal(reencrypted.cell(), ciphertext.cell())
+            }
+        )?;
+        Ok(message)
     }
 }
 
@@ -206,6 +354,7 @@ mod tests {
     use crate::mimc::primitives::{mimc5_encrypt_pallas, mimc5_encrypt_vesta};
 
     use super::*;
+    use crate::cost::chip_costs;
     use halo2_proofs::{dev::MockProver, pasta::Fp, plonk::Circuit, circuit::SimpleFloorPlanner};
 
     #[derive(Debug, Clone)]
@@ -298,7 +447,7 @@ mod tests {
  
     #[test]
     fn test_mimc5_pallas_cipher() {
-        let k = 7;
+        let k = chip_costs().mimc5_cipher.min_k(1);
 
         let msg = Fp::from(0);
         let key = Fp::from(1);
@@ -316,6 +465,93 @@ mod tests {
 
     }
 
+    // Encrypts two messages under one key in one region
+    #[derive(Default)]
+    struct MiMC5BatchCipherPallasCircuit {
+        pub messages: [Fp; 2],
+        pub key: Fp,
+        pub ciphertexts: [Fp; 2],
+    }
+
+    impl Circuit<Fp> for MiMC5BatchCipherPallasCircuit {
+        type Config = MiMC5CipherCircuitConfig;
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self::default()
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+            MiMC5CipherPallasCircuit::configure(meta)
+        }
+
+        fn synthesize(
+            &self,
+            config: Self::Config,
+            mut layouter: impl Layouter<Fp>,
+        ) -> Result<(), Error> {
+            let chip = MiMC5CipherPallasChip::construct(config.mimc_config);
+
+            let (messages, key) = layouter.assign_region(
+                || "load messages and key",
+                |mut region| {
+                    let messages = self.messages.iter().enumerate().map(|(row, message)| {
+                        region.assign_advice(|| "load input message", config.input, row, || Value::known(*message))
+                    }).collect::<Result<Vec<_>, Error>>()?;
+                    let key = region.assign_advice(|| "load encryption key", config.input, 2, || Value::known(self.key))?;
+                    Ok((messages, key))
+                }
+            )?;
+
+            let ciphertexts = chip.encrypt_messages(
+                layouter.namespace(|| "both encryptions"),
+                &messages,
+                &key,
+            )?;
+
+            layouter.assign_region(
+                || "constrain outputs",
+                |mut region| {
+                    for (row, (ciphertext, expected)) in ciphertexts.iter().zip(self.ciphertexts).enumerate() {
+                        let expected_output = region.assign_advice(
+                            || "load output",
+                            config.input,
+                            row,
+                            || Value::known(expected),
+                        )?;
+                        region.constrain_equal(ciphertext.cell(), expected_output.cell())?;
+                    }
+                    Ok(())
+                }
+            )?;
+
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_mimc5_pallas_cipher_batch() {
+        let k = chip_costs().mimc5_cipher.min_k(2);
+
+        let (messages, key) = ([Fp::from(3), Fp::from(4)], Fp::from(5));
+        let mut ciphertexts = messages;
+        for output in ciphertexts.iter_mut() {
+            mimc5_encrypt_pallas(output, key);
+        }
+
+        let circuit = MiMC5BatchCipherPallasCircuit { messages, key, ciphertexts };
+        let prover = MockProver::run(k, &circuit, vec![]).unwrap();
+        prover.assert_satisfied();
+
+        let circuit = MiMC5BatchCipherPallasCircuit {
+            messages,
+            key,
+            ciphertexts: [ciphertexts[0], ciphertexts[1] + Fp::one()],
+        };
+        let prover = MockProver::run(k, &circuit, vec![]).unwrap();
+        assert!(prover.verify().is_err());
+    }
+
     #[derive(Default)]
     struct MiMC5CipherVestaCircuit {
         pub message: Fq,
@@ -400,7 +636,7 @@ mod tests {
      
     #[test]
     fn test_mimc5_vesta_cipher() {
-        let k = 7;
+        let k = chip_costs().mimc5_cipher.min_k(1);
 
         let msg = Fq::from(0);
         let key = Fq::from(0);
@@ -418,12 +654,133 @@ mod tests {
 
     }
 
+    #[derive(Debug, Clone)]
+    struct MiMC5InstanceKeyCipherCircuitConfig {
+        input : Column<Advice>,
+        key_instance: Column<Instance>,
+        mimc_config: MiMC5CipherConfig,
+    }
+
+    #[derive(Default)]
+    struct MiMC5InstanceKeyCipherCircuit<F: MiMCField> {
+        pub message: F,
+        pub ciphertext: F,
+    }
+
+    impl<F: MiMCField> Circuit<F> for MiMC5InstanceKeyCipherCircuit<F> {
+        type Config = MiMC5InstanceKeyCipherCircuitConfig;
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self::default()
+        }
+
+        fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+            let circuit_input = meta.advice_column();
+            meta.enable_equality(circuit_input);
+            let key_instance = meta.instance_column();
+            meta.enable_equality(key_instance);
+            let state = meta.advice_column();
+            let key_column = meta.advice_column();
+            let round_constants = meta.fixed_column();
+            Self::Config {
+                input: circuit_input,
+                key_instance,
+                mimc_config: MiMC5CipherChip::<F>::configure(meta, state, key_column, round_constants)
+            }
+        }
+
+        fn synthesize(
+            &self,
+            config: Self::Config,
+            mut layouter: impl Layouter<F>,
+        ) -> Result<(), Error> {
+            let chip = MiMC5CipherChip::<F>::construct(config.mimc_config);
+
+            let message = layouter.assign_region(
+                || "load message",
+                |mut region| {
+                    region.assign_advice(
+                        || "load input message",
+                        config.input,
+                        0,
+                        || Value::known(self.message)
+                    )
+                }
+            )?;
+
+            let ciphertext = chip.encrypt_message_with_instance_key(
+                layouter.namespace(|| "entire table"),
+                &message,
+                config.key_instance,
+                0,
+            )?;
+
+            layouter.assign_region(
+                || "constrain output",
+                |mut region| {
+                    let expected_output = region.assign_advice(
+                        || "load output",
+                        config.input,
+                        0,
+                        || Value::known(self.ciphertext),
+                    )?;
+                    region.constrain_equal(ciphertext.cell(), expected_output.cell())
+                }
+            )?;
+
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_mimc5_pallas_cipher_with_instance_key() {
+        let k = chip_costs().mimc5_cipher.min_k(1);
+
+        let msg = Fp::from(5);
+        let key = Fp::from(9);
+        let mut output = msg;
+        mimc5_encrypt_pallas(&mut output, key);
+
+        let circuit = MiMC5InstanceKeyCipherCircuit {
+            message: msg,
+            ciphertext: output,
+        };
+
+        let prover = MockProver::run(k, &circuit, vec![vec![key]]).unwrap();
+        prover.assert_satisfied();
+
+        let prover = MockProver::run(k, &circuit, vec![vec![key + Fp::one()]]).unwrap();
+        assert!(prover.verify().is_err());
+    }
+
+    #[test]
+    fn test_mimc5_vesta_cipher_with_instance_key() {
+        let k = chip_costs().mimc5_cipher.min_k(1);
+
+        let msg = Fq::from(5);
+        let key = Fq::from(9);
+        let mut output = msg;
+        mimc5_encrypt_vesta(&mut output, key);
+
+        let circuit = MiMC5InstanceKeyCipherCircuit {
+            message: msg,
+            ciphertext: output,
+        };
+
+        let prover = MockProver::run(k, &circuit, vec![vec![key]]).unwrap();
+        prover.assert_satisfied();
+
+        let prover = MockProver::run(k, &circuit, vec![vec![key + Fq::one()]]).unwrap();
+        assert!(prover.verify().is_err());
+    }
+
 
     #[cfg(feature = "dev-graph")]
     #[test]
     fn plot_mimc5_pallas_cipher() {
         use plotters::prelude::*;
-        let k = 7;
+        let k = chip_costs().mimc5_cipher.min_k(1);
         let root = BitMapBackend::new("mimc5-pallas-cipher-layout.png", (1024, 3096)).into_drawing_area();
         root.fill(&WHITE).unwrap();
         let root = root.titled("MiMC Cipher Layout", ("sans-serif", 60)).unwrap();
@@ -0,0 +1,496 @@
+use halo2_proofs::{
+    plonk::{
+        Column, Advice, Fixed, Selector, ConstraintSystem, Expression, Error,
+    },
+    poly::Rotation,
+    circuit::{
+        Chip, Layouter, AssignedCell, Value,
+    },
+};
+use pasta_curves::{Fp, Fq};
+use std::slice;
+
+use super::primitives::{mimc5_hash_trace, mimc5_round_keys};
+use crate::field::MiMCField;
+use crate::layout::{ColumnLabels, LabeledConfig};
+
+
+#[allow(unused_variables, dead_code)]
+#[derive(Debug, Clone)]
+pub struct MiMC5FixedKeyCipherConfig {
+    state: Column<Advice>,
+    round_keys: Column<Fixed>,
+    s_in_rounds: Selector,
+    s_post_rounds: Selector,
+}
+
+impl LabeledConfig for MiMC5FixedKeyCipherConfig {
+    fn labels(&self) -> ColumnLabels {
+        ColumnLabels::default()
+            .column(self.state, "state")
+            .column(self.round_keys, "round keys")
+            .selector(self.s_in_rounds, "s_in_rounds")
+            .selector(self.s_post_rounds, "s_post_rounds")
+    }
+}
+
+impl MiMC5FixedKeyCipherConfig {
+    pub fn state(&self) -> Column<Advice> {
+        self.state
+    }
+
+    pub fn round_keys(&self) -> Column<Fixed> {
+        self.round_keys
+    }
+
+    pub fn s_in_rounds(&self) -> Selector {
+        self.s_in_rounds
+    }
+
+    pub fn s_post_rounds(&self) -> Selector {
+        self.s_post_rounds
+    }
+}
+
+// MiMC5 encryption under a key that is a circuit constant. The key is folded
+// into the round constants, so there is no key column and no key equality constraints.
+pub struct MiMC5FixedKeyCipherChip<F: MiMCField> {
+    config: MiMC5FixedKeyCipherConfig,
+    key: F,
+    // Round keys k + c_i precomputed once at construction
+    round_keys: Vec<F>,
+}
+
+pub type MiMC5FixedKeyCipherPallasChip = MiMC5FixedKeyCipherChip<Fp>;
+pub type MiMC5FixedKeyCipherVestaChip = MiMC5FixedKeyCipherChip<Fq>;
+
+impl<F: MiMCField> Chip<F> for MiMC5FixedKeyCipherChip<F> {
+    type Config = MiMC5FixedKeyCipherConfig;
+    type Loaded = Vec<F>;
+
+    fn config(&self) -> &Self::Config {
+        &self.config
+    }
+
+    fn loaded(&self) -> &Self::Loaded {
+        &self.round_keys
+    }
+}
+
+impl<F: MiMCField> MiMC5FixedKeyCipherChip<F> {
+    pub fn construct(config: MiMC5FixedKeyCipherConfig, key: F) -> Self {
+        Self {
+            config,
+            key,
+            round_keys: mimc5_round_keys(key, F::MIMC5_ROUND_CONSTANTS),
+        }
+    }
+
+    pub fn get_key(&self) -> F {
+        self.key
+    }
+
+    pub fn configure(
+        meta: &mut ConstraintSystem<F>,
+        state: Column<Advice>,
+        round_keys: Column<Fixed>,
+    ) -> MiMC5FixedKeyCipherConfig {
+        let s_in_rounds = meta.selector();
+        let s_post_rounds = meta.selector();
+
+        meta.enable_equality(state);
+
+        //  state                    | round_keys        | selector
+        //  x0 = message             |  key + c0         |
+        //  x1 = (x0+key+c0)^5       |  key + c1         | s_in_rounds
+        //  x2 = (x1+key+c1)^5       |  key + c2         | s_in_rounds
+        //  x3 = (x2+key+c2)^5       |  key + c3         | s_in_rounds
+        //       :                   |     :             |     :
+        //       :                   |  key + c109       |     :
+        //  x110 = (x109+key+c109)^5 |  key              | s_in_rounds
+        //  x110 + key               |                   | s_post_rounds
+
+        meta.create_gate("MiMC5 fixed key encryption rounds", |meta| {
+            let s = meta.query_selector(s_in_rounds);
+            let pow_5_expr = |v: Expression<F>| {
+                 v.clone() * v.clone() * v.clone() * v.clone() * v
+            };
+            let prev_state = meta.query_advice(state, Rotation::prev());
+            let rk = meta.query_fixed(round_keys, Rotation::prev());
+            let current_state = meta.query_advice(state, Rotation::cur());
+            vec![
+                s*(current_state - pow_5_expr(prev_state + rk)),
+            ]
+        });
+
+        meta.create_gate("post rounds fixed key addition", |meta| {
+            let s = meta.query_selector(s_post_rounds);
+            let prev_state = meta.query_advice(state, Rotation::prev());
+            let key = meta.query_fixed(round_keys, Rotation::prev()); // The key sits below the last round key
+            let current_state = meta.query_advice(state, Rotation::cur());
+            vec![s*(current_state - (prev_state + key))]
+        });
+
+        MiMC5FixedKeyCipherConfig {
+            state,
+            round_keys,
+            s_in_rounds,
+            s_post_rounds,
+        }
+    }
+
+    pub fn encrypt_message(
+        &self,
+        layouter: impl Layouter<F>,
+        message: &AssignedCell<F, F>,
+    ) -> Result<AssignedCell<F,F>, Error> {
+        let mut ciphertexts = self.encrypt_messages(layouter, slice::from_ref(message))?;
+        Ok(ciphertexts.remove(0))
+    }
+
+    // Encrypts the messages in one region, each invocation in the rows below
+    // the previous one. Every row of the region is assigned once, the round
+    // keys of each invocation included.
+    pub fn encrypt_messages(
+        &self,
+        mut layouter: impl Layouter<F>,
+        messages: &[AssignedCell<F, F>],
+    ) -> Result<Vec<AssignedCell<F,F>>, Error> {
+        let config = self.config();
+
+        let key = self.get_key();
+        let round_keys = self.loaded();
+        let rounds = round_keys.len();
+
+        // The rounds are MiMC5 hash rounds with the round keys as constants
+        let traces: Vec<_> = messages
+            .iter()
+            .map(|message| message.value().map(|message| mimc5_hash_trace(*message, round_keys)))
+            .collect();
+
+        layouter.assign_region(
+            || "MiMC5 fixed key table",
+            |mut region| {
+                messages.iter().zip(traces.iter()).enumerate().map(|(invocation, (message, trace))| {
+                    let offset = invocation * (rounds + 2);
+
+                    message.copy_advice(
+                        || "message to be encrypted",
+                        &mut region,
+                        config.state,
+                        offset,
+                    )?;
+
+                    for i in 1..=rounds {
+                        config.s_in_rounds.enable(&mut region, offset+i)?;
+                        region.assign_fixed(
+                            || format!("round key {:?}", i-1),
+                            config.round_keys,
+                            offset+i-1,
+                            || Value::known(round_keys[i-1]) // i starts at 1
+                        )?;
+
+                        region.assign_advice(
+                            || format!("round {:?} output", i),
+                            config.state,
+                            offset+i,
+                            || trace.as_ref().map(|trace| trace[i])
+                        )?;
+                    }
+
+                    region.assign_fixed(
+                        || "key",
+                        config.round_keys,
+                        offset+rounds,
+                        || Value::known(key)
+                    )?;
+
+                    config.s_post_rounds.enable(&mut region, offset+rounds+1)?;
+                    region.assign_advice(
+                        || "final state",
+                        config.state,
+                        offset+rounds+1,
+                        || trace.as_ref().map(|trace| trace[rounds] + key)
+                    )
+                }).collect()
+            }
+        )
+    }
+
+    // The rounds cannot be inverted by low-degree gates, so decryption
+    // witnesses the plaintext and constrains its encryption to `ciphertext`.
+    // The ciphertext's column must have equality enabled.
+    pub fn decrypt_message(
+        &self,
+        mut layouter: impl Layouter<F>,
+        ciphertext: &AssignedCell<F, F>,
+    ) -> Result<AssignedCell<F,F>, Error> {
+        let config = self.config();
+        let key = self.get_key();
+
+        let message = layouter.assign_region(
+            || "MiMC5 fixed key decryption",
+            |mut region| {
+                region.assign_advice(
+                    || "plaintext",
+                    config.state,
+                    0,
+                    || ciphertext.value().map(|ciphertext| {
+                        let mut state = *ciphertext;
+                        F::decrypt(&mut state, key);
+                        state
+                    })
+                )
+            }
+        )?;
+
+        let reencrypted = self.encrypt_message(layouter.namespace(|| "re-encryption"), &message)?;
+        layouter.assign_region(
+            || "constrain ciphertext",
+            |mut region| {
+                let ciphertext = ciphertext.copy_advice(|| "ciphertext", &mut region, config.state, 0)?;
+                region.constrain_equal(reencrypted.cell(), ciphertext.cell())
+            }
+        )?;
+        Ok(message)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::mimc::primitives::{mimc5_encrypt_pallas, mimc5_encrypt_vesta};
+
+    use super::*;
+    use crate::cost::chip_costs;
+    use halo2_proofs::{dev::MockProver, pasta::Fp, plonk::Circuit, circuit::SimpleFloorPlanner};
+
+    #[derive(Debug, Clone)]
+    struct MiMC5FixedKeyCipherCircuitConfig {
+        input : Column<Advice>,
+        mimc_config: MiMC5FixedKeyCipherConfig,
+    }
+
+    const PALLAS_KEY: u64 = 7;
+
+    #[derive(Default)]
+    struct MiMC5FixedKeyCipherPallasCircuit {
+        pub message: Fp,
+        pub ciphertext: Fp,
+    }
+
+    impl Circuit<Fp> for MiMC5FixedKeyCipherPallasCircuit {
+        type Config = MiMC5FixedKeyCipherCircuitConfig;
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self::default()
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+            let circuit_input = meta.advice_column();
+            meta.enable_equality(circuit_input);
+            let state = meta.advice_column();
+            let round_keys = meta.fixed_column();
+            Self::Config {
+                input: circuit_input,
+                mimc_config: MiMC5FixedKeyCipherPallasChip::configure(meta, state, round_keys)
+            }
+        }
+
+        fn synthesize(
+            &self,
+            config: Self::Config,
+            mut layouter: impl Layouter<Fp>,
+        ) -> Result<(), Error> {
+            let chip = MiMC5FixedKeyCipherPallasChip::construct(config.mimc_config, Fp::from(PALLAS_KEY));
+
+            let message = layouter.assign_region(
+                || "load message",
+                |mut region| {
+                    region.assign_advice(
+                        || "load input message",
+                        config.input,
+                        0,
+                        || Value::known(self.message)
+                    )
+                }
+            )?;
+
+            let ciphertext = chip.encrypt_message(
+                layouter.namespace(|| "entire table"),
+                &message,
+            )?;
+
+            layouter.assign_region(
+                || "constrain output",
+                |mut region| {
+                    let expected_output = region.assign_advice(
+                        || "load output",
+                        config.input,
+                        0,
+                        || Value::known(self.ciphertext),
+                    )?;
+                    region.constrain_equal(ciphertext.cell(), expected_output.cell())
+                }
+            )?;
+
+            Ok(())
+        }
+    }
+
+
+    #[test]
+    fn test_mimc5_pallas_fixed_key_cipher() {
+        let k = chip_costs().mimc5_fixed_key_cipher.min_k(1);
+
+        let msg = Fp::from(0);
+        let mut output = msg;
+        mimc5_encrypt_pallas(&mut output, Fp::from(PALLAS_KEY));
+
+        let circuit = MiMC5FixedKeyCipherPallasCircuit {
+            message: msg,
+            ciphertext: output,
+        };
+
+        let prover = MockProver::run(k, &circuit, vec![]).unwrap();
+        prover.assert_satisfied();
+
+        // A ciphertext under a different key must be rejected
+        let mut wrong_output = msg;
+        mimc5_encrypt_pallas(&mut wrong_output, Fp::from(PALLAS_KEY + 1));
+        let circuit = MiMC5FixedKeyCipherPallasCircuit {
+            message: msg,
+            ciphertext: wrong_output,
+        };
+
+        let prover = MockProver::run(k, &circuit, vec![]).unwrap();
+        assert!(prover.verify().is_err());
+    }
+
+    const VESTA_KEY: u64 = 11;
+
+    #[derive(Default)]
+    struct MiMC5FixedKeyCipherVestaCircuit {
+        pub message: Fq,
+        pub ciphertext: Fq,
+    }
+
+    impl Circuit<Fq> for MiMC5FixedKeyCipherVestaCircuit {
+        type Config = MiMC5FixedKeyCipherCircuitConfig;
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self::default()
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fq>) -> Self::Config {
+            let circuit_input = meta.advice_column();
+            meta.enable_equality(circuit_input);
+            let state = meta.advice_column();
+            let round_keys = meta.fixed_column();
+            Self::Config {
+                input: circuit_input,
+                mimc_config: MiMC5FixedKeyCipherVestaChip::configure(meta, state, round_keys)
+            }
+        }
+
+        fn synthesize(
+            &self,
+            config: Self::Config,
+            mut layouter: impl Layouter<Fq>,
+        ) -> Result<(), Error> {
+            let chip = MiMC5FixedKeyCipherVestaChip::construct(config.mimc_config, Fq::from(VESTA_KEY));
+
+            let message = layouter.assign_region(
+                || "load message",
+                |mut region| {
+                    region.assign_advice(
+                        || "load input message",
+                        config.input,
+                        0,
+                        || Value::known(self.message)
+                    )
+                }
+            )?;
+
+            let ciphertext = chip.encrypt_message(
+                layouter.namespace(|| "entire table"),
+                &message,
+            )?;
+
+            layouter.assign_region(
+                || "constrain output",
+                |mut region| {
+                    let expected_output = region.assign_advice(
+                        || "load output",
+                        config.input,
+                        0,
+                        || Value::known(self.ciphertext),
+                    )?;
+                    region.constrain_equal(ciphertext.cell(), expected_output.cell())
+                }
+            )?;
+
+            Ok(())
+        }
+    }
+
+
+    #[test]
+    fn test_mimc5_vesta_fixed_key_cipher() {
+        let k = chip_costs().mimc5_fixed_key_cipher.min_k(1);
+
+        let msg = Fq::from(3);
+        let mut output = msg;
+        mimc5_encrypt_vesta(&mut output, Fq::from(VESTA_KEY));
+
+        let circuit = MiMC5FixedKeyCipherVestaCircuit {
+            message: msg,
+            ciphertext: output,
+        };
+
+        let prover = MockProver::run(k, &circuit, vec![]).unwrap();
+        prover.assert_satisfied();
+
+        // A ciphertext under a different key must be rejected
+        let mut wrong_output = msg;
+        mimc5_encrypt_vesta(&mut wrong_output, Fq::from(VESTA_KEY + 1));
+        let circuit = MiMC5FixedKeyCipherVestaCircuit {
+            message: msg,
+            ciphertext: wrong_output,
+        };
+
+        let prover = MockProver::run(k, &circuit, vec![]).unwrap();
+        assert!(prover.verify().is_err());
+
+        // So must a tampered ciphertext under the right key
+        let circuit = MiMC5FixedKeyCipherVestaCircuit {
+            message: msg,
+            ciphertext: output + Fq::one(),
+        };
+
+        let prover = MockProver::run(k, &circuit, vec![]).unwrap();
+        assert!(prover.verify().is_err());
+    }
+
+
+    #[cfg(feature = "dev-graph")]
+    #[test]
+    fn plot_mimc5_pallas_fixed_key_cipher() {
+        use plotters::prelude::*;
+        let k = chip_costs().mimc5_fixed_key_cipher.min_k(1);
+        let root = BitMapBackend::new("mimc5-pallas-fixed-key-cipher-layout.png", (1024, 3096)).into_drawing_area();
+        root.fill(&WHITE).unwrap();
+        let root = root.titled("MiMC Fixed Key Cipher Layout", ("sans-serif", 60)).unwrap();
+
+        let circuit = MiMC5FixedKeyCipherPallasCircuit {
+            message: Fp::zero(),
+            ciphertext: Fp::zero(),
+        };
+
+        halo2_proofs::dev::CircuitLayout::default()
+            .render(k, &circuit, &root)
+            .unwrap();
+    }
+}
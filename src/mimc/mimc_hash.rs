@@ -1,16 +1,18 @@
 use halo2_proofs::{
-    arithmetic::FieldExt,
     plonk::{
         Column, Advice, Fixed, Selector, ConstraintSystem, Expression, Error,
     },
     poly::Rotation,
     circuit::{
-        Layouter, AssignedCell, Value,
+        Chip, Layouter, AssignedCell, Value,
     },
 };
 use pasta_curves::{Fp, Fq};
+use std::slice;
 
-use super::round_constants::{MIMC_PALLAS_ROUND_CONSTANTS, MIMC_VESTA_ROUND_CONSTANTS};
+use super::primitives::mimc5_hash_trace;
+use crate::field::MiMCField;
+use crate::layout::{ColumnLabels, LabeledConfig};
 
 
 #[allow(unused_variables, dead_code)]
@@ -21,14 +23,60 @@ pub struct MiMC5HashConfig {
     s_in_rounds: Selector,
 }
 
-pub trait MiMC5HashChip<F: FieldExt> {
-    fn construct(config: MiMC5HashConfig) -> Self;
+impl LabeledConfig for MiMC5HashConfig {
+    fn labels(&self) -> ColumnLabels {
+        ColumnLabels::default()
+            .column(self.state, "state")
+            .column(self.round_constants, "round constants")
+            .selector(self.s_in_rounds, "s_in_rounds")
+    }
+}
+
+impl MiMC5HashConfig {
+    pub fn state(&self) -> Column<Advice> {
+        self.state
+    }
+
+    pub fn round_constants(&self) -> Column<Fixed> {
+        self.round_constants
+    }
+
+    pub fn s_in_rounds(&self) -> Selector {
+        self.s_in_rounds
+    }
+}
+
+pub struct MiMC5HashChip<F: MiMCField> {
+    config: MiMC5HashConfig,
+    // Round constants loaded once at construction and shared by every invocation
+    round_constants: Vec<F>,
+}
+
+pub type MiMC5HashPallasChip = MiMC5HashChip<Fp>;
+pub type MiMC5HashVestaChip = MiMC5HashChip<Fq>;
+
+impl<F: MiMCField> Chip<F> for MiMC5HashChip<F> {
+    type Config = MiMC5HashConfig;
+    type Loaded = Vec<F>;
+
+    fn config(&self) -> &Self::Config {
+        &self.config
+    }
 
-    fn get_round_constants() -> Vec<F>;
+    fn loaded(&self) -> &Self::Loaded {
+        &self.round_constants
+    }
+}
 
-    fn get_config(&self) -> &MiMC5HashConfig;
+impl<F: MiMCField> MiMC5HashChip<F> {
+    pub fn construct(config: MiMC5HashConfig) -> Self {
+        Self {
+            config,
+            round_constants: F::MIMC5_ROUND_CONSTANTS.to_vec(),
+        }
+    }
 
-    fn configure(
+    pub fn configure(
         meta: &mut ConstraintSystem<F>,
         state: Column<Advice>,
         round_constants: Column<Fixed>,
@@ -36,7 +84,6 @@ pub trait MiMC5HashChip<F: FieldExt> {
         let s_in_rounds = meta.selector();
 
         meta.enable_equality(state);
-        meta.enable_constant(round_constants);
 
         //  state                    | round_constants   | selector
         //  x0 = message             |     c0            | 
@@ -69,102 +116,82 @@ pub trait MiMC5HashChip<F: FieldExt> {
         }
     }
 
-    fn hash_message(
+    pub fn hash_message(
         &self,
-        mut layouter: impl Layouter<F>,
+        layouter: impl Layouter<F>,
         message: &AssignedCell<F, F>,
     ) -> Result<AssignedCell<F, F>, Error> {
-        let config = self.get_config();
+        let mut hashes = self.hash_messages(layouter, slice::from_ref(message))?;
+        Ok(hashes.remove(0))
+    }
+
+    // Hashes the messages in one region, each invocation in the rows below
+    // the previous one. Every row of the region is assigned once, the round
+    // constants of each invocation included.
+    pub fn hash_messages(
+        &self,
+        mut layouter: impl Layouter<F>,
+        messages: &[AssignedCell<F, F>],
+    ) -> Result<Vec<AssignedCell<F, F>>, Error> {
+        let config = self.config();
+
+        let round_constant_values = self.loaded();
+        let rounds = round_constant_values.len();
+        let traces: Vec<_> = messages
+            .iter()
+            .map(|message| message.value().map(|message| mimc5_hash_trace(*message, round_constant_values)))
+            .collect();
 
-        let round_constant_values = Self::get_round_constants();
         layouter.assign_region(
             || "MiMC5 table",
             |mut region| {
+                messages.iter().zip(traces.iter()).enumerate().map(|(invocation, (message, trace))| {
+                    let offset = invocation * (rounds + 1);
 
-                let msg_cell =
-                region.assign_advice(
-                    || "message to be hashed",
-                    config.state,
-                    0,
-                    || message.value().copied(),
-                )?;
-
-                let pow_5 = |v: Value<F>| { v*v*v*v*v };
-
-                let mut current_state = message.value().copied();
-                let mut state_cell = msg_cell.clone();
-                for i in 1..=round_constant_values.len() {
-                    config.s_in_rounds.enable(&mut region, i)?;
-                    region.assign_fixed(
-                        || format!("round constant {:?}", i),
-                        config.round_constants,
-                        i-1,
-                        || Value::known(round_constant_values[i-1]) // i starts at 1
-                    )?;
-
-                    current_state = pow_5(current_state + Value::known(round_constant_values[i-1]));
-                    
-                    state_cell =
-                    region.assign_advice(
-                        || format!("round {:?} output", i),
+                    let msg_cell =
+                    message.copy_advice(
+                        || "message to be hashed",
+                        &mut region,
                         config.state,
-                        i,
-                        || current_state
+                        offset,
                     )?;
-                }
 
-                Ok(state_cell)
+                    let mut state_cell = msg_cell.clone();
+                    for i in 1..=rounds {
+                        config.s_in_rounds.enable(&mut region, offset+i)?;
+                        region.assign_fixed(
+                            || format!("round constant {:?}", i-1),
+                            config.round_constants,
+                            offset+i-1,
+                            || Value::known(round_constant_values[i-1]) // i starts at 1
+                        )?;
+
+                        state_cell =
+                        region.assign_advice(
+                            || format!("round {:?} output", i),
+                            config.state,
+                            offset+i,
+                            || trace.as_ref().map(|trace| trace[i])
+                        )?;
+                    }
+
+                    Ok(state_cell)
+                }).collect()
             }
         )
     }
 }
 
-pub struct MiMC5HashPallasChip {
-    config: MiMC5HashConfig
-}
-
-impl MiMC5HashChip<Fp> for MiMC5HashPallasChip {
-    fn construct(config: MiMC5HashConfig) -> Self {
-        Self {
-            config,
-        }
-    }
-
-    fn get_config(&self) -> &MiMC5HashConfig {
-        &self.config
-    }
-
-    fn get_round_constants() -> Vec<Fp> {
-        MIMC_PALLAS_ROUND_CONSTANTS.to_vec()
-    }
-}
-
-pub struct MiMC5HashVestaChip {
-    config: MiMC5HashConfig
-}
-
-impl MiMC5HashChip<Fq> for MiMC5HashVestaChip {
-    fn construct(config: MiMC5HashConfig) -> Self {
-        Self {
-            config,
-        }
-    }
-
-    fn get_config(&self) -> &MiMC5HashConfig {
-        &self.config
-    }
-
-    fn get_round_constants() -> Vec<Fq> {
-        MIMC_VESTA_ROUND_CONSTANTS.to_vec()
-    }
-}
 
 #[cfg(test)]
 mod tests {
     use crate::mimc::primitives::{mimc5_hash_pallas, mimc5_hash_vesta};
 
     use super::*;
+    use crate::cost::chip_costs;
     use halo2_proofs::{dev::MockProver, pasta::Fp, plonk::Circuit, circuit::SimpleFloorPlanner};
+    use crate::assignment::AssignmentTable;
+    use crate::mimc::round_constants::NUM_ROUNDS;
 
     #[derive(Debug, Clone)]
     struct MiMC5HashCircuitConfig {
@@ -241,7 +268,7 @@ mod tests {
  
     #[test]
     fn test_mimc5_pallas_hash() {
-        let k = 7;
+        let k = chip_costs().mimc5_hash.min_k(1);
 
         let msg = Fp::from(0);
         let mut output = msg;
@@ -327,7 +354,7 @@ mod tests {
      
     #[test]
     fn test_mimc5_vesta_hash() {
-        let k = 7;
+        let k = chip_costs().mimc5_hash.min_k(1);
 
         let msg = Fq::from(0);
         let mut output = msg;
@@ -343,12 +370,112 @@ mod tests {
 
     }
 
+    // The circuit's constants column, which gadgets such as halo2_gadgets'
+    // ECC and Poseidon chips assign their constants to, is also the column of
+    // the MiMC round constants. Both messages are hashed in one region.
+    #[derive(Default)]
+    struct MiMC5BatchHashPallasCircuit {
+        pub message_hashes: [Fp; 2],
+    }
+
+    impl Circuit<Fp> for MiMC5BatchHashPallasCircuit {
+        type Config = MiMC5HashCircuitConfig;
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self::default()
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+            let circuit_input = meta.advice_column();
+            meta.enable_equality(circuit_input);
+            let constants = meta.fixed_column();
+            meta.enable_constant(constants);
+            let state = meta.advice_column();
+            MiMC5HashCircuitConfig {
+                input: circuit_input,
+                mimc_config: MiMC5HashPallasChip::configure(meta, state, constants)
+            }
+        }
+
+        fn synthesize(
+            &self,
+            config: Self::Config,
+            mut layouter: impl Layouter<Fp>,
+        ) -> Result<(), Error> {
+            let chip = MiMC5HashPallasChip::construct(config.mimc_config);
+
+            let messages = layouter.assign_region(
+                || "load constant messages",
+                |mut region| {
+                    [42, 43].into_iter().enumerate().map(|(row, message)| {
+                        region.assign_advice_from_constant(
+                            || "load input message",
+                            config.input,
+                            row,
+                            Fp::from(message),
+                        )
+                    }).collect::<Result<Vec<_>, Error>>()
+                }
+            )?;
+
+            let msg_hashes = chip.hash_messages(
+                layouter.namespace(|| "both hashes"),
+                &messages,
+            )?;
+
+            layouter.assign_region(
+                || "constrain outputs",
+                |mut region| {
+                    for (row, (msg_hash, expected)) in msg_hashes.iter().zip(self.message_hashes).enumerate() {
+                        let expected_output = region.assign_advice(
+                            || "load output",
+                            config.input,
+                            row,
+                            || Value::known(expected),
+                        )?;
+                        region.constrain_equal(msg_hash.cell(), expected_output.cell())?;
+                    }
+                    Ok(())
+                }
+            )?;
+
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_mimc5_pallas_hash_batch_with_shared_constants_column() {
+        let k = chip_costs().mimc5_hash.min_k(2);
+
+        let mut message_hashes = [Fp::from(42), Fp::from(43)];
+        for output in message_hashes.iter_mut() {
+            mimc5_hash_pallas(output);
+        }
+
+        let circuit = MiMC5BatchHashPallasCircuit { message_hashes };
+        let prover = MockProver::run(k, &circuit, vec![]).unwrap();
+        prover.assert_satisfied();
+
+        // The fixed cells are the round constants of each invocation and the
+        // two constant messages, and there is no other fixed column
+        let table = AssignmentTable::synthesize(k, &circuit, vec![]).unwrap();
+        assert_eq!(table.fixed.len(), 1);
+        assert_eq!(table.fixed[0].len(), 2 * NUM_ROUNDS + 2);
+
+        let circuit = MiMC5BatchHashPallasCircuit {
+            message_hashes: [message_hashes[0], message_hashes[1] + Fp::one()],
+        };
+        let prover = MockProver::run(k, &circuit, vec![]).unwrap();
+        assert!(prover.verify().is_err());
+    }
+
 
     #[cfg(feature = "dev-graph")]
     #[test]
     fn plot_mimc5_pallas_hash() {
         use plotters::prelude::*;
-        let k = 7;
+        let k = chip_costs().mimc5_hash.min_k(1);
         let root = BitMapBackend::new("mimc5-pallas-hash-layout.png", (1024, 3096)).into_drawing_area();
         root.fill(&WHITE).unwrap();
         let root = root.titled("MiMC Hash Layout", ("sans-serif", 60)).unwrap();
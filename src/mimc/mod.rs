@@ -1,4 +1,5 @@
 pub mod mimc_hash;
 pub mod mimc_cipher;
-mod round_constants;
+pub mod mimc_fixed_key_cipher;
+pub(crate) mod round_constants;
 pub mod primitives;
\ No newline at end of file
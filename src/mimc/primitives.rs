@@ -1,57 +1,256 @@
 use halo2_proofs::arithmetic::FieldExt;
 use pasta_curves::{Fp, Fq};
-use crate::mimc::round_constants::NUM_ROUNDS;
+use rayon::prelude::*;
 use crate::mimc::round_constants::{MIMC_PALLAS_ROUND_CONSTANTS, MIMC_VESTA_ROUND_CONSTANTS};
 
-pub fn mimc5_encrypt<F: FieldExt, const ROUNDS: usize>(
+pub fn mimc5_encrypt<F: FieldExt>(
     state: &mut F,
     key: F,
-    round_constants: [F; ROUNDS],
+    round_constants: &[F],
 ) {
     let pow_5 = |v: F| { v*v*v*v*v };
 
     for c in round_constants {
         *state = pow_5(*state + key + c);
     }
-    *state = *state + key;
+    *state += key;
 }
 
-pub fn mimc5_hash<F: FieldExt, const ROUNDS: usize>(
+// Round keys k + c_i for a MiMC5 key that is fixed at circuit construction time
+pub fn mimc5_round_keys<F: FieldExt>(
+    key: F,
+    round_constants: &[F],
+) -> Vec<F> {
+    round_constants.iter().map(|c| key + c).collect()
+}
+
+// Inverts mimc5_encrypt. The fifth root is x^(1/5) = x^inv_5 where inv_5 is
+// the inverse of 5 modulo p - 1, given as little-endian 64-bit limbs
+pub fn mimc5_decrypt<F: FieldExt>(
+    state: &mut F,
+    key: F,
+    round_constants: &[F],
+    inv_5: &[u64; 4],
+) {
+    *state -= key;
+    for c in round_constants.iter().rev() {
+        *state = state.pow_vartime(inv_5) - key - c;
+    }
+}
+
+pub fn mimc5_hash<F: FieldExt>(
     state: &mut F,
-    round_constants: [F; ROUNDS],
+    round_constants: &[F],
 ) {
     mimc5_encrypt(state, F::zero(), round_constants);
 }
 
+// Every state of mimc5_encrypt before the final key addition: the message
+// followed by the output of each round. The ciphertext is the last state
+// plus the key.
+pub fn mimc5_encrypt_trace<F: FieldExt>(
+    message: F,
+    key: F,
+    round_constants: &[F],
+) -> Vec<F> {
+    let pow_5 = |v: F| { v*v*v*v*v };
+
+    let mut trace = Vec::with_capacity(round_constants.len() + 1);
+    let mut state = message;
+    trace.push(state);
+    for c in round_constants {
+        state = pow_5(state + key + c);
+        trace.push(state);
+    }
+    trace
+}
+
+// Every state of mimc5_hash: the message followed by the output of each round
+pub fn mimc5_hash_trace<F: FieldExt>(
+    message: F,
+    round_constants: &[F],
+) -> Vec<F> {
+    mimc5_encrypt_trace(message, F::zero(), round_constants)
+}
+
+// Messages encrypted together in one interleaved pass, so that the
+// multiplications of independent messages can overlap in the pipeline
+pub(crate) const BATCH_LANES: usize = 4;
+// Messages handed to each rayon task
+pub(crate) const BATCH_CHUNK: usize = 256;
+
+// Encrypts exactly LANES messages with their keys, or hashes them if there
+// are no keys, a round at a time across all of them
+fn mimc5_encrypt_lanes<F: FieldExt, const LANES: usize>(
+    states: &mut [F],
+    keys: Option<&[F]>,
+    round_constants: &[F],
+) {
+    let mut lanes = [F::zero(); LANES];
+    let mut lane_keys = [F::zero(); LANES];
+    lanes.copy_from_slice(states);
+    if let Some(keys) = keys {
+        lane_keys.copy_from_slice(keys);
+    }
+
+    for c in round_constants {
+        for (state, key) in lanes.iter_mut().zip(lane_keys.iter()) {
+            let x = *state + key + c;
+            *state = x.square().square() * x;
+        }
+    }
+    for ((state, lane), key) in states.iter_mut().zip(lanes.iter()).zip(lane_keys.iter()) {
+        *state = *lane + key;
+    }
+}
+
+// Runs a group of at most BATCH_LANES messages with one lane per message, so
+// a short tail does no work for empty lanes
+fn mimc5_encrypt_interleaved<F: FieldExt>(
+    states: &mut [F],
+    keys: Option<&[F]>,
+    round_constants: &[F],
+) {
+    match states.len() {
+        BATCH_LANES => mimc5_encrypt_lanes::<F, BATCH_LANES>(states, keys, round_constants),
+        3 => mimc5_encrypt_lanes::<F, 3>(states, keys, round_constants),
+        2 => mimc5_encrypt_lanes::<F, 2>(states, keys, round_constants),
+        1 => mimc5_encrypt_lanes::<F, 1>(states, keys, round_constants),
+        _ => unreachable!("groups hold 1 to BATCH_LANES messages"),
+    }
+}
+
+// Hashes each element of `states` in place, in parallel
+pub fn mimc5_hash_batch<F: FieldExt>(
+    states: &mut [F],
+    round_constants: &[F],
+) {
+    states.par_chunks_mut(BATCH_CHUNK).for_each(|chunk| {
+        for lanes in chunk.chunks_mut(BATCH_LANES) {
+            mimc5_encrypt_interleaved(lanes, None, round_constants);
+        }
+    });
+}
+
+// Encrypts each element of `states` in place under the key at the same
+// index, in parallel
+pub fn mimc5_encrypt_batch<F: FieldExt>(
+    states: &mut [F],
+    keys: &[F],
+    round_constants: &[F],
+) {
+    assert_eq!(states.len(), keys.len(), "one key is needed per message");
+    states
+        .par_chunks_mut(BATCH_CHUNK)
+        .zip(keys.par_chunks(BATCH_CHUNK))
+        .for_each(|(chunk, chunk_keys)| {
+            for (lanes, lane_keys) in chunk.chunks_mut(BATCH_LANES).zip(chunk_keys.chunks(BATCH_LANES)) {
+                mimc5_encrypt_interleaved(lanes, Some(lane_keys), round_constants);
+            }
+        });
+}
+
+// Inverse of 5 modulo p - 1 for the Pallas and Vesta base fields
+pub const PALLAS_INV_5: [u64; 4] = [
+    0xe0f0_f3f0_cccc_cccd,
+    0x4e9e_e0c9_a10a_60e2,
+    0x3333_3333_3333_3333,
+    0x3333_3333_3333_3333,
+];
+pub const VESTA_INV_5: [u64; 4] = [
+    0xd69f_2280_cccc_cccd,
+    0x4e9e_e0c9_a143_ba4a,
+    0x3333_3333_3333_3333,
+    0x3333_3333_3333_3333,
+];
+
 pub fn mimc5_hash_pallas(
     state: &mut Fp,
 ) {
-    mimc5_hash::<Fp, NUM_ROUNDS>(state, MIMC_PALLAS_ROUND_CONSTANTS);
+    mimc5_hash(state, &MIMC_PALLAS_ROUND_CONSTANTS);
 }
 
 pub fn mimc5_hash_vesta(
     state: &mut Fq,
 ) {
-    mimc5_hash::<Fq, NUM_ROUNDS>(state, MIMC_VESTA_ROUND_CONSTANTS);
+    mimc5_hash(state, &MIMC_VESTA_ROUND_CONSTANTS);
 }
 
 pub fn mimc5_encrypt_pallas(
     state: &mut Fp,
     key: Fp,
 ) {
-    mimc5_encrypt::<Fp, NUM_ROUNDS>(state, key, MIMC_PALLAS_ROUND_CONSTANTS);
+    mimc5_encrypt(state, key, &MIMC_PALLAS_ROUND_CONSTANTS);
 }
 
 pub fn mimc5_encrypt_vesta(
     state: &mut Fq,
     key: Fq,
 ) {
-    mimc5_encrypt::<Fq, NUM_ROUNDS>(state, key, MIMC_VESTA_ROUND_CONSTANTS);
+    mimc5_encrypt(state, key, &MIMC_VESTA_ROUND_CONSTANTS);
+}
+
+pub fn mimc5_decrypt_pallas(
+    state: &mut Fp,
+    key: Fp,
+) {
+    mimc5_decrypt(state, key, &MIMC_PALLAS_ROUND_CONSTANTS, &PALLAS_INV_5);
+}
+
+pub fn mimc5_decrypt_vesta(
+    state: &mut Fq,
+    key: Fq,
+) {
+    mimc5_decrypt(state, key, &MIMC_VESTA_ROUND_CONSTANTS, &VESTA_INV_5);
+}
+
+pub fn mimc5_hash_batch_pallas(
+    states: &mut [Fp],
+) {
+    mimc5_hash_batch(states, &MIMC_PALLAS_ROUND_CONSTANTS);
+}
+
+pub fn mimc5_hash_batch_vesta(
+    states: &mut [Fq],
+) {
+    mimc5_hash_batch(states, &MIMC_VESTA_ROUND_CONSTANTS);
+}
+
+pub fn mimc5_encrypt_batch_pallas(
+    states: &mut [Fp],
+    keys: &[Fp],
+) {
+    mimc5_encrypt_batch(states, keys, &MIMC_PALLAS_ROUND_CONSTANTS);
+}
+
+pub fn mimc5_encrypt_batch_vesta(
+    states: &mut [Fq],
+    keys: &[Fq],
+) {
+    mimc5_encrypt_batch(states, keys, &MIMC_VESTA_ROUND_CONSTANTS);
+}
+
+pub fn mimc5_round_keys_pallas(
+    key: Fp,
+) -> Vec<Fp> {
+    mimc5_round_keys(key, &MIMC_PALLAS_ROUND_CONSTANTS)
+}
+
+pub fn mimc5_round_keys_vesta(
+    key: Fq,
+) -> Vec<Fq> {
+    mimc5_round_keys(key, &MIMC_VESTA_ROUND_CONSTANTS)
 }
 
 #[cfg(test)]
 mod tests {
     use super::{mimc5_hash_pallas, mimc5_hash_vesta, mimc5_encrypt_pallas, mimc5_encrypt_vesta};
+    use super::mimc5_round_keys_pallas;
+    use crate::mimc::round_constants::NUM_ROUNDS;
+    use super::{mimc5_decrypt_pallas, mimc5_decrypt_vesta};
+    use super::{mimc5_hash_batch_pallas, mimc5_encrypt_batch_vesta};
+    use super::{mimc5_hash_trace, mimc5_encrypt_trace, mimc5_encrypt, mimc5_decrypt};
+    use crate::field::MiMCField;
     use pasta_curves::{pallas, vesta};
 
     #[test]
@@ -118,4 +317,93 @@ mod tests {
         assert_eq!(vesta_expected_ciphertext, vesta_output);
 
     }
+
+    #[test]
+    fn test_mimc5_round_keys () {
+        // Hashing with the round keys as round constants and adding the key
+        // at the end must agree with encryption under that key
+        let message = pallas::Base::from(1);
+        let key = pallas::Base::from(2);
+        let mut expected_ciphertext = message;
+        mimc5_encrypt_pallas(&mut expected_ciphertext, key);
+
+        let trace = mimc5_hash_trace(message, &mimc5_round_keys_pallas(key));
+        let output = trace[trace.len() - 1] + key;
+        assert_eq!(expected_ciphertext, output);
+    }
+
+    #[test]
+    fn test_mimc5_round_count_is_constant_count () {
+        // The generic primitives run one round per constant they are given
+        let round_constants = &pallas::Base::MIMC5_ROUND_CONSTANTS[..3];
+        let (message, key) = (pallas::Base::from(1), pallas::Base::from(2));
+        let mut ciphertext = message;
+        mimc5_encrypt(&mut ciphertext, key, round_constants);
+        let trace = mimc5_encrypt_trace(message, key, round_constants);
+        assert_eq!(trace.len(), 4);
+        assert_eq!(trace[3] + key, ciphertext);
+        mimc5_decrypt(&mut ciphertext, key, round_constants, &pallas::Base::INV_5);
+        assert_eq!(ciphertext, message);
+    }
+
+    #[test]
+    fn test_mimc5_decrypt_primitives () {
+        let pallas_message = pallas::Base::from(1);
+        let pallas_key = pallas::Base::from(2);
+        let mut pallas_output = pallas_message;
+        mimc5_encrypt_pallas(&mut pallas_output, pallas_key);
+        mimc5_decrypt_pallas(&mut pallas_output, pallas_key);
+        assert_eq!(pallas_message, pallas_output);
+
+        let vesta_message = vesta::Base::from(1);
+        let vesta_key = vesta::Base::from(2);
+        let mut vesta_output = vesta_message;
+        mimc5_encrypt_vesta(&mut vesta_output, vesta_key);
+        mimc5_decrypt_vesta(&mut vesta_output, vesta_key);
+        assert_eq!(vesta_message, vesta_output);
+    }
+
+    #[test]
+    fn test_mimc5_batch_primitives () {
+        // Sizes around the lane and chunk boundaries, including every tail length
+        for n in [0, 1, 2, 3, 5, 1000] {
+            let messages: Vec<pallas::Base> = (0..n).map(pallas::Base::from).collect();
+            let mut batch = messages.clone();
+            mimc5_hash_batch_pallas(&mut batch);
+            for (message, output) in messages.iter().zip(batch.iter()) {
+                let mut expected = *message;
+                mimc5_hash_pallas(&mut expected);
+                assert_eq!(expected, *output);
+            }
+
+            let messages: Vec<vesta::Base> = (0..n).map(vesta::Base::from).collect();
+            let keys: Vec<vesta::Base> = (0..n).map(|i| vesta::Base::from(i + 7)).collect();
+            let mut batch = messages.clone();
+            mimc5_encrypt_batch_vesta(&mut batch, &keys);
+            for ((message, key), output) in messages.iter().zip(keys.iter()).zip(batch.iter()) {
+                let mut expected = *message;
+                mimc5_encrypt_vesta(&mut expected, *key);
+                assert_eq!(expected, *output);
+            }
+        }
+    }
+
+    #[test]
+    fn test_mimc5_trace_primitives () {
+        let message = pallas::Base::from(1);
+        let key = pallas::Base::from(2);
+        let trace = mimc5_encrypt_trace(message, key, pallas::Base::MIMC5_ROUND_CONSTANTS);
+        assert_eq!(trace.len(), NUM_ROUNDS + 1);
+        assert_eq!(trace[0], message);
+        let mut ciphertext = message;
+        mimc5_encrypt_pallas(&mut ciphertext, key);
+        assert_eq!(trace[NUM_ROUNDS] + key, ciphertext);
+
+        let message = vesta::Base::from(1);
+        let trace = mimc5_hash_trace(message, vesta::Base::MIMC5_ROUND_CONSTANTS);
+        let mut hash = message;
+        mimc5_hash_vesta(&mut hash);
+        assert_eq!(trace.len(), NUM_ROUNDS + 1);
+        assert_eq!(trace[NUM_ROUNDS], hash);
+    }
 }
\ No newline at end of file
@@ -1,16 +1,18 @@
 use halo2_proofs::{
-    arithmetic::FieldExt,
     plonk::{
         Column, Advice, Fixed, Selector, ConstraintSystem, Expression, Error,
     },
     poly::Rotation,
     circuit::{
-        Layouter, AssignedCell, Value,
+        Chip, Layouter, AssignedCell, Value,
     },
 };
 use pasta_curves::{Fp, Fq};
+use std::slice;
 
-use super::round_constants::{MIMC_FEISTEL_PALLAS_ROUND_CONSTANTS, MIMC_FEISTEL_VESTA_ROUND_CONSTANTS};
+use super::primitives::mimc5_feistel_encrypt_trace;
+use crate::field::MiMCField;
+use crate::layout::{ColumnLabels, LabeledConfig};
 
 
 #[allow(unused_variables, dead_code)]
@@ -24,14 +26,75 @@ pub struct MiMC5FeistelCipherConfig {
     s_last_round: Selector,
 }
 
-pub trait MiMC5FeistelCipherChip<F: FieldExt> {
-    fn construct(config: MiMC5FeistelCipherConfig) -> Self;
+impl LabeledConfig for MiMC5FeistelCipherConfig {
+    fn labels(&self) -> ColumnLabels {
+        ColumnLabels::default()
+            .column(self.state_left, "state_left")
+            .column(self.state_right, "state_right")
+            .column(self.key_column, "key")
+            .column(self.round_constants, "round constants")
+            .selector(self.s_inner_rounds, "s_inner_rounds")
+            .selector(self.s_last_round, "s_last_round")
+    }
+}
+
+impl MiMC5FeistelCipherConfig {
+    pub fn state_left(&self) -> Column<Advice> {
+        self.state_left
+    }
+
+    pub fn state_right(&self) -> Column<Advice> {
+        self.state_right
+    }
+
+    pub fn key_column(&self) -> Column<Advice> {
+        self.key_column
+    }
+
+    pub fn round_constants(&self) -> Column<Fixed> {
+        self.round_constants
+    }
+
+    pub fn s_inner_rounds(&self) -> Selector {
+        self.s_inner_rounds
+    }
+
+    pub fn s_last_round(&self) -> Selector {
+        self.s_last_round
+    }
+}
+
+pub struct MiMC5FeistelCipherChip<F: MiMCField> {
+    config: MiMC5FeistelCipherConfig,
+    // Round constants loaded once at construction and shared by every invocation
+    round_constants: Vec<F>,
+}
 
-    fn get_round_constants() -> Vec<F>;
+pub type MiMC5FeistelCipherPallasChip = MiMC5FeistelCipherChip<Fp>;
+pub type MiMC5FeistelCipherVestaChip = MiMC5FeistelCipherChip<Fq>;
 
-    fn get_config(&self) -> &MiMC5FeistelCipherConfig;
+impl<F: MiMCField> Chip<F> for MiMC5FeistelCipherChip<F> {
+    type Config = MiMC5FeistelCipherConfig;
+    type Loaded = Vec<F>;
+
+    fn config(&self) -> &Self::Config {
+        &self.config
+    }
 
-    fn configure(
+    fn loaded(&self) -> &Self::Loaded {
+        &self.round_constants
+    }
+}
+
+impl<F: MiMCField> MiMC5FeistelCipherChip<F> {
+    pub fn construct(config: MiMC5FeistelCipherConfig) -> Self {
+        Self {
+            config,
+            round_constants: F::MIMC5_FEISTEL_ROUND_CONSTANTS.to_vec(),
+        }
+    }
+
+    pub fn configure(
         meta: &mut ConstraintSystem<F>,
         state_left: Column<Advice>,
         state_right: Column<Advice>,
@@ -44,7 +107,6 @@ pub trait MiMC5FeistelCipherChip<F: FieldExt> {
         meta.enable_equality(state_left);
         meta.enable_equality(state_right);
         meta.enable_equality(key_column);
-        meta.enable_constant(round_constants);
 
         //  state_left                           | state_right                      | key_column | round_constants   | selector
         //  xL,0 = xL                            | xR,0 = xR                        | k          |     c0            | 
@@ -66,7 +128,7 @@ pub trait MiMC5FeistelCipherChip<F: FieldExt> {
 
             let rc = meta.query_fixed(round_constants, Rotation::prev());
             let key = meta.query_advice(key_column, Rotation::cur());
-            let prev_key = meta.query_advice(key_column, Rotation::cur());
+            let prev_key = meta.query_advice(key_column, Rotation::prev());
             
             let current_state_left = meta.query_advice(state_left, Rotation::cur());
             let current_state_right = meta.query_advice(state_right, Rotation::cur());
@@ -83,7 +145,7 @@ pub trait MiMC5FeistelCipherChip<F: FieldExt> {
             let prev_state_right = meta.query_advice(state_right, Rotation::prev());
 
             let key = meta.query_advice(key_column, Rotation::cur());
-            let prev_key = meta.query_advice(key_column, Rotation::cur());
+            let prev_key = meta.query_advice(key_column, Rotation::prev());
 
             let current_state_left = meta.query_advice(state_left, Rotation::cur());
             let current_state_right = meta.query_advice(state_right, Rotation::cur());
@@ -104,162 +166,195 @@ pub trait MiMC5FeistelCipherChip<F: FieldExt> {
         }
     }
 
-    fn encrypt_message(
+    #[allow(clippy::type_complexity)]
+    pub fn encrypt_message(
         &self,
-        mut layouter: impl Layouter<F>,
+        layouter: impl Layouter<F>,
         message_left: &AssignedCell<F, F>,
         message_right: &AssignedCell<F, F>,
         key: &AssignedCell<F, F>,
     ) -> Result<(AssignedCell<F, F>, AssignedCell<F,F>), Error> {
-        let config = self.get_config();
-
-        let round_constant_values = Self::get_round_constants();
-        layouter.assign_region(
-            || "MiMC5 Feistel table",
-            |mut region| {
-
-                region.assign_advice(
-                    || "left part of message to be hashed",
-                    config.state_left,
-                    0,
-                    || message_left.value().copied(),
-                )?;
+        let message = (message_left.clone(), message_right.clone());
+        let mut ciphertexts = self.encrypt_messages(layouter, slice::from_ref(&message), key)?;
+        Ok(ciphertexts.remove(0))
+    }
 
-                region.assign_advice(
-                    || "right part of message to be hashed",
-                    config.state_right,
-                    0,
-                    || message_right.value().copied(),
-                )?;
+    // Encrypts the (left, right) messages under one key in one region, each
+    // invocation in the rows below the previous one. Every row of the region
+    // is assigned once, the round constants of each invocation included.
+    #[allow(clippy::type_complexity)]
+    pub fn encrypt_messages(
+        &self,
+        mut layouter: impl Layouter<F>,
+        messages: &[(AssignedCell<F, F>, AssignedCell<F, F>)],
+        key: &AssignedCell<F, F>,
+    ) -> Result<Vec<(AssignedCell<F, F>, AssignedCell<F,F>)>, Error> {
+        let config = self.config();
 
-                region.assign_advice(
-                    || format!("key in row 0"),
-                    config.key_column,
-                    0,
-                    || key.value().copied(),
-                )?;
+        let round_constant_values = self.loaded();
+        let rounds = round_constant_values.len();
 
-                let pow_5 = |v: Value<F>| { v*v*v*v*v };
+        let traces: Vec<_> = messages
+            .iter()
+            .map(|(left, right)| left.value().zip(right.value()).zip(key.value()).map(|((left, right), key)| {
+                mimc5_feistel_encrypt_trace(*left, *right, *key, round_constant_values)
+            }))
+            .collect();
 
-                let mut current_state_left = message_left.value().copied();
-                let mut current_state_right = message_right.value().copied();
+        layouter.assign_region(
+            || "MiMC5 Feistel table",
+            |mut region| {
+                messages.iter().zip(traces.iter()).enumerate().map(|(invocation, ((message_left, message_right), trace))| {
+                    let offset = invocation * (rounds + 1);
 
-                let state_cell_left;
-                let state_cell_right;
+                    message_left.copy_advice(
+                        || "left part of message to be hashed",
+                        &mut region,
+                        config.state_left,
+                        offset,
+                    )?;
 
-                for i in 1..round_constant_values.len() { // i goes from 1 to 219
-                    config.s_inner_rounds.enable(&mut region, i)?;
-                    region.assign_fixed(
-                        || format!("round constant {:?}", i),
-                        config.round_constants,
-                        i-1,
-                        || Value::known(round_constant_values[i-1]) // i starts at 1
+                    message_right.copy_advice(
+                        || "right part of message to be hashed",
+                        &mut region,
+                        config.state_right,
+                        offset,
                     )?;
 
-                    region.assign_advice(
-                        || format!("key in row {:?} ", i),
+                    key.copy_advice(
+                        || "key in row 0",
+                        &mut region,
                         config.key_column,
-                        i,
-                        || key.value().copied()
+                        offset,
                     )?;
 
+                    for i in 1..rounds { // i goes from 1 to 219
+                        config.s_inner_rounds.enable(&mut region, offset+i)?;
+                        region.assign_fixed(
+                            || format!("round constant {:?}", i-1),
+                            config.round_constants,
+                            offset+i-1,
+                            || Value::known(round_constant_values[i-1]) // i starts at 1
+                        )?;
+
+                        key.copy_advice(
+                            || format!("key in row {:?} ", i),
+                            &mut region,
+                            config.key_column,
+                            offset+i,
+                        )?;
+
+                        region.assign_advice(
+                            || format!("round {:?} output on the left", i),
+                            config.state_left,
+                            offset+i,
+                            || trace.as_ref().map(|trace| trace[i].0)
+                        )?;
+
+                        region.assign_advice(
+                            || format!("round {:?} output on the right", i),
+                            config.state_right,
+                            offset+i,
+                            || trace.as_ref().map(|trace| trace[i].1)
+                        )?;
+                    }
+
+                    config.s_last_round.enable(&mut region, offset+rounds)?;
+                    key.copy_advice(
+                        || format!("key in row {:?}", rounds),
+                        &mut region,
+                        config.key_column,
+                        offset+rounds,
+                    )?;
 
-                    let temp = current_state_right + pow_5(current_state_left + key.value().copied() + Value::known(round_constant_values[i-1]));
-                    current_state_right = current_state_left;
-                    current_state_left = temp;
-                    
+                    let state_cell_left =
                     region.assign_advice(
-                        || format!("round {:?} output on the left", i),
+                        || "last round output on the left",
                         config.state_left,
-                        i,
-                        || current_state_left
+                        offset+rounds,
+                        || trace.as_ref().map(|trace| trace[rounds].0)
                     )?;
-
+                    let state_cell_right =
                     region.assign_advice(
-                        || format!("round {:?} output on the right", i),
+                        || "last round output on the right",
                         config.state_right,
-                        i,
-                        || current_state_right
+                        offset+rounds,
+                        || trace.as_ref().map(|trace| trace[rounds].1)
                     )?;
-                }
-
-                config.s_last_round.enable(&mut region, round_constant_values.len())?;
-                region.assign_advice(
-                    || format!("key in row {:?}", round_constant_values.len()),
-                    config.key_column,
-                    round_constant_values.len(),
-                    || key.value().copied(),
-                )?;
-
-                current_state_right = current_state_right + pow_5(current_state_left + key.value().copied());
-                state_cell_left =
-                region.assign_advice(
-                    || "last round output on the left",
-                    config.state_left,
-                    round_constant_values.len(),
-                    || current_state_left
-                )?;
-                state_cell_right =
-                region.assign_advice(
-                    || "last round output on the right",
-                    config.state_right,
-                    round_constant_values.len(),
-                    || current_state_right
-                )?;
 
-                // The left output is unchanged in the last round
+                    // The left output is unchanged in the last round
 
-                Ok((state_cell_left, state_cell_right))
+                    Ok((state_cell_left, state_cell_right))
+                }).collect()
             }
         )
     }
-}
-
-pub struct MiMC5FeistelCipherPallasChip {
-    config: MiMC5FeistelCipherConfig
-}
 
-impl MiMC5FeistelCipherChip<Fp> for MiMC5FeistelCipherPallasChip {
-    fn construct(config: MiMC5FeistelCipherConfig) -> Self {
-        Self {
-            config,
-        }
-    }
+    // The rounds cannot be inverted by low-degree gates, so decryption
+    // witnesses the plaintext and constrains its encryption to the
+    // ciphertext. The ciphertext's columns must have equality enabled.
+    #[allow(clippy::type_complexity)]
+    pub fn decrypt_message(
+        &self,
+        mut layouter: impl Layouter<F>,
+        ciphertext_left: &AssignedCell<F, F>,
+        ciphertext_right: &AssignedCell<F, F>,
+        key: &AssignedCell<F, F>,
+    ) -> Result<(AssignedCell<F, F>, AssignedCell<F,F>), Error> {
+        let config = self.config();
 
-    fn get_config(&self) -> &MiMC5FeistelCipherConfig {
-        &self.config
-    }
+        let message = ciphertext_left.value().zip(ciphertext_right.value()).zip(key.value()).map(
+            |((left, right), key)| {
+                let (mut state_left, mut state_right) = (*left, *right);
+                F::feistel_decrypt(&mut state_left, &mut state_right, *key);
+                (state_left, state_right)
+            }
+        );
+        let (message_left, message_right) = layouter.assign_region(
+            || "MiMC5 Feistel decryption",
+            |mut region| {
+                let message_left = region.assign_advice(
+                    || "left plaintext",
+                    config.state_left,
+                    0,
+                    || message.map(|message| message.0)
+                )?;
+                let message_right = region.assign_advice(
+                    || "right plaintext",
+                    config.state_right,
+                    0,
+                    || message.map(|message| message.1)
+                )?;
+                Ok((message_left, message_right))
+            }
+        )?;
 
-    fn get_round_constants() -> Vec<Fp> {
-        MIMC_FEISTEL_PALLAS_ROUND_CONSTANTS.to_vec()
+        let (reencrypted_left, reencrypted_right) = self.encrypt_message(
+            layouter.namespace(|| "re-encryption"),
+            &message_left,
+            &message_right,
+            key,
+        )?;
+        layouter.assign_region(
+            || "constrain ciphertext",
+            |mut region| {
+                let ciphertext_left = ciphertext_left.copy_advice(|| "left ciphertext", &mut region, config.state_left, 0)?;
+                let ciphertext_right = ciphertext_right.copy_advice(|| "right ciphertext", &mut region, config.state_right, 0)?;
+                region.constrain_equal(reencrypted_left.cell(), ciphertext_left.cell())?;
+                region.constrain_equal(reencrypted_right.cell(), ciphertext_right.cell())
+            }
+        )?;
+        Ok((message_left, message_right))
     }
 }
 
-pub struct MiMC5FeistelCipherVestaChip {
-    config: MiMC5FeistelCipherConfig
-}
-
-impl MiMC5FeistelCipherChip<Fq> for MiMC5FeistelCipherVestaChip {
-    fn construct(config: MiMC5FeistelCipherConfig) -> Self {
-        Self {
-            config,
-        }
-    }
-
-    fn get_config(&self) -> &MiMC5FeistelCipherConfig {
-        &self.config
-    }
-
-    fn get_round_constants() -> Vec<Fq> {
-        MIMC_FEISTEL_VESTA_ROUND_CONSTANTS.to_vec()
-    }
-}
 
 #[cfg(test)]
 mod tests {
     use crate::mimc_feistel::primitives::{mimc5_feistel_encrypt_pallas, mimc5_feistel_encrypt_vesta};
 
     use super::*;
+    use crate::cost::chip_costs;
     use halo2_proofs::{dev::MockProver, pasta::Fp, plonk::Circuit, circuit::SimpleFloorPlanner};
 
     #[derive(Debug, Clone)]
@@ -376,7 +471,7 @@ mod tests {
  
     #[test]
     fn test_mimc5_feistel_pallas_cipher() {
-        let k = 8;
+        let k = chip_costs().mimc5_feistel_cipher.min_k(1);
 
         let msg_l = Fp::from(1);
         let msg_r = Fp::from(2);
@@ -506,7 +601,7 @@ mod tests {
  
     #[test]
     fn test_mimc5_feistel_vesta_cipher() {
-        let k = 8;
+        let k = chip_costs().mimc5_feistel_cipher.min_k(1);
 
         let msg_l = Fq::from(1);
         let msg_r = Fq::from(2);
@@ -534,7 +629,7 @@ mod tests {
     #[test]
     fn plot_mimc5_feistel_pallas_cipher() {
         use plotters::prelude::*;
-        let k = 8;
+        let k = chip_costs().mimc5_feistel_cipher.min_k(1);
         let root = BitMapBackend::new("mimc5-feistel-pallas-cipher-layout.png", (1024, 3096)).into_drawing_area();
         root.fill(&WHITE).unwrap();
         let root = root.titled("MiMC Feistel Cipher Layout", ("sans-serif", 60)).unwrap();
@@ -1,16 +1,18 @@
 use halo2_proofs::{
-    arithmetic::FieldExt,
     plonk::{
         Column, Advice, Fixed, Selector, ConstraintSystem, Expression, Error,
     },
     poly::Rotation,
     circuit::{
-        Layouter, AssignedCell, Value,
+        Chip, Layouter, AssignedCell, Value,
     },
 };
 use pasta_curves::{Fp, Fq};
+use std::slice;
 
-use super::round_constants::{MIMC_FEISTEL_PALLAS_ROUND_CONSTANTS, MIMC_FEISTEL_VESTA_ROUND_CONSTANTS};
+use super::primitives::mimc5_feistel_hash_trace;
+use crate::field::MiMCField;
+use crate::layout::{ColumnLabels, LabeledConfig};
 
 
 #[allow(unused_variables, dead_code)]
@@ -23,14 +25,70 @@ pub struct MiMC5FeistelHashConfig {
     s_last_round: Selector,
 }
 
-pub trait MiMC5FeistelHashChip<F: FieldExt> {
-    fn construct(config: MiMC5FeistelHashConfig) -> Self;
+impl LabeledConfig for MiMC5FeistelHashConfig {
+    fn labels(&self) -> ColumnLabels {
+        ColumnLabels::default()
+            .column(self.state_left, "state_left")
+            .column(self.state_right, "state_right")
+            .column(self.round_constants, "round constants")
+            .selector(self.s_inner_rounds, "s_inner_rounds")
+            .selector(self.s_last_round, "s_last_round")
+    }
+}
 
-    fn get_round_constants() -> Vec<F>;
+impl MiMC5FeistelHashConfig {
+    pub fn state_left(&self) -> Column<Advice> {
+        self.state_left
+    }
 
-    fn get_config(&self) -> &MiMC5FeistelHashConfig;
+    pub fn state_right(&self) -> Column<Advice> {
+        self.state_right
+    }
 
-    fn configure(
+    pub fn round_constants(&self) -> Column<Fixed> {
+        self.round_constants
+    }
+
+    pub fn s_inner_rounds(&self) -> Selector {
+        self.s_inner_rounds
+    }
+
+    pub fn s_last_round(&self) -> Selector {
+        self.s_last_round
+    }
+}
+
+pub struct MiMC5FeistelHashChip<F: MiMCField> {
+    config: MiMC5FeistelHashConfig,
+    // Round constants loaded once at construction and shared by every invocation
+    round_constants: Vec<F>,
+}
+
+pub type MiMC5FeistelHashPallasChip = MiMC5FeistelHashChip<Fp>;
+pub type MiMC5FeistelHashVestaChip = MiMC5FeistelHashChip<Fq>;
+
+impl<F: MiMCField> Chip<F> for MiMC5FeistelHashChip<F> {
+    type Config = MiMC5FeistelHashConfig;
+    type Loaded = Vec<F>;
+
+    fn config(&self) -> &Self::Config {
+        &self.config
+    }
+
+    fn loaded(&self) -> &Self::Loaded {
+        &self.round_constants
+    }
+}
+
+impl<F: MiMCField> MiMC5FeistelHashChip<F> {
+    pub fn construct(config: MiMC5FeistelHashConfig) -> Self {
+        Self {
+            config,
+            round_constants: F::MIMC5_FEISTEL_ROUND_CONSTANTS.to_vec(),
+        }
+    }
+
+    pub fn configure(
         meta: &mut ConstraintSystem<F>,
         state_left: Column<Advice>,
         state_right: Column<Advice>,
@@ -41,7 +99,6 @@ pub trait MiMC5FeistelHashChip<F: FieldExt> {
 
         meta.enable_equality(state_left);
         meta.enable_equality(state_right);
-        meta.enable_constant(round_constants);
 
         //  state_left                     | state_right                  | round_constants   | selector
         //  xL,0 = xL                      | xR,0 = xR                    |     c0            | 
@@ -90,139 +147,115 @@ pub trait MiMC5FeistelHashChip<F: FieldExt> {
         }
     }
 
-    fn hash_message(
+    #[allow(clippy::type_complexity)]
+    pub fn hash_message(
         &self,
-        mut layouter: impl Layouter<F>,
+        layouter: impl Layouter<F>,
         message_left: &AssignedCell<F, F>,
         message_right: &AssignedCell<F, F>,
     ) -> Result<(AssignedCell<F, F>, AssignedCell<F,F>), Error> {
-        let config = self.get_config();
+        let message = (message_left.clone(), message_right.clone());
+        let mut hashes = self.hash_messages(layouter, slice::from_ref(&message))?;
+        Ok(hashes.remove(0))
+    }
+
+    // Hashes the (left, right) messages in one region, each invocation in the
+    // rows below the previous one. Every row of the region is assigned once,
+    // the round constants of each invocation included.
+    #[allow(clippy::type_complexity)]
+    pub fn hash_messages(
+        &self,
+        mut layouter: impl Layouter<F>,
+        messages: &[(AssignedCell<F, F>, AssignedCell<F, F>)],
+    ) -> Result<Vec<(AssignedCell<F, F>, AssignedCell<F,F>)>, Error> {
+        let config = self.config();
+
+        let round_constant_values = self.loaded();
+        let rounds = round_constant_values.len();
+
+        let traces: Vec<_> = messages
+            .iter()
+            .map(|(left, right)| left.value().zip(right.value()).map(|(left, right)| {
+                mimc5_feistel_hash_trace(*left, *right, round_constant_values)
+            }))
+            .collect();
 
-        let round_constant_values = Self::get_round_constants();
         layouter.assign_region(
             || "MiMC5 Feistel table",
             |mut region| {
+                messages.iter().zip(traces.iter()).enumerate().map(|(invocation, ((message_left, message_right), trace))| {
+                    let offset = invocation * (rounds + 1);
 
-                region.assign_advice(
-                    || "left part of message to be hashed",
-                    config.state_left,
-                    0,
-                    || message_left.value().copied(),
-                )?;
-
-                region.assign_advice(
-                    || "right part of message to be hashed",
-                    config.state_right,
-                    0,
-                    || message_right.value().copied(),
-                )?;
-
-                let pow_5 = |v: Value<F>| { v*v*v*v*v };
-
-                let mut current_state_left = message_left.value().copied();
-                let mut current_state_right = message_right.value().copied();
-
-                let state_cell_left;
-                let state_cell_right;
-
-                for i in 1..round_constant_values.len() { // i goes from 1 to 219
-                    config.s_inner_rounds.enable(&mut region, i)?;
-                    region.assign_fixed(
-                        || format!("round constant {:?}", i),
-                        config.round_constants,
-                        i-1,
-                        || Value::known(round_constant_values[i-1]) // i starts at 1
+                    message_left.copy_advice(
+                        || "left part of message to be hashed",
+                        &mut region,
+                        config.state_left,
+                        offset,
+                    )?;
+
+                    message_right.copy_advice(
+                        || "right part of message to be hashed",
+                        &mut region,
+                        config.state_right,
+                        offset,
                     )?;
 
-                    let temp = current_state_right + pow_5(current_state_left + Value::known(round_constant_values[i-1]));
-                    current_state_right = current_state_left;
-                    current_state_left = temp;
-                    
+                    for i in 1..rounds { // i goes from 1 to 219
+                        config.s_inner_rounds.enable(&mut region, offset+i)?;
+                        region.assign_fixed(
+                            || format!("round constant {:?}", i-1),
+                            config.round_constants,
+                            offset+i-1,
+                            || Value::known(round_constant_values[i-1]) // i starts at 1
+                        )?;
+
+                        region.assign_advice(
+                            || format!("round {:?} output on the left", i),
+                            config.state_left,
+                            offset+i,
+                            || trace.as_ref().map(|trace| trace[i].0)
+                        )?;
+
+                        region.assign_advice(
+                            || format!("round {:?} output on the right", i),
+                            config.state_right,
+                            offset+i,
+                            || trace.as_ref().map(|trace| trace[i].1)
+                        )?;
+                    }
+
+                    config.s_last_round.enable(&mut region, offset+rounds)?;
+                    let state_cell_left =
                     region.assign_advice(
-                        || format!("round {:?} output on the left", i),
+                        || "last round output on the left",
                         config.state_left,
-                        i,
-                        || current_state_left
+                        offset+rounds,
+                        || trace.as_ref().map(|trace| trace[rounds].0)
                     )?;
-
+                    let state_cell_right =
                     region.assign_advice(
-                        || format!("round {:?} output on the right", i),
+                        || "last round output on the right",
                         config.state_right,
-                        i,
-                        || current_state_right
+                        offset+rounds,
+                        || trace.as_ref().map(|trace| trace[rounds].1)
                     )?;
-                }
 
-                config.s_last_round.enable(&mut region, round_constant_values.len())?;
-                current_state_right = current_state_right + pow_5(current_state_left);
-                state_cell_left =
-                region.assign_advice(
-                    || "last round output on the left",
-                    config.state_left,
-                    round_constant_values.len(),
-                    || current_state_left
-                )?;
-                state_cell_right =
-                region.assign_advice(
-                    || "last round output on the right",
-                    config.state_right,
-                    round_constant_values.len(),
-                    || current_state_right
-                )?;
-
-                // The left output is unchanged in the last round
-
-                Ok((state_cell_left, state_cell_right))
+                    // The left output is unchanged in the last round
+
+                    Ok((state_cell_left, state_cell_right))
+                }).collect()
             }
         )
     }
 }
 
-pub struct MiMC5FeistelHashPallasChip {
-    config: MiMC5FeistelHashConfig
-}
-
-impl MiMC5FeistelHashChip<Fp> for MiMC5FeistelHashPallasChip {
-    fn construct(config: MiMC5FeistelHashConfig) -> Self {
-        Self {
-            config,
-        }
-    }
-
-    fn get_config(&self) -> &MiMC5FeistelHashConfig {
-        &self.config
-    }
-
-    fn get_round_constants() -> Vec<Fp> {
-        MIMC_FEISTEL_PALLAS_ROUND_CONSTANTS.to_vec()
-    }
-}
-
-pub struct MiMC5FeistelHashVestaChip {
-    config: MiMC5FeistelHashConfig
-}
-
-impl MiMC5FeistelHashChip<Fq> for MiMC5FeistelHashVestaChip {
-    fn construct(config: MiMC5FeistelHashConfig) -> Self {
-        Self {
-            config,
-        }
-    }
-
-    fn get_config(&self) -> &MiMC5FeistelHashConfig {
-        &self.config
-    }
-
-    fn get_round_constants() -> Vec<Fq> {
-        MIMC_FEISTEL_VESTA_ROUND_CONSTANTS.to_vec()
-    }
-}
 
 #[cfg(test)]
 mod tests {
     use crate::mimc_feistel::primitives::{mimc5_feistel_hash_pallas, mimc5_feistel_hash_vesta};
 
     use super::*;
+    use crate::cost::chip_costs;
     use halo2_proofs::{dev::MockProver, pasta::Fp, plonk::Circuit, circuit::SimpleFloorPlanner};
 
     #[derive(Debug, Clone)]
@@ -323,7 +356,7 @@ mod tests {
  
     #[test]
     fn test_mimc5_feistel_pallas_hash() {
-        let k = 8;
+        let k = chip_costs().mimc5_feistel_hash.min_k(1);
 
         let msg_l = Fp::from(1);
         let msg_r = Fp::from(2);
@@ -435,7 +468,7 @@ mod tests {
  
     #[test]
     fn test_mimc5_feistel_vesta_hash() {
-        let k = 8;
+        let k = chip_costs().mimc5_feistel_hash.min_k(1);
 
         let msg_l = Fq::from(1);
         let msg_r = Fq::from(2);
@@ -460,7 +493,7 @@ mod tests {
     #[test]
     fn plot_mimc5_feistel_pallas_hash() {
         use plotters::prelude::*;
-        let k = 8;
+        let k = chip_costs().mimc5_feistel_hash.min_k(1);
         let root = BitMapBackend::new("mimc5-feistel-pallas-hash-layout.png", (1024, 3096)).into_drawing_area();
         root.fill(&WHITE).unwrap();
         let root = root.titled("MiMC Feistel Hash Layout", ("sans-serif", 60)).unwrap();
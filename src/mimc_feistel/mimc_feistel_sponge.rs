@@ -0,0 +1,363 @@
+use halo2_proofs::{
+    plonk::{
+        Column, Advice, Fixed, Selector, ConstraintSystem, Error,
+    },
+    poly::Rotation,
+    circuit::{
+        Chip, Layouter, AssignedCell,
+    },
+};
+use pasta_curves::{Fp, Fq};
+
+use super::mimc_feistel_hash::{
+    MiMC5FeistelHashConfig, MiMC5FeistelHashChip,
+};
+use crate::field::MiMCField;
+use crate::layout::{ColumnLabels, LabeledConfig};
+
+
+#[derive(Debug, Clone)]
+pub struct MiMCSpongeConfig {
+    hash_config: MiMC5FeistelHashConfig,
+    s_absorb: Selector,
+}
+
+impl LabeledConfig for MiMCSpongeConfig {
+    fn labels(&self) -> ColumnLabels {
+        self.hash_config.labels().selector(self.s_absorb, "s_absorb")
+    }
+}
+
+impl MiMCSpongeConfig {
+    pub fn hash_config(&self) -> &MiMC5FeistelHashConfig {
+        &self.hash_config
+    }
+
+    pub fn s_absorb(&self) -> Selector {
+        self.s_absorb
+    }
+}
+
+// The (left, right) halves of the sponge state
+type SpongeState<F> = (AssignedCell<F, F>, AssignedCell<F, F>);
+
+// In-circuit counterpart of the native `MiMCSponge`: absorbs assigned cells
+// and squeezes challenge cells with the same permutation calls in the same
+// order. Constructed with `TRANSCRIPT_DOMAIN` it mirrors `MiMCWrite` and
+// `MiMCRead`, given cells holding the encodings from
+// `point_to_base_elements` and `scalar_to_base_elements`.
+pub struct MiMCSpongeChip<F: MiMCField> {
+    config: MiMCSpongeConfig,
+    hash_chip: MiMC5FeistelHashChip<F>,
+    domain: u64,
+    // Assigned on first use
+    state: Option<SpongeState<F>>,
+    // Set while an absorb or squeeze is under way, and left set if it fails.
+    // A failed call may have assigned part of its regions, so the chip no
+    // longer follows the native sponge and refuses further calls.
+    poisoned: bool,
+}
+
+pub type MiMCSpongePallasChip = MiMCSpongeChip<Fp>;
+pub type MiMCSpongeVestaChip = MiMCSpongeChip<Fq>;
+
+// The loaded values are the round constants of the underlying hash chip
+impl<F: MiMCField> Chip<F> for MiMCSpongeChip<F> {
+    type Config = MiMCSpongeConfig;
+    type Loaded = Vec<F>;
+
+    fn config(&self) -> &Self::Config {
+        &self.config
+    }
+
+    fn loaded(&self) -> &Self::Loaded {
+        self.hash_chip.loaded()
+    }
+}
+
+impl<F: MiMCField> MiMCSpongeChip<F> {
+    pub fn construct(config: MiMCSpongeConfig, domain: u64) -> Self {
+        let hash_chip = MiMC5FeistelHashChip::construct(config.hash_config.clone());
+        Self {
+            config,
+            hash_chip,
+            domain,
+            state: None,
+            poisoned: false,
+        }
+    }
+
+    pub fn configure(
+        meta: &mut ConstraintSystem<F>,
+        state_left: Column<Advice>,
+        state_right: Column<Advice>,
+        round_constants: Column<Fixed>,
+        constants: Column<Fixed>,
+    ) -> MiMCSpongeConfig {
+        let hash_config = MiMC5FeistelHashChip::configure(meta, state_left, state_right, round_constants);
+        meta.enable_constant(constants);
+        let s_absorb = meta.selector();
+
+        //  state_left  | state_right | selector
+        //  left        | value       |
+        //  left+value  |             | s_absorb
+        meta.create_gate("MiMC sponge absorb", |meta| {
+            let s = meta.query_selector(s_absorb);
+            let prev_state_left = meta.query_advice(state_left, Rotation::prev());
+            let value = meta.query_advice(state_right, Rotation::prev());
+            let current_state_left = meta.query_advice(state_left, Rotation::cur());
+            vec![s * (current_state_left - prev_state_left - value)]
+        });
+
+        MiMCSpongeConfig {
+            hash_config,
+            s_absorb,
+        }
+    }
+
+    fn initial_state(
+        &mut self,
+        layouter: &mut impl Layouter<F>,
+    ) -> Result<SpongeState<F>, Error> {
+        if let Some(state) = &self.state {
+            return Ok(state.clone());
+        }
+        let config = &self.config;
+        let domain = F::from(self.domain);
+        layouter.assign_region(
+            || "MiMC sponge initial state",
+            |mut region| {
+                let state_left = region.assign_advice_from_constant(
+                    || "initial left state",
+                    config.hash_config.state_left(),
+                    0,
+                    F::zero(),
+                )?;
+                let state_right = region.assign_advice_from_constant(
+                    || "initial right state (domain)",
+                    config.hash_config.state_right(),
+                    0,
+                    domain,
+                )?;
+                Ok((state_left, state_right))
+            },
+        )
+    }
+
+    fn begin(&mut self) -> Result<(), Error> {
+        if self.poisoned {
+            return Err(Error::Synthesis);
+        }
+        self.poisoned = true;
+        Ok(())
+    }
+
+    pub fn absorb(
+        &mut self,
+        mut layouter: impl Layouter<F>,
+        value: &AssignedCell<F, F>,
+    ) -> Result<(), Error> {
+        self.begin()?;
+        let (state_left, state_right) = self.initial_state(&mut layouter)?;
+        let config = &self.config;
+        let sum = layouter.assign_region(
+            || "MiMC sponge absorb",
+            |mut region| {
+                let state_left = state_left.copy_advice(
+                    || "left state",
+                    &mut region,
+                    config.hash_config.state_left(),
+                    0,
+                )?;
+                value.copy_advice(|| "absorbed value", &mut region, config.hash_config.state_right(), 0)?;
+                config.s_absorb.enable(&mut region, 1)?;
+                region.assign_advice(
+                    || "left state plus absorbed value",
+                    config.hash_config.state_left(),
+                    1,
+                    || state_left.value().copied() + value.value(),
+                )
+            },
+        )?;
+        let state = self.hash_chip.hash_message(layouter.namespace(|| "permutation"), &sum, &state_right)?;
+        self.state = Some(state);
+        self.poisoned = false;
+        Ok(())
+    }
+
+    pub fn squeeze(&mut self, mut layouter: impl Layouter<F>) -> Result<AssignedCell<F, F>, Error> {
+        self.begin()?;
+        let (state_left, state_right) = self.initial_state(&mut layouter)?;
+        let state = self.hash_chip.hash_message(layouter.namespace(|| "permutation"), &state_left, &state_right)?;
+        let challenge = state.0.clone();
+        self.state = Some(state);
+        self.poisoned = false;
+        Ok(challenge)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use halo2_proofs::{
+        circuit::{SimpleFloorPlanner, Value},
+        dev::MockProver,
+        plonk::{Circuit, Instance},
+        transcript::{Transcript, TranscriptWrite},
+    };
+    use pasta_curves::group::prime::PrimeCurveAffine;
+    use pasta_curves::pallas;
+
+    use crate::mimc_feistel::sponge::MiMCSponge;
+    use crate::transcript::{point_to_base_elements, scalar_to_base_elements, MiMCWrite, TRANSCRIPT_DOMAIN};
+
+    #[derive(Debug, Clone)]
+    struct MiMCSpongeCircuitConfig {
+        input: Column<Advice>,
+        instance: Column<Instance>,
+        sponge_config: MiMCSpongeConfig,
+    }
+
+    // Absorbs `absorbed[i]` in order, squeezing once after each index listed
+    // in `squeeze_after`, and exposes the squeezed cells as public inputs
+    #[derive(Default)]
+    struct MiMCSpongeCircuit<F: MiMCField> {
+        absorbed: Vec<F>,
+        squeeze_after: Vec<usize>,
+    }
+
+    impl<F: MiMCField> Circuit<F> for MiMCSpongeCircuit<F> {
+        type Config = MiMCSpongeCircuitConfig;
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self {
+                absorbed: vec![F::zero(); self.absorbed.len()],
+                squeeze_after: self.squeeze_after.clone(),
+            }
+        }
+
+        fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+            let input = meta.advice_column();
+            meta.enable_equality(input);
+            let instance = meta.instance_column();
+            meta.enable_equality(instance);
+            let state_left = meta.advice_column();
+            let state_right = meta.advice_column();
+            let round_constants = meta.fixed_column();
+            let constants = meta.fixed_column();
+            Self::Config {
+                input,
+                instance,
+                sponge_config: MiMCSpongeChip::<F>::configure(meta, state_left, state_right, round_constants, constants),
+            }
+        }
+
+        fn synthesize(&self, config: Self::Config, mut layouter: impl Layouter<F>) -> Result<(), Error> {
+            let mut chip = MiMCSpongeChip::<F>::construct(config.sponge_config, TRANSCRIPT_DOMAIN);
+            let mut challenges = vec![];
+            for (i, value) in self.absorbed.iter().enumerate() {
+                let cell = layouter.assign_region(
+                    || "load absorbed value",
+                    |mut region| region.assign_advice(|| "absorbed value", config.input, 0, || Value::known(*value)),
+                )?;
+                chip.absorb(layouter.namespace(|| format!("absorb {}", i)), &cell)?;
+                if self.squeeze_after.contains(&i) {
+                    challenges.push(chip.squeeze(layouter.namespace(|| format!("squeeze after {}", i)))?);
+                }
+            }
+            for (row, challenge) in challenges.iter().enumerate() {
+                layouter.constrain_instance(challenge.cell(), config.instance, row)?;
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_mimc_sponge_chip_matches_native_sponge() {
+        let k = 11;
+        let absorbed: Vec<Fp> = (1..=4).map(Fp::from).collect();
+        let mut sponge = MiMCSponge::<Fp>::new(TRANSCRIPT_DOMAIN);
+        let mut challenges = vec![];
+        for (i, value) in absorbed.iter().enumerate() {
+            sponge.absorb(*value);
+            if i == 1 || i == 3 {
+                challenges.push(sponge.squeeze());
+            }
+        }
+
+        let circuit = MiMCSpongePallasCircuit {
+            absorbed,
+            squeeze_after: vec![1, 3],
+        };
+        let prover = MockProver::run(k, &circuit, vec![challenges.clone()]).unwrap();
+        prover.assert_satisfied();
+
+        challenges[0] += Fp::one();
+        let prover = MockProver::run(k, &circuit, vec![challenges]).unwrap();
+        assert!(prover.verify().is_err());
+    }
+
+    #[test]
+    fn test_mimc_sponge_chip_matches_native_transcript() {
+        // A point and a scalar written to a native transcript over Pallas
+        // commitments, whose base field Fp is the field of the circuit
+        let k = 11;
+        let point = pallas::Affine::generator();
+        let scalar = Fq::from(5);
+        let mut transcript = MiMCWrite::<_, pallas::Affine>::init(vec![]);
+        transcript.write_point(point).unwrap();
+        let first_challenge = transcript.squeeze_challenge().base();
+        transcript.write_scalar(scalar).unwrap();
+        let second_challenge = transcript.squeeze_challenge().base();
+
+        let mut absorbed = point_to_base_elements(point).unwrap().to_vec();
+        absorbed.extend(scalar_to_base_elements::<pallas::Affine>(scalar));
+        let circuit = MiMCSpongePallasCircuit {
+            absorbed,
+            squeeze_after: vec![1, 3],
+        };
+        let prover = MockProver::run(k, &circuit, vec![vec![first_challenge, second_challenge]]).unwrap();
+        prover.assert_satisfied();
+    }
+
+    type MiMCSpongePallasCircuit = MiMCSpongeCircuit<Fp>;
+
+    // Absorbs a cell whose column has no equality constraints, which fails,
+    // then a valid cell
+    #[derive(Default)]
+    struct MiMCSpongeFailedAbsorbCircuit;
+
+    impl Circuit<Fp> for MiMCSpongeFailedAbsorbCircuit {
+        type Config = (MiMCSpongeCircuitConfig, Column<Advice>);
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+            let no_equality = meta.advice_column();
+            (MiMCSpongePallasCircuit::configure(meta), no_equality)
+        }
+
+        fn synthesize(&self, (config, no_equality): Self::Config, mut layouter: impl Layouter<Fp>) -> Result<(), Error> {
+            let mut chip = MiMCSpongePallasChip::construct(config.sponge_config, TRANSCRIPT_DOMAIN);
+            let (uncopyable, value) = layouter.assign_region(
+                || "load absorbed values",
+                |mut region| {
+                    let uncopyable = region.assign_advice(|| "uncopyable", no_equality, 0, || Value::known(Fp::one()))?;
+                    let value = region.assign_advice(|| "absorbed value", config.input, 0, || Value::known(Fp::one()))?;
+                    Ok((uncopyable, value))
+                },
+            )?;
+            assert!(chip.absorb(layouter.namespace(|| "failed absorb"), &uncopyable).is_err());
+            chip.absorb(layouter.namespace(|| "absorb"), &value)
+        }
+    }
+
+    #[test]
+    fn test_mimc_sponge_chip_fails_after_failed_absorb() {
+        assert!(MockProver::run(11, &MiMCSpongeFailedAbsorbCircuit, vec![vec![]]).is_err());
+    }
+}
@@ -1,4 +1,6 @@
 pub mod mimc_feistel_hash;
 pub mod mimc_feistel_cipher;
-mod round_constants;
+pub(crate) mod round_constants;
 pub mod primitives;
+pub mod sponge;
+pub mod mimc_feistel_sponge;
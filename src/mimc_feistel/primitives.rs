@@ -1,45 +1,173 @@
 use halo2_proofs::arithmetic::FieldExt;
 use pasta_curves::{Fp, Fq};
-use crate::mimc_feistel::round_constants::NUM_ROUNDS;
+use rayon::prelude::*;
+use crate::mimc::primitives::{BATCH_CHUNK, BATCH_LANES};
 use crate::mimc_feistel::round_constants::{MIMC_FEISTEL_PALLAS_ROUND_CONSTANTS, MIMC_FEISTEL_VESTA_ROUND_CONSTANTS};
 
-pub fn mimc5_feistel_encrypt<F: FieldExt, const ROUNDS: usize>(
+pub fn mimc5_feistel_encrypt<F: FieldExt>(
     state_l: &mut F,
     state_r: &mut F,
     key: F,
-    round_constants: [F; ROUNDS],
+    round_constants: &[F],
 ) {
     let pow_5 = |v: F| { v*v*v*v*v };
 
-    for i in 0..ROUNDS-1 {
-        let new_state_l = *state_r + pow_5(*state_l + key + round_constants[i]);
+    for c in round_constants.iter().take(round_constants.len() - 1) {
+        let new_state_l = *state_r + pow_5(*state_l + key + c);
         let new_state_r = *state_l;
         *state_l = new_state_l;
         *state_r = new_state_r;
     }
-    *state_r = *state_r + pow_5(*state_l + key);
+    *state_r += pow_5(*state_l + key);
 }
 
-pub fn mimc5_feistel_hash<F: FieldExt, const ROUNDS: usize>(
+// Every (left, right) state of mimc5_feistel_encrypt: the message followed
+// by the output of each round, the last one being the ciphertext
+pub fn mimc5_feistel_encrypt_trace<F: FieldExt>(
+    message_l: F,
+    message_r: F,
+    key: F,
+    round_constants: &[F],
+) -> Vec<(F, F)> {
+    let pow_5 = |v: F| { v*v*v*v*v };
+
+    let mut trace = Vec::with_capacity(round_constants.len() + 1);
+    let (mut state_l, mut state_r) = (message_l, message_r);
+    trace.push((state_l, state_r));
+    for c in round_constants.iter().take(round_constants.len() - 1) {
+        let new_state_l = state_r + pow_5(state_l + key + c);
+        state_r = state_l;
+        state_l = new_state_l;
+        trace.push((state_l, state_r));
+    }
+    state_r += pow_5(state_l + key);
+    trace.push((state_l, state_r));
+    trace
+}
+
+// Every (left, right) state of mimc5_feistel_hash
+pub fn mimc5_feistel_hash_trace<F: FieldExt>(
+    message_l: F,
+    message_r: F,
+    round_constants: &[F],
+) -> Vec<(F, F)> {
+    mimc5_feistel_encrypt_trace(message_l, message_r, F::zero(), round_constants)
+}
+
+// Inverts mimc5_feistel_encrypt by undoing the final half-round and then
+// running the Feistel rounds backwards
+pub fn mimc5_feistel_decrypt<F: FieldExt>(
+    state_l: &mut F,
+    state_r: &mut F,
+    key: F,
+    round_constants: &[F],
+) {
+    let pow_5 = |v: F| { v*v*v*v*v };
+
+    *state_r -= pow_5(*state_l + key);
+    for c in round_constants.iter().take(round_constants.len() - 1).rev() {
+        let new_state_l = *state_r;
+        let new_state_r = *state_l - pow_5(new_state_l + key + c);
+        *state_l = new_state_l;
+        *state_r = new_state_r;
+    }
+}
+
+pub fn mimc5_feistel_hash<F: FieldExt>(
     state_l: &mut F,
     state_r: &mut F,
-    round_constants: [F; ROUNDS],
+    round_constants: &[F],
 ) {
     mimc5_feistel_encrypt(state_l, state_r, F::zero(), round_constants);
 }
 
+// Encrypts exactly LANES (left, right) messages with their keys, or hashes
+// them if there are no keys, a round at a time across all of them
+fn mimc5_feistel_encrypt_lanes<F: FieldExt, const LANES: usize>(
+    states: &mut [(F, F)],
+    keys: Option<&[F]>,
+    round_constants: &[F],
+) {
+    let mut lanes = [(F::zero(), F::zero()); LANES];
+    let mut lane_keys = [F::zero(); LANES];
+    lanes.copy_from_slice(states);
+    if let Some(keys) = keys {
+        lane_keys.copy_from_slice(keys);
+    }
+
+    for c in round_constants.iter().take(round_constants.len() - 1) {
+        for ((state_l, state_r), key) in lanes.iter_mut().zip(lane_keys.iter()) {
+            let x = *state_l + key + c;
+            let new_state_l = *state_r + x.square().square() * x;
+            *state_r = *state_l;
+            *state_l = new_state_l;
+        }
+    }
+    for ((state_l, state_r), key) in lanes.iter_mut().zip(lane_keys.iter()) {
+        let x = *state_l + key;
+        *state_r += x.square().square() * x;
+    }
+    states.copy_from_slice(&lanes);
+}
+
+// Runs a group of at most BATCH_LANES messages with one lane per message, so
+// a short tail does no work for empty lanes
+fn mimc5_feistel_encrypt_interleaved<F: FieldExt>(
+    states: &mut [(F, F)],
+    keys: Option<&[F]>,
+    round_constants: &[F],
+) {
+    match states.len() {
+        BATCH_LANES => mimc5_feistel_encrypt_lanes::<F, BATCH_LANES>(states, keys, round_constants),
+        3 => mimc5_feistel_encrypt_lanes::<F, 3>(states, keys, round_constants),
+        2 => mimc5_feistel_encrypt_lanes::<F, 2>(states, keys, round_constants),
+        1 => mimc5_feistel_encrypt_lanes::<F, 1>(states, keys, round_constants),
+        _ => unreachable!("groups hold 1 to BATCH_LANES messages"),
+    }
+}
+
+// Hashes each (left, right) element of `states` in place, in parallel
+pub fn mimc5_feistel_hash_batch<F: FieldExt>(
+    states: &mut [(F, F)],
+    round_constants: &[F],
+) {
+    states.par_chunks_mut(BATCH_CHUNK).for_each(|chunk| {
+        for lanes in chunk.chunks_mut(BATCH_LANES) {
+            mimc5_feistel_encrypt_interleaved(lanes, None, round_constants);
+        }
+    });
+}
+
+// Encrypts each (left, right) element of `states` in place under the key at
+// the same index, in parallel
+pub fn mimc5_feistel_encrypt_batch<F: FieldExt>(
+    states: &mut [(F, F)],
+    keys: &[F],
+    round_constants: &[F],
+) {
+    assert_eq!(states.len(), keys.len(), "one key is needed per message");
+    states
+        .par_chunks_mut(BATCH_CHUNK)
+        .zip(keys.par_chunks(BATCH_CHUNK))
+        .for_each(|(chunk, chunk_keys)| {
+            for (lanes, lane_keys) in chunk.chunks_mut(BATCH_LANES).zip(chunk_keys.chunks(BATCH_LANES)) {
+                mimc5_feistel_encrypt_interleaved(lanes, Some(lane_keys), round_constants);
+            }
+        });
+}
+
 pub fn mimc5_feistel_hash_pallas(
     state_l: &mut Fp,
     state_r: &mut Fp,
 ) {
-    mimc5_feistel_hash::<Fp, NUM_ROUNDS>(state_l, state_r, MIMC_FEISTEL_PALLAS_ROUND_CONSTANTS);
+    mimc5_feistel_hash(state_l, state_r, &MIMC_FEISTEL_PALLAS_ROUND_CONSTANTS);
 }
 
 pub fn mimc5_feistel_hash_vesta(
     state_l: &mut Fq,
     state_r: &mut Fq,
 ) {
-    mimc5_feistel_hash::<Fq, NUM_ROUNDS>(state_l, state_r, MIMC_FEISTEL_VESTA_ROUND_CONSTANTS);
+    mimc5_feistel_hash(state_l, state_r, &MIMC_FEISTEL_VESTA_ROUND_CONSTANTS);
 }
 
 pub fn mimc5_feistel_encrypt_pallas(
@@ -47,7 +175,7 @@ pub fn mimc5_feistel_encrypt_pallas(
     state_r: &mut Fp,
     key: Fp,
 ) {
-    mimc5_feistel_encrypt::<Fp, NUM_ROUNDS>(state_l, state_r, key, MIMC_FEISTEL_PALLAS_ROUND_CONSTANTS);
+    mimc5_feistel_encrypt(state_l, state_r, key, &MIMC_FEISTEL_PALLAS_ROUND_CONSTANTS);
 }
 
 pub fn mimc5_feistel_encrypt_vesta(
@@ -55,15 +183,62 @@ pub fn mimc5_feistel_encrypt_vesta(
     state_r: &mut Fq,
     key: Fq,
 ) {
-    mimc5_feistel_encrypt::<Fq, NUM_ROUNDS>(state_l, state_r, key, MIMC_FEISTEL_VESTA_ROUND_CONSTANTS);
+    mimc5_feistel_encrypt(state_l, state_r, key, &MIMC_FEISTEL_VESTA_ROUND_CONSTANTS);
+}
+
+pub fn mimc5_feistel_decrypt_pallas(
+    state_l: &mut Fp,
+    state_r: &mut Fp,
+    key: Fp,
+) {
+    mimc5_feistel_decrypt(state_l, state_r, key, &MIMC_FEISTEL_PALLAS_ROUND_CONSTANTS);
+}
+
+pub fn mimc5_feistel_decrypt_vesta(
+    state_l: &mut Fq,
+    state_r: &mut Fq,
+    key: Fq,
+) {
+    mimc5_feistel_decrypt(state_l, state_r, key, &MIMC_FEISTEL_VESTA_ROUND_CONSTANTS);
+}
+
+pub fn mimc5_feistel_hash_batch_pallas(
+    states: &mut [(Fp, Fp)],
+) {
+    mimc5_feistel_hash_batch(states, &MIMC_FEISTEL_PALLAS_ROUND_CONSTANTS);
+}
+
+pub fn mimc5_feistel_hash_batch_vesta(
+    states: &mut [(Fq, Fq)],
+) {
+    mimc5_feistel_hash_batch(states, &MIMC_FEISTEL_VESTA_ROUND_CONSTANTS);
+}
+
+pub fn mimc5_feistel_encrypt_batch_pallas(
+    states: &mut [(Fp, Fp)],
+    keys: &[Fp],
+) {
+    mimc5_feistel_encrypt_batch(states, keys, &MIMC_FEISTEL_PALLAS_ROUND_CONSTANTS);
+}
+
+pub fn mimc5_feistel_encrypt_batch_vesta(
+    states: &mut [(Fq, Fq)],
+    keys: &[Fq],
+) {
+    mimc5_feistel_encrypt_batch(states, keys, &MIMC_FEISTEL_VESTA_ROUND_CONSTANTS);
 }
 
 #[cfg(test)]
 mod tests {
     use super::{
         mimc5_feistel_hash_pallas, mimc5_feistel_hash_vesta,
-        mimc5_feistel_encrypt_pallas, mimc5_feistel_encrypt_vesta
+        mimc5_feistel_encrypt_pallas, mimc5_feistel_encrypt_vesta,
+        mimc5_feistel_decrypt_pallas, mimc5_feistel_decrypt_vesta,
+        mimc5_feistel_hash_trace, mimc5_feistel_encrypt_trace,
+        mimc5_feistel_hash_batch_pallas, mimc5_feistel_encrypt_batch_vesta,
     };
+    use crate::field::MiMCField;
+    use crate::mimc_feistel::round_constants::NUM_ROUNDS;
     use pasta_curves::{pallas, vesta};
 
     #[test]
@@ -177,4 +352,73 @@ mod tests {
         assert_eq!(vesta_expected_ciphertext_l, vesta_output_l, "Checking equality of left outputs");
         assert_eq!(vesta_expected_ciphertext_r, vesta_output_r, "Checking equality of right outputs");
     }
+
+    #[test]
+    fn test_mimc5_feistel_decrypt_primitives () {
+        let pallas_key = pallas::Base::from(3);
+        let mut pallas_output_l = pallas::Base::from(1);
+        let mut pallas_output_r = pallas::Base::from(2);
+        mimc5_feistel_encrypt_pallas(&mut pallas_output_l, &mut pallas_output_r, pallas_key);
+        mimc5_feistel_decrypt_pallas(&mut pallas_output_l, &mut pallas_output_r, pallas_key);
+        assert_eq!((pallas::Base::from(1), pallas::Base::from(2)), (pallas_output_l, pallas_output_r));
+
+        let vesta_key = vesta::Base::from(3);
+        let mut vesta_output_l = vesta::Base::from(1);
+        let mut vesta_output_r = vesta::Base::from(2);
+        mimc5_feistel_encrypt_vesta(&mut vesta_output_l, &mut vesta_output_r, vesta_key);
+        mimc5_feistel_decrypt_vesta(&mut vesta_output_l, &mut vesta_output_r, vesta_key);
+        assert_eq!((vesta::Base::from(1), vesta::Base::from(2)), (vesta_output_l, vesta_output_r));
+    }
+
+    #[test]
+    fn test_mimc5_feistel_batch_primitives () {
+        // Sizes around the lane and chunk boundaries, including every tail length
+        for n in [0, 1, 2, 3, 4, 7, 1000] {
+            let messages: Vec<(pallas::Base, pallas::Base)> =
+                (0..n).map(|i| (pallas::Base::from(i), pallas::Base::from(i + 1))).collect();
+            let mut batch = messages.clone();
+            mimc5_feistel_hash_batch_pallas(&mut batch);
+            for (message, output) in messages.iter().zip(batch.iter()) {
+                let (mut expected_l, mut expected_r) = *message;
+                mimc5_feistel_hash_pallas(&mut expected_l, &mut expected_r);
+                assert_eq!((expected_l, expected_r), *output);
+            }
+
+            let messages: Vec<(vesta::Base, vesta::Base)> =
+                (0..n).map(|i| (vesta::Base::from(i), vesta::Base::from(i + 1))).collect();
+            let keys: Vec<vesta::Base> = (0..n).map(|i| vesta::Base::from(i + 7)).collect();
+            let mut batch = messages.clone();
+            mimc5_feistel_encrypt_batch_vesta(&mut batch, &keys);
+            for ((message, key), output) in messages.iter().zip(keys.iter()).zip(batch.iter()) {
+                let (mut expected_l, mut expected_r) = *message;
+                mimc5_feistel_encrypt_vesta(&mut expected_l, &mut expected_r, *key);
+                assert_eq!((expected_l, expected_r), *output);
+            }
+        }
+    }
+
+    #[test]
+    fn test_mimc5_feistel_trace_primitives () {
+        let trace = mimc5_feistel_hash_trace(
+            pallas::Base::from(1),
+            pallas::Base::from(2),
+            pallas::Base::MIMC5_FEISTEL_ROUND_CONSTANTS,
+        );
+        assert_eq!(trace.len(), NUM_ROUNDS + 1);
+        assert_eq!(trace[0], (pallas::Base::from(1), pallas::Base::from(2)));
+        let (mut hash_l, mut hash_r) = (pallas::Base::from(1), pallas::Base::from(2));
+        mimc5_feistel_hash_pallas(&mut hash_l, &mut hash_r);
+        assert_eq!(trace[NUM_ROUNDS], (hash_l, hash_r));
+
+        let trace = mimc5_feistel_encrypt_trace(
+            vesta::Base::from(1),
+            vesta::Base::from(2),
+            vesta::Base::from(3),
+            vesta::Base::MIMC5_FEISTEL_ROUND_CONSTANTS,
+        );
+        let (mut ciphertext_l, mut ciphertext_r) = (vesta::Base::from(1), vesta::Base::from(2));
+        mimc5_feistel_encrypt_vesta(&mut ciphertext_l, &mut ciphertext_r, vesta::Base::from(3));
+        assert_eq!(trace.len(), NUM_ROUNDS + 1);
+        assert_eq!(trace[NUM_ROUNDS], (ciphertext_l, ciphertext_r));
+    }
 }
\ No newline at end of file
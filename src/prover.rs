@@ -0,0 +1,368 @@
+use std::{fmt, io, marker::PhantomData};
+
+use halo2_proofs::{
+    arithmetic::{CurveAffine, FieldExt},
+    plonk::{
+        create_proof, keygen_pk, keygen_vk, verify_proof, BatchVerifier, Circuit, Error,
+        ProvingKey, SingleVerifier, VerifyingKey,
+    },
+    poly::commitment::Params,
+    transcript::{Blake2bRead, Blake2bWrite, Challenge255},
+};
+use pasta_curves::{pallas, vesta};
+use rand_core::{OsRng, RngCore};
+
+use crate::artifacts::{
+    keygen_pk_pinned, keygen_vk_pinned, read_params, write_key_pin, write_params, ArtifactError,
+};
+use crate::circuits::{
+    mimc_hash::{MiMC5HashCircuit, MiMC5HashPallasCircuit, MiMC5HashVestaCircuit},
+    mimc_cipher::{MiMC5CipherCircuit, MiMC5CipherPallasCircuit, MiMC5CipherVestaCircuit},
+    mimc_feistel_hash::{
+        MiMC5FeistelHashCircuit, MiMC5FeistelHashPallasCircuit, MiMC5FeistelHashVestaCircuit,
+    },
+    mimc_feistel_cipher::{
+        MiMC5FeistelCipherCircuit, MiMC5FeistelCipherPallasCircuit, MiMC5FeistelCipherVestaCircuit,
+    },
+};
+use crate::field::MiMCField;
+
+
+// A MiMC circuit that can be proven with `MiMCProver`. `K` is the smallest
+// log2 number of rows that fits one invocation of the chip.
+pub trait MiMCStatement<F: FieldExt>: Circuit<F> + Default {
+    const K: u32;
+
+    fn public_inputs(&self) -> Vec<F>;
+}
+
+impl<F: MiMCField> MiMCStatement<F> for MiMC5HashCircuit<F> {
+    const K: u32 = 7;
+
+    fn public_inputs(&self) -> Vec<F> {
+        MiMC5HashCircuit::public_inputs(self)
+    }
+}
+
+impl<F: MiMCField> MiMCStatement<F> for MiMC5CipherCircuit<F> {
+    const K: u32 = 7;
+
+    fn public_inputs(&self) -> Vec<F> {
+        MiMC5CipherCircuit::public_inputs(self)
+    }
+}
+
+impl<F: MiMCField> MiMCStatement<F> for MiMC5FeistelHashCircuit<F> {
+    const K: u32 = 8;
+
+    fn public_inputs(&self) -> Vec<F> {
+        MiMC5FeistelHashCircuit::public_inputs(self)
+    }
+}
+
+impl<F: MiMCField> MiMCStatement<F> for MiMC5FeistelCipherCircuit<F> {
+    const K: u32 = 8;
+
+    fn public_inputs(&self) -> Vec<F> {
+        MiMC5FeistelCipherCircuit::public_inputs(self)
+    }
+}
+
+// Holds the IPA parameters and proving key for one MiMC circuit and produces
+// Blake2b-transcript proofs as bytes.
+//
+// Keys are generated from a circuit "shape": the visibility of its inputs
+// and outputs. Statements proven later must have the same shape.
+pub struct MiMCProver<C: CurveAffine, S: MiMCStatement<C::Scalar>> {
+    params: Params<C>,
+    pk: ProvingKey<C>,
+    _marker: PhantomData<S>,
+}
+
+impl<C: CurveAffine, S: MiMCStatement<C::Scalar>> MiMCProver<C, S> {
+    // Sets up a prover for the default shape of the statement
+    pub fn new() -> Result<Self, Error> {
+        Self::with_shape(&S::default())
+    }
+
+    pub fn with_shape(shape: &S) -> Result<Self, Error> {
+        Self::with_params(Params::new(S::K), shape)
+    }
+
+    pub fn with_params(params: Params<C>, shape: &S) -> Result<Self, Error> {
+        let empty_circuit = shape.without_witnesses();
+        let vk = keygen_vk(&params, &empty_circuit)?;
+        let pk = keygen_pk(&params, vk, &empty_circuit)?;
+        Ok(Self {
+            params,
+            pk,
+            _marker: PhantomData,
+        })
+    }
+
+    // Loads params written by `write_artifacts` and re-runs keygen, rejecting
+    // the key if it differs from the key pin, e.g. after a layout change
+    pub fn read_artifacts<R: io::Read>(
+        params_reader: &mut R,
+        key_pin_reader: &mut R,
+        shape: &S,
+    ) -> Result<Self, ArtifactError> {
+        let params = read_params(params_reader)?;
+        let pk = keygen_pk_pinned(key_pin_reader, &params, shape)?;
+        Ok(Self {
+            params,
+            pk,
+            _marker: PhantomData,
+        })
+    }
+
+    pub fn write_artifacts<W: io::Write>(
+        &self,
+        params_writer: &mut W,
+        key_pin_writer: &mut W,
+    ) -> Result<(), ArtifactError> {
+        write_params(&self.params, params_writer)?;
+        write_key_pin::<C, S, W>(&self.params, self.pk.get_vk(), key_pin_writer)
+    }
+
+    pub fn params(&self) -> &Params<C> {
+        &self.params
+    }
+
+    pub fn proving_key(&self) -> &ProvingKey<C> {
+        &self.pk
+    }
+
+    pub fn verifying_key(&self) -> &VerifyingKey<C> {
+        self.pk.get_vk()
+    }
+
+    pub fn prove(&self, statement: &S) -> Result<Vec<u8>, Error> {
+        self.prove_with_rng(statement, OsRng)
+    }
+
+    pub fn prove_with_rng(&self, statement: &S, rng: impl RngCore) -> Result<Vec<u8>, Error> {
+        let public_inputs = statement.public_inputs();
+        let mut transcript = Blake2bWrite::<_, _, Challenge255<_>>::init(vec![]);
+        create_proof(
+            &self.params,
+            &self.pk,
+            std::slice::from_ref(statement),
+            &[&[&public_inputs]],
+            rng,
+            &mut transcript,
+        )?;
+        Ok(transcript.finalize())
+    }
+
+    // A verifier sharing this prover's parameters and verifying key
+    pub fn verifier(&self) -> MiMCVerifier<C, S> {
+        MiMCVerifier {
+            params: self.params.clone(),
+            vk: self.pk.get_vk().clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+// Holds the IPA parameters and verifying key for one MiMC circuit and checks
+// proofs produced by `MiMCProver`.
+pub struct MiMCVerifier<C: CurveAffine, S: MiMCStatement<C::Scalar>> {
+    params: Params<C>,
+    vk: VerifyingKey<C>,
+    _marker: PhantomData<S>,
+}
+
+impl<C: CurveAffine, S: MiMCStatement<C::Scalar>> MiMCVerifier<C, S> {
+    pub fn new() -> Result<Self, Error> {
+        Self::with_shape(&S::default())
+    }
+
+    pub fn with_shape(shape: &S) -> Result<Self, Error> {
+        Self::with_params(Params::new(S::K), shape)
+    }
+
+    pub fn with_params(params: Params<C>, shape: &S) -> Result<Self, Error> {
+        let vk = keygen_vk(&params, &shape.without_witnesses())?;
+        Ok(Self {
+            params,
+            vk,
+            _marker: PhantomData,
+        })
+    }
+
+    pub fn read_artifacts<R: io::Read>(
+        params_reader: &mut R,
+        key_pin_reader: &mut R,
+        shape: &S,
+    ) -> Result<Self, ArtifactError> {
+        let params = read_params(params_reader)?;
+        let vk = keygen_vk_pinned(key_pin_reader, &params, shape)?;
+        Ok(Self {
+            params,
+            vk,
+            _marker: PhantomData,
+        })
+    }
+
+    pub fn write_artifacts<W: io::Write>(
+        &self,
+        params_writer: &mut W,
+        key_pin_writer: &mut W,
+    ) -> Result<(), ArtifactError> {
+        write_params(&self.params, params_writer)?;
+        write_key_pin::<C, S, W>(&self.params, &self.vk, key_pin_writer)
+    }
+
+    pub fn params(&self) -> &Params<C> {
+        &self.params
+    }
+
+    pub fn verifying_key(&self) -> &VerifyingKey<C> {
+        &self.vk
+    }
+
+    pub fn verify(&self, public_inputs: &[C::Scalar], proof: &[u8]) -> Result<(), Error> {
+        let strategy = SingleVerifier::new(&self.params);
+        let mut transcript = Blake2bRead::<_, _, Challenge255<_>>::init(proof);
+        verify_proof(&self.params, &self.vk, strategy, &[&[public_inputs]], &mut transcript)
+    }
+
+    // Verifies many (public inputs, proof) pairs with a single accumulated MSM.
+    // If the batch fails it is bisected to find the failing proofs, so a batch
+    // with few bad proofs still costs only a few extra batch checks.
+    pub fn verify_batch(&self, proofs: &[(&[C::Scalar], &[u8])]) -> Result<(), BatchVerificationError> {
+        let indices: Vec<usize> = (0..proofs.len()).collect();
+        if self.batch_holds(proofs, &indices) {
+            return Ok(());
+        }
+        let mut failed = vec![];
+        self.find_failures(proofs, &indices, &mut failed);
+        Err(BatchVerificationError { failed })
+    }
+
+    fn batch_holds(&self, proofs: &[(&[C::Scalar], &[u8])], indices: &[usize]) -> bool {
+        let mut batch = BatchVerifier::new();
+        for &i in indices {
+            let (public_inputs, proof) = proofs[i];
+            batch.add_proof(vec![vec![public_inputs.to_vec()]], proof.to_vec());
+        }
+        batch.finalize(&self.params, &self.vk)
+    }
+
+    // Collects the failing proofs among `indices`, which are known to fail as a batch
+    fn find_failures(&self, proofs: &[(&[C::Scalar], &[u8])], indices: &[usize], failed: &mut Vec<usize>) {
+        if let [i] = indices {
+            let (public_inputs, proof) = proofs[*i];
+            if self.verify(public_inputs, proof).is_err() {
+                failed.push(*i);
+            }
+            return;
+        }
+        let (left, right) = indices.split_at(indices.len() / 2);
+        for half in [left, right] {
+            if !self.batch_holds(proofs, half) {
+                self.find_failures(proofs, half, failed);
+            }
+        }
+    }
+}
+
+// The positions, in the order given, of the proofs that failed batch verification
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatchVerificationError {
+    pub failed: Vec<usize>,
+}
+
+impl fmt::Display for BatchVerificationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} proof(s) failed batch verification: {:?}", self.failed.len(), self.failed)
+    }
+}
+
+impl std::error::Error for BatchVerificationError {}
+
+// Circuits over the Pallas base field are proven with Vesta commitments and vice versa
+pub type MiMC5HashPallasProver = MiMCProver<vesta::Affine, MiMC5HashPallasCircuit>;
+pub type MiMC5HashVestaProver = MiMCProver<pallas::Affine, MiMC5HashVestaCircuit>;
+pub type MiMC5CipherPallasProver = MiMCProver<vesta::Affine, MiMC5CipherPallasCircuit>;
+pub type MiMC5CipherVestaProver = MiMCProver<pallas::Affine, MiMC5CipherVestaCircuit>;
+pub type MiMC5FeistelHashPallasProver = MiMCProver<vesta::Affine, MiMC5FeistelHashPallasCircuit>;
+pub type MiMC5FeistelHashVestaProver = MiMCProver<pallas::Affine, MiMC5FeistelHashVestaCircuit>;
+pub type MiMC5FeistelCipherPallasProver = MiMCProver<vesta::Affine, MiMC5FeistelCipherPallasCircuit>;
+pub type MiMC5FeistelCipherVestaProver = MiMCProver<pallas::Affine, MiMC5FeistelCipherVestaCircuit>;
+
+pub type MiMC5HashPallasVerifier = MiMCVerifier<vesta::Affine, MiMC5HashPallasCircuit>;
+pub type MiMC5HashVestaVerifier = MiMCVerifier<pallas::Affine, MiMC5HashVestaCircuit>;
+pub type MiMC5CipherPallasVerifier = MiMCVerifier<vesta::Affine, MiMC5CipherPallasCircuit>;
+pub type MiMC5CipherVestaVerifier = MiMCVerifier<pallas::Affine, MiMC5CipherVestaCircuit>;
+pub type MiMC5FeistelHashPallasVerifier = MiMCVerifier<vesta::Affine, MiMC5FeistelHashPallasCircuit>;
+pub type MiMC5FeistelHashVestaVerifier = MiMCVerifier<pallas::Affine, MiMC5FeistelHashVestaCircuit>;
+pub type MiMC5FeistelCipherPallasVerifier = MiMCVerifier<vesta::Affine, MiMC5FeistelCipherPallasCircuit>;
+pub type MiMC5FeistelCipherVestaVerifier = MiMCVerifier<pallas::Affine, MiMC5FeistelCipherVestaCircuit>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pasta_curves::{Fp, Fq};
+
+    #[test]
+    fn test_mimc5_pallas_hash_prover() {
+        let prover = MiMC5HashPallasProver::new().unwrap();
+        let statement = MiMC5HashPallasCircuit::new(Fp::from(1));
+        let proof = prover.prove(&statement).unwrap();
+
+        let verifier = MiMC5HashPallasVerifier::new().unwrap();
+        assert!(verifier.verify(&statement.public_inputs(), &proof).is_ok());
+
+        let wrong_hash = [statement.message_hash() + Fp::one()];
+        assert!(verifier.verify(&wrong_hash, &proof).is_err());
+    }
+
+    #[test]
+    fn test_mimc5_feistel_vesta_hash_prover() {
+        let prover = MiMC5FeistelHashVestaProver::new().unwrap();
+        let statement = MiMC5FeistelHashVestaCircuit::new(Fq::from(1), Fq::from(2));
+        let proof = prover.prove(&statement).unwrap();
+
+        let verifier = prover.verifier();
+        assert!(verifier.verify(&statement.public_inputs(), &proof).is_ok());
+    }
+
+    #[test]
+    fn test_mimc5_pallas_cipher_prover_artifacts() {
+        let prover = MiMC5CipherPallasProver::new().unwrap();
+        let mut params_bytes = vec![];
+        let mut key_bytes = vec![];
+        prover.write_artifacts(&mut params_bytes, &mut key_bytes).unwrap();
+
+        let shape = MiMC5CipherPallasCircuit::default();
+        let prover = MiMC5CipherPallasProver::read_artifacts(&mut &params_bytes[..], &mut &key_bytes[..], &shape).unwrap();
+        let verifier = MiMC5CipherPallasVerifier::read_artifacts(&mut &params_bytes[..], &mut &key_bytes[..], &shape).unwrap();
+
+        let statement = MiMC5CipherPallasCircuit::new(Fp::from(1), Fp::from(2));
+        let proof = prover.prove(&statement).unwrap();
+        assert!(verifier.verify(&statement.public_inputs(), &proof).is_ok());
+    }
+
+    #[test]
+    fn test_mimc5_vesta_hash_batch_verification() {
+        let prover = MiMC5HashVestaProver::new().unwrap();
+        let verifier = prover.verifier();
+        let statements: Vec<_> = (0..4).map(|i| MiMC5HashVestaCircuit::new(Fq::from(i))).collect();
+        let proofs: Vec<_> = statements.iter().map(|s| prover.prove(s).unwrap()).collect();
+        let mut public_inputs: Vec<_> = statements.iter().map(|s| s.public_inputs()).collect();
+
+        let batch = |public_inputs: &[Vec<Fq>]| {
+            let items: Vec<_> = public_inputs.iter().zip(&proofs).map(|(p, proof)| (&p[..], &proof[..])).collect();
+            verifier.verify_batch(&items)
+        };
+        assert_eq!(batch(&public_inputs), Ok(()));
+        assert_eq!(verifier.verify_batch(&[]), Ok(()));
+
+        // Claim wrong hashes for two of the proofs
+        public_inputs[1][0] += Fq::one();
+        public_inputs[3][0] += Fq::one();
+        assert_eq!(batch(&public_inputs), Err(BatchVerificationError { failed: vec![1, 3] }));
+    }
+}
\ No newline at end of file
@@ -0,0 +1,165 @@
+// A deterministic RNG that runs MiMC5 in counter mode: the i-th output block
+// is mimc5_encrypt(i, key), with the counter starting at zero. Every output
+// can be recomputed inside a circuit with the MiMC5 cipher chip, using the
+// counter as the message and the seed key as the key.
+//
+// `next_field` returns whole blocks. The byte stream takes the low 31 bytes
+// of each block, which are close to uniform because the Pasta moduli are
+// just above 2^254. The two views share the counter, so interleaving them
+// skips blocks.
+use pasta_curves::{group::ff::PrimeField, Fp, Fq};
+use rand_core::{impls, Error, RngCore, SeedableRng};
+
+use crate::field::MiMCField;
+
+// Bytes of each block used by the byte stream
+pub const BYTES_PER_BLOCK: usize = 31;
+
+#[derive(Debug, Clone)]
+pub struct MiMCRng<F: MiMCField + PrimeField<Repr = [u8; 32]>> {
+    key: F,
+    counter: u64,
+    // Unread bytes of the last block, consumed from the front
+    buffer: [u8; BYTES_PER_BLOCK],
+    buffer_pos: usize,
+}
+
+pub type MiMCRngPallas = MiMCRng<Fp>;
+pub type MiMCRngVesta = MiMCRng<Fq>;
+
+impl<F: MiMCField + PrimeField<Repr = [u8; 32]>> MiMCRng<F> {
+    pub fn from_key(key: F) -> Self {
+        Self {
+            key,
+            counter: 0,
+            buffer: [0; BYTES_PER_BLOCK],
+            buffer_pos: BYTES_PER_BLOCK,
+        }
+    }
+
+    pub fn key(&self) -> F {
+        self.key
+    }
+
+    // The counter of the next block
+    pub fn counter(&self) -> u64 {
+        self.counter
+    }
+
+    // The output block for a counter, independent of the RNG state
+    pub fn block(&self, counter: u64) -> F {
+        let mut state = F::from(counter);
+        F::encrypt(&mut state, self.key);
+        state
+    }
+
+    pub fn next_field(&mut self) -> F {
+        let block = self.block(self.counter);
+        self.counter = self.counter.checked_add(1).expect("MiMC RNG counter overflow");
+        block
+    }
+}
+
+impl<F: MiMCField + PrimeField<Repr = [u8; 32]>> SeedableRng for MiMCRng<F> {
+    type Seed = [u8; 32];
+
+    // The key is the seed read as a little-endian integer reduced modulo p
+    fn from_seed(seed: Self::Seed) -> Self {
+        let mut wide = [0u8; 64];
+        wide[..32].copy_from_slice(&seed);
+        Self::from_key(F::from_bytes_wide(&wide))
+    }
+}
+
+impl<F: MiMCField + PrimeField<Repr = [u8; 32]>> RngCore for MiMCRng<F> {
+    fn next_u32(&mut self) -> u32 {
+        impls::next_u32_via_fill(self)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        impls::next_u64_via_fill(self)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        let mut filled = 0;
+        while filled < dest.len() {
+            if self.buffer_pos == BYTES_PER_BLOCK {
+                let block = self.next_field().to_repr();
+                self.buffer.copy_from_slice(&block[..BYTES_PER_BLOCK]);
+                self.buffer_pos = 0;
+            }
+            let n = (dest.len() - filled).min(BYTES_PER_BLOCK - self.buffer_pos);
+            dest[filled..filled + n].copy_from_slice(&self.buffer[self.buffer_pos..self.buffer_pos + n]);
+            self.buffer_pos += n;
+            filled += n;
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use halo2_proofs::dev::MockProver;
+
+    use crate::circuits::{mimc_cipher::MiMC5CipherPallasCircuit, Visibility};
+    use crate::mimc::primitives::mimc5_encrypt_pallas;
+
+    #[test]
+    fn test_mimc_rng_outputs() {
+        let mut rng = MiMCRngPallas::seed_from_u64(7);
+        let mut same_seed = MiMCRngPallas::seed_from_u64(7);
+        let mut other_seed = MiMCRngPallas::seed_from_u64(8);
+
+        let first = rng.next_field();
+        assert_eq!(first, same_seed.next_field());
+        assert_ne!(first, other_seed.next_field());
+        assert_ne!(first, rng.next_field());
+        assert_eq!(rng.counter(), 2);
+
+        // Outputs are MiMC5 encryptions of the counter under the key
+        let mut expected = Fp::zero();
+        mimc5_encrypt_pallas(&mut expected, rng.key());
+        assert_eq!(first, expected);
+
+        // The byte stream does not depend on how it is split into reads
+        let mut whole = [0u8; 100];
+        MiMCRngVesta::from_seed([1; 32]).fill_bytes(&mut whole);
+        let mut chunked = [0u8; 100];
+        let mut split = MiMCRngVesta::from_seed([1; 32]);
+        for chunk in chunked.chunks_mut(7) {
+            split.fill_bytes(chunk);
+        }
+        assert_eq!(whole, chunked);
+        assert_eq!(split.counter(), 4);
+
+        let block_bytes = MiMCRngVesta::from_seed([1; 32]).next_field().to_repr();
+        assert_eq!(whole[..BYTES_PER_BLOCK], block_bytes[..BYTES_PER_BLOCK]);
+    }
+
+    #[test]
+    fn test_mimc_rng_output_recomputed_in_circuit() {
+        let k = 7;
+        let mut rng = MiMCRngPallas::seed_from_u64(42);
+        rng.next_field();
+        let output = rng.next_field();
+
+        // The counter is public and the key stays private
+        let circuit = MiMC5CipherPallasCircuit::with_visibility(
+            Fp::from(1),
+            rng.key(),
+            Visibility::Public,
+            Visibility::Private,
+            Visibility::Public,
+        );
+        let prover = MockProver::run(k, &circuit, vec![vec![Fp::from(1), output]]).unwrap();
+        prover.assert_satisfied();
+
+        let prover = MockProver::run(k, &circuit, vec![vec![Fp::from(0), output]]).unwrap();
+        assert!(prover.verify().is_err());
+    }
+}
@@ -0,0 +1,274 @@
+// A Fiat-Shamir transcript backed by the MiMC5 Feistel sponge, as an
+// algebraic alternative to Blake2bWrite/Blake2bRead.
+//
+// The sponge works over the base field of the commitment curve, so points are
+// absorbed as their two coordinates. Scalars live in the other Pasta field and
+// are absorbed as two 128-bit limbs, low limb first. A challenge is a squeezed
+// base field element; its scalar is the element's bytes reduced modulo the
+// scalar field. Proof bytes are the same as with the Blake2b transcript:
+// compressed points and canonical scalars.
+use std::io::{self, Read, Write};
+use std::marker::PhantomData;
+
+use halo2_proofs::{
+    arithmetic::{Coordinates, CurveAffine, FieldExt},
+    transcript::{EncodedChallenge, Transcript, TranscriptRead, TranscriptWrite},
+};
+use pasta_curves::group::ff::PrimeField;
+
+use crate::field::MiMCField;
+use crate::mimc_feistel::sponge::MiMCSponge;
+
+// Initial capacity of the transcript sponge
+pub const TRANSCRIPT_DOMAIN: u64 = u64::from_le_bytes(*b"MiMCTrns");
+
+// The base field elements absorbed for a point: its affine coordinates
+pub fn point_to_base_elements<C: CurveAffine>(point: C) -> io::Result<[C::Base; 2]> {
+    let coordinates: Coordinates<C> = Option::from(point.coordinates()).ok_or_else(|| {
+        io::Error::other("cannot write points at infinity to the transcript")
+    })?;
+    Ok([*coordinates.x(), *coordinates.y()])
+}
+
+// The base field elements absorbed for a scalar: its low and high 128 bits
+pub fn scalar_to_base_elements<C: CurveAffine>(scalar: C::Scalar) -> [C::Base; 2] {
+    let repr = scalar.to_repr();
+    let bytes = repr.as_ref();
+    let limb = |half: &[u8]| C::Base::from_u128(u128::from_le_bytes(half.try_into().unwrap()));
+    [limb(&bytes[..16]), limb(&bytes[16..32])]
+}
+
+// A challenge squeezed from the MiMC transcript
+#[derive(Copy, Clone, Debug)]
+pub struct MiMCChallenge<C: CurveAffine> {
+    base: C::Base,
+    scalar: C::Scalar,
+}
+
+impl<C: CurveAffine> MiMCChallenge<C> {
+    // The squeezed base field element the challenge was derived from
+    pub fn base(&self) -> C::Base {
+        self.base
+    }
+}
+
+impl<C: CurveAffine> EncodedChallenge<C> for MiMCChallenge<C> {
+    type Input = C::Base;
+
+    fn new(challenge_input: &C::Base) -> Self {
+        let mut wide = [0u8; 64];
+        wide[..32].copy_from_slice(challenge_input.to_repr().as_ref());
+        Self {
+            base: *challenge_input,
+            scalar: C::Scalar::from_bytes_wide(&wide),
+        }
+    }
+
+    fn get_scalar(&self) -> C::Scalar {
+        self.scalar
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MiMCWrite<W: Write, C: CurveAffine>
+where
+    C::Base: MiMCField,
+{
+    sponge: MiMCSponge<C::Base>,
+    writer: W,
+    _marker: PhantomData<C>,
+}
+
+impl<W: Write, C: CurveAffine> MiMCWrite<W, C>
+where
+    C::Base: MiMCField,
+{
+    pub fn init(writer: W) -> Self {
+        Self {
+            sponge: MiMCSponge::new(TRANSCRIPT_DOMAIN),
+            writer,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn finalize(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write, C: CurveAffine> Transcript<C, MiMCChallenge<C>> for MiMCWrite<W, C>
+where
+    C::Base: MiMCField,
+{
+    fn squeeze_challenge(&mut self) -> MiMCChallenge<C> {
+        MiMCChallenge::new(&self.sponge.squeeze())
+    }
+
+    fn common_point(&mut self, point: C) -> io::Result<()> {
+        for value in point_to_base_elements(point)? {
+            self.sponge.absorb(value);
+        }
+        Ok(())
+    }
+
+    fn common_scalar(&mut self, scalar: C::Scalar) -> io::Result<()> {
+        for value in scalar_to_base_elements::<C>(scalar) {
+            self.sponge.absorb(value);
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write, C: CurveAffine> TranscriptWrite<C, MiMCChallenge<C>> for MiMCWrite<W, C>
+where
+    C::Base: MiMCField,
+{
+    fn write_point(&mut self, point: C) -> io::Result<()> {
+        self.common_point(point)?;
+        self.writer.write_all(point.to_bytes().as_ref())
+    }
+
+    fn write_scalar(&mut self, scalar: C::Scalar) -> io::Result<()> {
+        self.common_scalar(scalar)?;
+        self.writer.write_all(scalar.to_repr().as_ref())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MiMCRead<R: Read, C: CurveAffine>
+where
+    C::Base: MiMCField,
+{
+    sponge: MiMCSponge<C::Base>,
+    reader: R,
+    _marker: PhantomData<C>,
+}
+
+impl<R: Read, C: CurveAffine> MiMCRead<R, C>
+where
+    C::Base: MiMCField,
+{
+    pub fn init(reader: R) -> Self {
+        Self {
+            sponge: MiMCSponge::new(TRANSCRIPT_DOMAIN),
+            reader,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<R: Read, C: CurveAffine> Transcript<C, MiMCChallenge<C>> for MiMCRead<R, C>
+where
+    C::Base: MiMCField,
+{
+    fn squeeze_challenge(&mut self) -> MiMCChallenge<C> {
+        MiMCChallenge::new(&self.sponge.squeeze())
+    }
+
+    fn common_point(&mut self, point: C) -> io::Result<()> {
+        for value in point_to_base_elements(point)? {
+            self.sponge.absorb(value);
+        }
+        Ok(())
+    }
+
+    fn common_scalar(&mut self, scalar: C::Scalar) -> io::Result<()> {
+        for value in scalar_to_base_elements::<C>(scalar) {
+            self.sponge.absorb(value);
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read, C: CurveAffine> TranscriptRead<C, MiMCChallenge<C>> for MiMCRead<R, C>
+where
+    C::Base: MiMCField,
+{
+    fn read_point(&mut self) -> io::Result<C> {
+        let mut compressed = C::Repr::default();
+        self.reader.read_exact(compressed.as_mut())?;
+        let point: C = Option::from(C::from_bytes(&compressed))
+            .ok_or_else(|| io::Error::other("invalid point encoding in proof"))?;
+        self.common_point(point)?;
+        Ok(point)
+    }
+
+    fn read_scalar(&mut self) -> io::Result<C::Scalar> {
+        let mut data = <C::Scalar as PrimeField>::Repr::default();
+        self.reader.read_exact(data.as_mut())?;
+        let scalar: C::Scalar = Option::from(C::Scalar::from_repr(data))
+            .ok_or_else(|| io::Error::other("invalid field element encoding in proof"))?;
+        self.common_scalar(scalar)?;
+        Ok(scalar)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use halo2_proofs::{
+        arithmetic::Field,
+        plonk::{create_proof, keygen_pk, keygen_vk, verify_proof, SingleVerifier},
+        poly::commitment::Params,
+    };
+    use pasta_curves::group::prime::PrimeCurveAffine;
+    use pasta_curves::{pallas, vesta, Fp, Fq};
+    use rand_core::OsRng;
+
+    use crate::circuits::{mimc_hash::MiMC5HashPallasCircuit, mimc_feistel_cipher::MiMC5FeistelCipherVestaCircuit};
+    use crate::prover::MiMCStatement;
+
+    fn round_trip<C: CurveAffine, S: MiMCStatement<C::Scalar>>(statement: S) -> (bool, bool)
+    where
+        C::Base: MiMCField,
+    {
+        let params: Params<C> = Params::new(S::K);
+        let vk = keygen_vk(&params, &statement.without_witnesses()).unwrap();
+        let pk = keygen_pk(&params, vk, &statement.without_witnesses()).unwrap();
+        let public_inputs = statement.public_inputs();
+
+        let mut transcript = MiMCWrite::<_, C>::init(vec![]);
+        create_proof(&params, &pk, &[statement], &[&[&public_inputs]], OsRng, &mut transcript).unwrap();
+        let proof = transcript.finalize();
+
+        let verify = |public_inputs: &[C::Scalar]| {
+            let strategy = SingleVerifier::new(&params);
+            let mut transcript = MiMCRead::<_, C>::init(&proof[..]);
+            verify_proof(&params, pk.get_vk(), strategy, &[&[public_inputs]], &mut transcript).is_ok()
+        };
+        let mut wrong_inputs = public_inputs.clone();
+        wrong_inputs[0] += C::Scalar::one();
+        (verify(&public_inputs), verify(&wrong_inputs))
+    }
+
+    #[test]
+    fn test_mimc_transcript_pallas_hash_proof() {
+        let (valid, wrong_inputs_valid) = round_trip::<vesta::Affine, _>(MiMC5HashPallasCircuit::new(Fp::from(1)));
+        assert!(valid);
+        assert!(!wrong_inputs_valid);
+    }
+
+    #[test]
+    fn test_mimc_transcript_vesta_feistel_cipher_proof() {
+        let circuit = MiMC5FeistelCipherVestaCircuit::new(Fq::from(1), Fq::from(2), Fq::from(3));
+        let (valid, wrong_inputs_valid) = round_trip::<pallas::Affine, _>(circuit);
+        assert!(valid);
+        assert!(!wrong_inputs_valid);
+    }
+
+    #[test]
+    fn test_mimc_transcript_agrees_between_prover_and_verifier() {
+        let point = pallas::Affine::generator();
+        let scalar = Fq::from(5);
+        let mut writer = MiMCWrite::<_, pallas::Affine>::init(vec![]);
+        writer.write_point(point).unwrap();
+        writer.write_scalar(scalar).unwrap();
+        let written = writer.squeeze_challenge().get_scalar();
+        let proof = writer.finalize();
+
+        let mut reader = MiMCRead::<_, pallas::Affine>::init(&proof[..]);
+        assert_eq!(reader.read_point().unwrap(), point);
+        assert_eq!(reader.read_scalar().unwrap(), scalar);
+        assert_eq!(reader.squeeze_challenge().get_scalar(), written);
+    }
+}